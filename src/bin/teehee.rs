@@ -1,25 +1,70 @@
 #![deny(clippy::all)]
 
 use std::io::{stdout, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process;
+use teehee::config::Config;
 use teehee::hex_view::view::HexView;
 use teehee::{Buffer, Buffers};
 
 const STDOUT_BUF: usize = 8192;
 
+fn read_file(filename: &str) -> Result<Vec<u8>, String> {
+    // Reading a directory fails with a less helpful message, or not at all on some platforms
+    if Path::new(filename).is_dir() {
+        return Err(format!("{} is a directory", filename));
+    }
+    std::fs::read(filename).map_err(|e| format!("couldn't read {}: {}", filename, e))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("teehee: {}", message);
+    process::exit(1);
+}
+
 fn main() {
+    let mut filename = None;
+    let mut alt_screen = true;
+    let mut config_path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-altscreen" => alt_screen = false,
+            "--config" => match args.next() {
+                Some(path) => config_path = Some(PathBuf::from(path)),
+                None => fail("--config expects a path"),
+            },
+            flag if flag.starts_with("--") => fail(&format!("unknown option {}", flag)),
+            _ if filename.is_some() => fail("expected at most one file"),
+            _ => filename = Some(arg),
+        }
+    }
+    let buffers = match &filename {
+        Some(filename) => match read_file(filename) {
+            Ok(data) => Buffers::with_buffer(Buffer::from_data_and_path(data, Some(filename))),
+            Err(e) => fail(&e),
+        },
+        None => Buffers::default(),
+    };
+    let mut view = HexView::with_buffers(buffers);
+    view.set_alt_screen(alt_screen);
+
+    let config = config_path
+        .or_else(Config::default_path)
+        .map(Config::load)
+        .unwrap_or_else(|| Ok(Config::new()));
+    let warnings = match config {
+        Ok(config) => config.apply(),
+        Err(e) => vec![format!("config: {}", e)],
+    };
+    if !warnings.is_empty() {
+        view.set_info(warnings.join("; "));
+    }
+
     let stdout = stdout();
     let mut stdout = BufWriter::with_capacity(STDOUT_BUF, stdout.lock());
-    let filename = std::env::args().nth(1);
-    let buffers = filename
-        .as_ref()
-        .map(|filename| {
-            Buffers::with_buffer(Buffer::from_data_and_path(
-                std::fs::read(&filename).expect("Couldn't read file"),
-                Some(filename),
-            ))
-        })
-        .unwrap_or_else(Buffers::new);
-    let view = HexView::with_buffers(buffers);
-
-    view.run_event_loop(&mut stdout).unwrap();
+    if let Err(e) = view.run_event_loop(&mut stdout) {
+        drop(stdout);
+        fail(&e.to_string());
+    }
 }
@@ -1,12 +1,21 @@
 use xi_rope::Interval;
 
+use std::cmp;
 use std::collections::HashMap;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 
 use super::byte_rope::*;
-use super::history::History;
+use super::cancel::{CancelCheck, Cancelled};
+use super::history::{invert_delta, History};
+use super::patch::Patch;
 use crate::modes::mode::DirtyBytes;
+use crate::modes::search::Pattern;
+use crate::operations as ops;
 use crate::selection::{SelRegion, Selection};
+use crate::ViewOptions;
+
+const MAX_JUMPLIST_LEN: usize = 100;
 
 #[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
 pub enum OverflowSelectionStyle {
@@ -15,32 +24,88 @@ pub enum OverflowSelectionStyle {
     CursorTail,
 }
 
+// A saved set of selections, drawn in its own color alongside the live selection
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub name: String,
+    pub selection: Selection,
+    pub visible: bool,
+}
+
+// Where the bytes of a carved buffer were copied from, so that they can be spliced back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Carve {
+    // None once the parent buffer has been closed
+    pub parent: Option<usize>,
+    pub parent_name: String,
+    pub start: usize,
+    pub len: usize,
+}
+
 #[derive(Default)]
 pub struct Buffer {
     pub path: Option<PathBuf>,
     pub data: Rope,
     pub selection: Selection,
     pub registers: HashMap<char, Vec<Vec<u8>>>,
+    // Where each entry of a register was yanked from, kept until the register is replaced
+    pub register_origins: HashMap<char, Vec<usize>>,
     pub dirty: bool,
+    // Contents as loaded or last written, used for showing changes
+    pub saved_data: Rope,
+    pub patch: Patch,
+    pub recording_patch: bool,
+    // Drawn in order, so later layers cover earlier ones
+    pub layers: Vec<Layer>,
+    pub carve: Option<Carve>,
 
     history: History,
+    // Positions to return to with `jump_back`, most recent last
+    jumplist: Vec<usize>,
+    // Reverts the half byte being entered in hex insert mode, until another change is made
+    half_byte_undo: Option<HalfByteUndo>,
+}
+
+// What the buffer was like before a half byte was entered, so that dropping it leaves no trace
+struct HalfByteUndo {
+    delta: RopeDelta,
+    selection: Selection,
+    dirty: bool,
+    // Whether the half byte was added to an undo step being gathered, rather than starting one
+    had_partial: bool,
 }
 
 impl Buffer {
     pub fn from_data_and_path(data: Vec<u8>, path: Option<impl Into<PathBuf>>) -> Buffer {
+        let data: Rope = data.into();
         Buffer {
-            data: data.into(),
+            saved_data: data.clone(),
+            data,
             selection: Selection::new(),
             registers: HashMap::new(),
+            register_origins: HashMap::new(),
             dirty: false,
             path: path.map(Into::into),
+            patch: Patch::new(),
+            recording_patch: false,
+            layers: vec![],
+            carve: None,
             history: History::new(),
+            jumplist: vec![],
+            half_byte_undo: None,
         }
     }
 
     pub fn name(&self) -> String {
         if let Some(path) = &self.path {
             format!("{}", path.display())
+        } else if let Some(carve) = &self.carve {
+            format!(
+                "{}[0x{:x}..0x{:x}]",
+                carve.parent_name,
+                carve.start,
+                carve.start + carve.len
+            )
         } else {
             "*scratch*".to_string()
         }
@@ -56,7 +121,7 @@ impl Buffer {
             }
             new
         });
-        invalidated_ranges.sort_by(|a, b| a.start.cmp(&b.start));
+        invalidated_ranges.sort_by_key(|r| r.start);
 
         let mut disjoint_invalidated_ranges = Vec::new();
         for r in invalidated_ranges {
@@ -74,8 +139,21 @@ impl Buffer {
         DirtyBytes::ChangeInPlace(disjoint_invalidated_ranges)
     }
 
+    fn record_patch_step(&mut self, delta: &RopeDelta) {
+        if self.recording_patch {
+            self.patch.record(delta);
+        }
+    }
+
     fn apply_delta_to_buffer(&mut self, delta: RopeDelta, is_final: bool) {
+        self.half_byte_undo = None;
+        if self.data.is_unchanged_by(&delta) {
+            // Nothing to undo and nothing to save
+            return;
+        }
+        self.record_patch_step(&delta);
         let next_data = self.data.apply_delta(&delta);
+        self.move_layers(&delta);
         if is_final {
             self.history
                 .perform_final(&self.data, delta, self.selection.clone());
@@ -87,12 +165,41 @@ impl Buffer {
         self.dirty = true;
     }
 
+    // Everything from the first changed byte on may have moved, up to the end of the longer of
+    // the old and new data, plus the row of the overflow cursor. Bytes and selections before
+    // that look the same, so their rows don't need to be redrawn.
+    fn dirty_after_delta(
+        &self,
+        delta: &RopeDelta,
+        old_len: usize,
+        old_selection: &Selection,
+    ) -> DirtyBytes {
+        let (changed, _) = delta.summary();
+        let start = match old_selection.first_difference(&self.selection) {
+            Some(offset) => cmp::min(offset, changed.start),
+            None => changed.start,
+        };
+        let end = cmp::max(old_len, self.data.len()) + 1;
+        DirtyBytes::ChangeInPlace(vec![Interval::new(start, end)])
+    }
+
     pub fn apply_delta(&mut self, delta: RopeDelta) -> DirtyBytes {
         let max_len = self.data.len();
+        let old_selection = self.selection.clone();
         self.apply_delta_to_buffer(delta.clone(), true);
         self.selection.apply_delta(&delta, max_len);
 
-        DirtyBytes::ChangeLength
+        self.dirty_after_delta(&delta, max_len, &old_selection)
+    }
+
+    // Applies the delta, keeping each region over the bytes that replaced its own
+    pub fn apply_delta_covering(&mut self, delta: RopeDelta) -> DirtyBytes {
+        let max_len = self.data.len();
+        let old_selection = self.selection.clone();
+        self.apply_delta_to_buffer(delta.clone(), true);
+        self.selection.apply_delta_covering(&delta, max_len);
+
+        self.dirty_after_delta(&delta, max_len, &old_selection)
     }
 
     pub fn apply_delta_offset_carets(
@@ -102,19 +209,31 @@ impl Buffer {
         tail_offset: isize,
     ) -> DirtyBytes {
         let max_len = self.data.len();
+        let old_selection = self.selection.clone();
         self.apply_delta_to_buffer(delta.clone(), true);
         self.selection
             .apply_delta_offset_carets(&delta, caret_offset, tail_offset, max_len);
 
-        DirtyBytes::ChangeLength
+        self.dirty_after_delta(&delta, max_len, &old_selection)
     }
 
     pub fn apply_incomplete_delta(&mut self, delta: RopeDelta) -> DirtyBytes {
         let max_len = self.data.len();
+        let old_selection = self.selection.clone();
         self.apply_delta_to_buffer(delta.clone(), false);
         self.selection.apply_delta(&delta, max_len);
 
-        DirtyBytes::ChangeLength
+        self.dirty_after_delta(&delta, max_len, &old_selection)
+    }
+
+    // Applies an incomplete delta made by typing in insert mode
+    pub fn apply_typed_delta(&mut self, delta: RopeDelta) -> DirtyBytes {
+        let max_len = self.data.len();
+        let old_selection = self.selection.clone();
+        self.apply_delta_to_buffer(delta.clone(), false);
+        self.selection.apply_typed_delta(&delta, max_len);
+
+        self.dirty_after_delta(&delta, max_len, &old_selection)
     }
 
     pub fn apply_incomplete_delta_offset_carets(
@@ -124,21 +243,118 @@ impl Buffer {
         tail_offset: isize,
     ) -> DirtyBytes {
         let max_len = self.data.len();
+        let old_selection = self.selection.clone();
         self.apply_delta_to_buffer(delta.clone(), false);
         self.selection
             .apply_delta_offset_carets(&delta, caret_offset, tail_offset, max_len);
 
-        DirtyBytes::ChangeLength
+        self.dirty_after_delta(&delta, max_len, &old_selection)
+    }
+
+    // Applies the incomplete delta that enters the high nibble of a byte in hex insert mode.
+    // The carets stay on the half-entered byte.
+    pub fn apply_half_byte_delta(&mut self, delta: RopeDelta) -> DirtyBytes {
+        let undo = HalfByteUndo {
+            delta: invert_delta(&delta, &self.data),
+            selection: self.selection.clone(),
+            dirty: self.dirty,
+            had_partial: self.history.has_partial(),
+        };
+        let dirty = self.apply_incomplete_delta_offset_carets(delta, -1, 0);
+        self.half_byte_undo = Some(undo);
+        dirty
+    }
+
+    // Reverts the half byte applied with `apply_half_byte_delta`, if no change came after it
+    pub fn discard_half_byte(&mut self) -> DirtyBytes {
+        match self.half_byte_undo.take() {
+            Some(undo) => {
+                self.apply_incomplete_delta(undo.delta);
+                if !undo.had_partial {
+                    // The step only holds the half byte and its reversal, so it undoes nothing
+                    self.history.drop_partial();
+                }
+                self.selection = undo.selection;
+                self.dirty = undo.dirty;
+                DirtyBytes::ChangeLength
+            }
+            None => DirtyBytes::ChangeInPlace(vec![]),
+        }
+    }
+
+    // Keeps the layers on the same bytes when the data around them changes
+    fn move_layers(&mut self, delta: &RopeDelta) {
+        let max_len = self.data.len();
+        for layer in &mut self.layers {
+            layer.selection.apply_delta(delta, max_len);
+        }
+    }
+
+    // Saves the current selections as a visible layer, replacing any layer of the same name
+    pub fn save_layer(&mut self, name: &str) {
+        let layer = Layer {
+            name: name.to_owned(),
+            selection: self.selection.clone(),
+            visible: true,
+        };
+        match self.layers.iter_mut().find(|layer| layer.name == name) {
+            Some(existing) => *existing = layer,
+            None => self.layers.push(layer),
+        }
+    }
+
+    // Returns whether the layer is visible now
+    pub fn toggle_layer(&mut self, name: &str) -> Result<bool, String> {
+        let layer = self
+            .layers
+            .iter_mut()
+            .find(|layer| layer.name == name)
+            .ok_or_else(|| format!("no layer named {}", name))?;
+        layer.visible = !layer.visible;
+        Ok(layer.visible)
+    }
+
+    pub fn delete_layer(&mut self, name: &str) -> Result<(), String> {
+        let len = self.layers.len();
+        self.layers.retain(|layer| layer.name != name);
+        if self.layers.len() == len {
+            return Err(format!("no layer named {}", name));
+        }
+        Ok(())
+    }
+
+    // Rebuilds the rope after many small edits left it fragmented. The contents stay the
+    // same, so this is neither undoable nor a change to save.
+    pub fn compact(&mut self) {
+        self.data = self.data.compacted();
+        self.saved_data = self.saved_data.compacted();
+    }
+
+    // Whether there are edits that will become a single undo step once committed
+    pub fn has_uncommitted_edits(&self) -> bool {
+        self.history.has_partial()
     }
 
     pub fn commit_delta(&mut self) {
         self.history.commit_partial();
     }
 
+    pub fn apply_patch(&mut self, patch: &Patch) -> Result<DirtyBytes, String> {
+        // Validate every step before touching the buffer
+        let deltas = patch.to_deltas(self.data.len())?;
+        for delta in deltas {
+            self.apply_incomplete_delta(delta);
+        }
+        self.commit_delta(); // The whole patch is a single undo step
+        Ok(DirtyBytes::ChangeLength)
+    }
+
     pub fn perform_undo(&mut self) -> Option<DirtyBytes> {
         if let Some((undo_delta, old_selection)) =
             self.history.undo(&self.data, self.selection.clone())
         {
+            self.record_patch_step(&undo_delta);
+            self.move_layers(&undo_delta);
             self.selection = old_selection;
             self.data = self.data.apply_delta(&undo_delta);
             self.dirty = true;
@@ -152,6 +368,8 @@ impl Buffer {
         if let Some((redo_delta, old_selection)) =
             self.history.redo(&self.data, self.selection.clone())
         {
+            self.record_patch_step(&redo_delta);
+            self.move_layers(&redo_delta);
             self.selection = old_selection;
             self.data = self.data.apply_delta(&redo_delta);
             self.dirty = true;
@@ -176,32 +394,119 @@ impl Buffer {
         dirty
     }
 
+    // Selects the regions, which must be sorted. Overlapping ones are merged, and the first one
+    // becomes the main selection.
+    pub fn replace_selections(&mut self, regions: Vec<SelRegion>) -> DirtyBytes {
+        let mut dirty: Vec<Interval> = self.selection.iter().copied().map(Into::into).collect();
+        dirty.extend(regions.iter().copied().map(Interval::from));
+        let mut regions = Some(regions);
+        self.selection.clear();
+        self.selection
+            .map_selections(|_| regions.take().unwrap_or_default());
+
+        DirtyBytes::ChangeInPlace(dirty)
+    }
+
+    pub fn replace_selection(&mut self, region: SelRegion) -> DirtyBytes {
+        let mut dirty: Vec<Interval> = self.selection.iter().copied().map(Into::into).collect();
+        dirty.push(region.into());
+        self.selection.clear();
+        self.selection.map_selections(|_| vec![region]);
+
+        DirtyBytes::ChangeInPlace(dirty)
+    }
+
+    // Moves to `offset`, remembering the current position for `jump_back`
+    pub fn jump_and_remember(&mut self, offset: usize) -> DirtyBytes {
+        if self.jumplist.len() == MAX_JUMPLIST_LEN {
+            self.jumplist.remove(0);
+        }
+        self.jumplist.push(self.selection.main_cursor_offset());
+        self.replace_selection(SelRegion::new(offset, offset))
+    }
+
+    pub fn jump_back(&mut self) -> Option<DirtyBytes> {
+        // The buffer may have shrunk since the position was remembered
+        let offset = cmp::min(self.jumplist.pop()?, self.data.len());
+        Some(self.replace_selection(SelRegion::new(offset, offset)))
+    }
+
+    pub fn add_selection(&mut self, region: SelRegion) -> DirtyBytes {
+        let old_main_sel_interval = self.selection.main().into();
+        self.selection.add_region(region);
+        DirtyBytes::ChangeInPlace(vec![old_main_sel_interval, region.into()])
+    }
+
     pub fn remove_selection(&mut self, index: usize) -> DirtyBytes {
         self.modify_sels_in_place(|sel| sel.remove(index % sel.len()))
     }
     pub fn retain_selection(&mut self, index: usize) -> DirtyBytes {
         self.modify_sels_in_place(|sel| sel.retain(index % sel.len()))
     }
+    // Drops every selection but the main one and collapses it to its caret
+    pub fn retain_main_caret(&mut self) -> DirtyBytes {
+        self.retain_caret(self.selection.main_selection)
+    }
+    // Drops every selection but the one at `index` and collapses it to its caret
+    pub fn retain_caret(&mut self, index: usize) -> DirtyBytes {
+        self.modify_sels_in_place(|sel| {
+            sel.retain(index);
+            sel.map_selections(|region| vec![region.collapse()]);
+        })
+    }
     pub fn select_next(&mut self, count: usize) -> DirtyBytes {
         self.switch_main_sel(|sel| sel.select_next(count))
     }
     pub fn select_prev(&mut self, count: usize) -> DirtyBytes {
         self.switch_main_sel(|sel| sel.select_prev(count))
     }
+    pub fn select_index(&mut self, index: usize) -> DirtyBytes {
+        self.switch_main_sel(|sel| sel.select_index(index))
+    }
+    pub fn select_at(&mut self, offset: usize) -> DirtyBytes {
+        self.switch_main_sel(|sel| sel.select_at(offset))
+    }
 
-    pub fn yank_selections(&mut self, reg: char) {
-        if self.data.is_empty() {
-            self.registers
-                .insert(reg, vec![vec![]; self.selection.len()]);
-            return;
+    // Stores `contents` in `reg`, also copying the unnamed register into `+` if the
+    // clipboard option is set to unnamed
+    pub fn set_register(&mut self, reg: char, contents: Vec<Vec<u8>>, options: &ViewOptions) {
+        if reg == '"' && options.clipboard_unnamed {
+            self.register_origins.remove(&'+');
+            self.registers.insert('+', contents.clone());
         }
+        self.register_origins.remove(&reg);
+        self.registers.insert(reg, contents);
+    }
+
+    pub fn yank_selections(&mut self, reg: char, options: &ViewOptions) {
+        let selections = if self.data.is_empty() {
+            vec![vec![]; self.selection.len()]
+        } else {
+            self.selection
+                .iter()
+                .map(|region| self.data.slice_to_cow(region.min()..=region.max()).to_vec())
+                .collect()
+        };
+        self.set_register(reg, selections, options);
+    }
+
+    // Yanks the selections along with their offsets, so that they can be put back where they
+    // came from
+    pub fn yank_selections_with_origins(&mut self, reg: char, options: &ViewOptions) {
+        self.yank_selections(reg, options);
+        let origins = self.selection.iter().map(|region| region.min()).collect();
+        self.register_origins.insert(reg, origins);
+    }
 
-        let selections = self
+    // Yanks all selections into a single entry, so that pasting inserts all of them together
+    pub fn yank_selections_joined(&mut self, reg: char, options: &ViewOptions) {
+        let joined = self
             .selection
             .iter()
-            .map(|region| self.data.slice_to_cow(region.min()..=region.max()).to_vec())
+            .filter(|_| !self.data.is_empty())
+            .flat_map(|region| self.data.slice_to_cow(region.min()..=region.max()).to_vec())
             .collect();
-        self.registers.insert(reg, selections);
+        self.set_register(reg, vec![joined], options);
     }
 
     pub fn overflow_sel_style(&self) -> Option<OverflowSelectionStyle> {
@@ -218,6 +523,171 @@ impl Buffer {
         }
     }
 
+    // Edits that would be lost by closing the buffer. A scratch buffer without a path only
+    // counts if it holds some data.
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.dirty && (self.path.is_some() || !self.data.is_empty())
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.dirty = false;
+        self.saved_data = self.data.clone();
+        self.history.mark_saved();
+    }
+
+    // Undoes (or redoes) back to the last saved state, or to the contents as loaded if the
+    // buffer was never saved
+    pub fn revert(&mut self) -> Result<DirtyBytes, String> {
+        let steps = self
+            .history
+            .steps_to_saved()
+            .ok_or("the saved state is no longer in the undo history")?;
+        if steps == 0 {
+            return Err("no changes since the last save".to_owned());
+        }
+        for _ in steps..0 {
+            self.perform_undo();
+        }
+        for _ in 0..steps {
+            self.perform_redo();
+        }
+        self.dirty = false;
+        Ok(DirtyBytes::ChangeLength)
+    }
+
+    pub fn changed_in_range(&self, range: Range<usize>) -> Vec<bool> {
+        let start = cmp::min(range.start, self.data.len());
+        let end = cmp::min(range.end, self.data.len());
+        let current = self.data.slice_to_cow(start..end);
+        let saved_len = self.saved_data.len();
+        let saved = self
+            .saved_data
+            .slice_to_cow(cmp::min(start, saved_len)..cmp::min(end, saved_len));
+
+        current
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| saved.get(i) != Some(byte))
+            .collect()
+    }
+
+    pub fn find_change(&self, from: usize, forward: bool) -> Result<Option<usize>, Cancelled> {
+        const CHUNK_SIZE: usize = 0x1000;
+        let len = self.data.len();
+        let mut cancel = CancelCheck::new();
+        if forward {
+            let mut start = from + 1;
+            while start < len {
+                cancel.check()?;
+                let end = cmp::min(len, start + CHUNK_SIZE);
+                if let Some(i) = self.changed_in_range(start..end).iter().position(|&c| c) {
+                    return Ok(Some(start + i));
+                }
+                start = end;
+            }
+        } else {
+            let mut end = cmp::min(from, len);
+            while end > 0 {
+                cancel.check()?;
+                let start = end.saturating_sub(CHUNK_SIZE);
+                if let Some(i) = self.changed_in_range(start..end).iter().rposition(|&c| c) {
+                    return Ok(Some(start + i));
+                }
+                end = start;
+            }
+        }
+        Ok(None)
+    }
+
+    // The first byte at or after `from` (or before it, going backward) that satisfies `pred`
+    fn position_from(
+        &self,
+        from: usize,
+        forward: bool,
+        pred: impl Fn(u8) -> bool,
+        cancel: &mut CancelCheck,
+    ) -> Result<Option<usize>, Cancelled> {
+        const CHUNK_SIZE: usize = 0x10000;
+        let len = self.data.len();
+        if forward {
+            let mut start = from;
+            while start < len {
+                cancel.check()?;
+                let end = cmp::min(len, start + CHUNK_SIZE);
+                let chunk = self.data.slice_to_cow(start..end);
+                if let Some(i) = chunk.iter().position(|&byte| pred(byte)) {
+                    return Ok(Some(start + i));
+                }
+                start = end;
+            }
+        } else {
+            let mut end = cmp::min(from, len);
+            while end > 0 {
+                cancel.check()?;
+                let start = end.saturating_sub(CHUNK_SIZE);
+                let chunk = self.data.slice_to_cow(start..end);
+                if let Some(i) = chunk.iter().rposition(|&byte| pred(byte)) {
+                    return Ok(Some(start + i));
+                }
+                end = start;
+            }
+        }
+        Ok(None)
+    }
+
+    // The longest run of bytes satisfying `pred` that contains `from`, or else the first one
+    // after it
+    pub fn find_run(
+        &self,
+        from: usize,
+        pred: impl Fn(u8) -> bool,
+    ) -> Result<Option<Range<usize>>, Cancelled> {
+        let mut cancel = CancelCheck::new();
+        let in_run = from < self.data.len() && pred(self.data.slice_to_cow(from..from + 1)[0]);
+        let start = if in_run {
+            self.position_from(from, false, |byte| !pred(byte), &mut cancel)?
+                .map_or(0, |i| i + 1)
+        } else {
+            match self.position_from(from, true, &pred, &mut cancel)? {
+                Some(start) => start,
+                None => return Ok(None),
+            }
+        };
+        let end = self
+            .position_from(start, true, |byte| !pred(byte), &mut cancel)?
+            .unwrap_or(self.data.len());
+        Ok(Some(start..end))
+    }
+
+    // The offset of the first byte that differs from `other` and the number of differing
+    // bytes, counting any length difference as differing bytes
+    pub fn diff_against(&self, other: &[u8]) -> Result<(Option<usize>, usize), Cancelled> {
+        const CHUNK_SIZE: usize = 0x10000;
+        let len = self.data.len();
+        let common_len = cmp::min(len, other.len());
+        let mut cancel = CancelCheck::new();
+        let mut first = None;
+        let mut count = 0;
+        let mut start = 0;
+        while start < common_len {
+            cancel.check()?;
+            let end = cmp::min(common_len, start + CHUNK_SIZE);
+            let chunk = self.data.slice_to_cow(start..end);
+            for (i, (a, b)) in chunk.iter().zip(&other[start..end]).enumerate() {
+                if a != b {
+                    first.get_or_insert(start + i);
+                    count += 1;
+                }
+            }
+            start = end;
+        }
+        if len != other.len() {
+            first.get_or_insert(common_len);
+            count += cmp::max(len, other.len()) - common_len;
+        }
+        Ok((first, count))
+    }
+
     pub fn update_path_if_missing(&mut self, path: impl Into<PathBuf>) {
         if self.path.is_none() {
             self.path = Some(path.into());
@@ -228,6 +698,12 @@ impl Buffer {
 pub struct Buffers {
     list: Vec<Buffer>,
     cur_buf_index: usize,
+    // The buffer that was current before the last switch, like vim's alternate file
+    prev_buf_index: Option<usize>,
+    // Shared by all buffers, like in vim
+    pub last_pattern: Option<Pattern>,
+    // The last line run from command mode, repeated by `@:`
+    pub last_command: Option<String>,
 }
 
 impl Default for Buffers {
@@ -244,7 +720,10 @@ impl Buffers {
     pub fn with_buffer(buf: Buffer) -> Buffers {
         Buffers {
             cur_buf_index: 0,
+            prev_buf_index: None,
             list: vec![buf],
+            last_pattern: None,
+            last_command: None,
         }
     }
 
@@ -262,12 +741,43 @@ impl Buffers {
         self.list.iter_mut()
     }
 
+    // Runs `f` with each buffer as the current one in turn, then switches back. `f` must not
+    // add or remove buffers.
+    pub fn for_each_as_current<T>(&mut self, mut f: impl FnMut(&mut Buffers) -> T) -> Vec<T> {
+        let current = self.cur_buf_index;
+        let results = (0..self.list.len())
+            .map(|index| {
+                self.cur_buf_index = index;
+                f(self)
+            })
+            .collect();
+        self.cur_buf_index = current;
+        results
+    }
+
+    fn set_current(&mut self, index: usize) {
+        if index != self.cur_buf_index {
+            self.prev_buf_index = Some(self.cur_buf_index);
+            self.cur_buf_index = index;
+        }
+    }
+
+    pub fn switch_to_previous(&mut self) -> bool {
+        match self.prev_buf_index {
+            Some(index) => {
+                self.set_current(index);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn switch_buffer(&mut self, filename: impl AsRef<Path>) -> Result<(), std::io::Error> {
         let canon = filename.as_ref().canonicalize()?;
         for (i, buf) in self.list.iter().enumerate() {
             if let Some(path) = &buf.path {
                 if path.canonicalize()? == canon {
-                    self.cur_buf_index = i;
+                    self.set_current(i);
                     return Ok(());
                 }
             }
@@ -277,15 +787,230 @@ impl Buffers {
             std::fs::read(&filename)?,
             Some(filename.as_ref().to_owned()),
         ));
-        self.cur_buf_index = self.list.len() - 1;
+        self.set_current(self.list.len() - 1);
+        Ok(())
+    }
+
+    // Opens a copy of `range` of the current buffer as a new buffer
+    pub fn carve(&mut self, range: Range<usize>) {
+        let parent = self.current();
+        let mut carved = Buffer::from_data_and_path(
+            parent.data.slice_to_cow(range.clone()).into_owned(),
+            None::<PathBuf>,
+        );
+        carved.carve = Some(Carve {
+            parent: Some(self.cur_buf_index),
+            parent_name: parent.name(),
+            start: range.start,
+            len: range.len(),
+        });
+        self.list.push(carved);
+        self.set_current(self.list.len() - 1);
+    }
+
+    // Writes the current carved buffer over the range it was copied from
+    pub fn splice_back(&mut self) -> Result<(), String> {
+        let carved = self.current();
+        let carve = carved.carve.clone().ok_or("buffer wasn't carved")?;
+        let parent = carve
+            .parent
+            .ok_or("the buffer it was carved from is closed")?;
+        let data = carved.data.slice_to_cow(..).into_owned();
+        let new_len = data.len();
+
+        let parent = &mut self.list[parent];
+        if carve.start + carve.len > parent.data.len() {
+            return Err("the buffer it was carved from is now too short".to_owned());
+        }
+        let delta = ops::splice(&parent.data, carve.start..carve.start + carve.len, data);
+        parent.apply_delta(delta);
+
+        let carved = self.current_mut();
+        carved.carve = Some(Carve {
+            len: new_len,
+            ..carve
+        });
+        carved.mark_saved();
         Ok(())
     }
 
     pub fn delete_current(&mut self) {
-        self.list.remove(self.cur_buf_index);
-        self.cur_buf_index = self.cur_buf_index.saturating_sub(1);
+        let removed = self.cur_buf_index;
+        self.list.remove(removed);
+        for buffer in &mut self.list {
+            if let Some(carve) = &mut buffer.carve {
+                carve.parent = match carve.parent {
+                    Some(parent) if parent > removed => Some(parent - 1),
+                    Some(parent) if parent < removed => Some(parent),
+                    _ => None,
+                };
+            }
+        }
+        self.cur_buf_index = removed.saturating_sub(1);
+        self.prev_buf_index = match self.prev_buf_index {
+            Some(prev) if prev > removed => Some(prev - 1),
+            Some(prev) if prev < removed => Some(prev),
+            _ => None,
+        }
+        .filter(|&prev| prev != self.cur_buf_index);
         if self.list.is_empty() {
             self.list.push(Buffer::default());
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers_follow_edits() {
+        let mut buffer = Buffer::from_data_and_path(vec![0; 8], None::<&str>);
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(4, 5)]);
+        buffer.save_layer("field");
+
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(0, 0)]);
+        let delta = ops::insert(&buffer.data, &buffer.selection, vec![1, 2]);
+        buffer.apply_delta(delta);
+        let region = buffer.layers[0].selection.main();
+        assert_eq!((region.min(), region.max()), (6, 7));
+
+        buffer.perform_undo();
+        let region = buffer.layers[0].selection.main();
+        assert_eq!((region.min(), region.max()), (4, 5));
+
+        assert_eq!(buffer.toggle_layer("field"), Ok(false));
+        assert!(buffer.delete_layer("other").is_err());
+    }
+
+    #[test]
+    fn test_carve_and_splice_back() {
+        let mut buffers =
+            Buffers::with_buffer(Buffer::from_data_and_path(b"abcdef".to_vec(), None::<&str>));
+        buffers.carve(2..4);
+        assert_eq!(Vec::from(&buffers.current().data), b"cd");
+
+        let carved = buffers.current_mut();
+        let delta = ops::insert(&carved.data, &carved.selection, b"XY".to_vec());
+        carved.apply_delta(delta);
+        assert_eq!(buffers.splice_back(), Ok(()));
+        assert_eq!(buffers.current().carve.as_ref().unwrap().len, 4);
+
+        // Splicing again replaces what the first splice wrote
+        assert_eq!(buffers.splice_back(), Ok(()));
+        buffers.switch_to_previous();
+        assert_eq!(Vec::from(&buffers.current().data), b"abXYcdef");
+        assert!(buffers.splice_back().is_err());
+    }
+
+    #[test]
+    fn test_diff_against() {
+        let buffer = Buffer::from_data_and_path(b"abcdef".to_vec(), None::<&str>);
+        assert_eq!(buffer.diff_against(b"abcdef"), Ok((None, 0)));
+        assert_eq!(buffer.diff_against(b"abXdeY"), Ok((Some(2), 2)));
+        assert_eq!(buffer.diff_against(b"abcdefgh"), Ok((Some(6), 2)));
+        assert_eq!(buffer.diff_against(b"Xbc"), Ok((Some(0), 4)));
+    }
+
+    #[test]
+    fn test_identity_replace_is_not_a_change() {
+        let mut buffer = Buffer::from_data_and_path(b"abc".to_vec(), None::<&str>);
+        let delta = ops::replace(&buffer.data, &buffer.selection, b'a');
+        assert!(buffer.data.is_unchanged_by(&delta));
+        buffer.apply_delta(delta);
+        assert!(!buffer.dirty);
+        assert!(buffer.perform_undo().is_none());
+
+        let delta = ops::replace(&buffer.data, &buffer.selection, b'z');
+        assert!(!buffer.data.is_unchanged_by(&delta));
+        buffer.apply_delta(delta);
+        assert!(buffer.dirty);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut buffer = Buffer::from_data_and_path(vec![], None::<&str>);
+        for i in 0..2000 {
+            let delta = ops::insert(&buffer.data, &buffer.selection, vec![i as u8]);
+            buffer.apply_incomplete_delta(delta);
+        }
+        buffer.commit_delta();
+        buffer.mark_saved();
+        let contents = buffer.data.slice_to_cow(..).to_vec();
+
+        buffer.compact();
+        assert_eq!(buffer.data.slice_to_cow(..), contents);
+        assert_eq!(buffer.data.stats().small_leaves, 0);
+        assert!(!buffer.dirty);
+    }
+
+    #[test]
+    fn test_revert() {
+        let mut buffer = Buffer::from_data_and_path(b"abc".to_vec(), None::<&str>);
+        buffer.apply_delta(ops::replace(&buffer.data, &buffer.selection, b'x'));
+        buffer.mark_saved();
+        buffer.apply_delta(ops::replace(&buffer.data, &buffer.selection, b'y'));
+        buffer.apply_delta(ops::replace(&buffer.data, &buffer.selection, b'z'));
+
+        assert!(buffer.revert().is_ok());
+        assert_eq!(buffer.data.slice_to_cow(..), &b"xbc"[..]);
+        assert!(!buffer.dirty);
+        buffer.perform_redo();
+        assert_eq!(buffer.data.slice_to_cow(..), &b"xyc"[..]);
+
+        // Undoing past the saved state and editing loses it
+        buffer.perform_undo();
+        buffer.perform_undo();
+        buffer.apply_delta(ops::replace(&buffer.data, &buffer.selection, b'w'));
+        assert!(buffer.revert().is_err());
+    }
+
+    #[test]
+    fn test_insert_dirties_from_insertion() {
+        let mut buffer = Buffer::from_data_and_path(vec![0; 0x40], None::<&str>);
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(0x3e, 0x3e)]);
+        let delta = ops::insert(&buffer.data, &buffer.selection, vec![1]);
+        // The rows before the insertion stay as they were
+        assert_eq!(
+            buffer.apply_delta(delta),
+            DirtyBytes::ChangeInPlace(vec![Interval::new(0x3e, 0x42)])
+        );
+
+        // Carets moved in front of the changed bytes are redrawn too
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(0x10, 0x10), SelRegion::new(0x3f, 0x3f)]);
+        let delta = ops::deletion(&buffer.data, &buffer.selection);
+        assert_eq!(
+            buffer.apply_delta_offset_carets(delta, -1, -1),
+            DirtyBytes::ChangeInPlace(vec![Interval::new(0xf, 0x42)])
+        );
+    }
+
+    #[test]
+    fn test_discarded_half_byte_leaves_buffer_clean() {
+        let mut buffer = Buffer::from_data_and_path(vec![0; 4], None::<&str>);
+        let delta = ops::insert(&buffer.data, &buffer.selection, vec![0xa0]);
+        buffer.apply_half_byte_delta(delta);
+        assert!(buffer.dirty);
+
+        buffer.discard_half_byte();
+        buffer.commit_delta();
+        assert!(!buffer.dirty);
+        assert_eq!(buffer.data.slice_to_cow(..), &[0; 4][..]);
+        // Leaving insert mode after it doesn't leave an undo step that changes nothing
+        assert_eq!(buffer.perform_undo(), None);
+        assert!(!buffer.dirty);
+        // Nothing left to discard, so nothing to redraw
+        assert_eq!(
+            buffer.discard_half_byte(),
+            DirtyBytes::ChangeInPlace(vec![])
+        );
+    }
+}
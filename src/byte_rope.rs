@@ -14,7 +14,6 @@ pub struct Bytes(pub Vec<u8>);
 #[derive(Clone, Default)]
 pub struct Rope(Node<RopeInfo>);
 pub type RopeDelta = Delta<RopeInfo>;
-#[allow(dead_code)]
 pub type RopeDeltaElement = DeltaElement<RopeInfo>;
 
 impl Leaf for Bytes {
@@ -94,7 +93,37 @@ impl Metric<RopeInfo> for BaseMetric {
     }
 }
 
+// Shape of a rope's tree, for diagnosing fragmentation after many small edits
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct RopeStats {
+    pub leaves: usize,
+    // Leaves under the size that the tree tries to keep them at
+    pub small_leaves: usize,
+    // Bytes allocated for leaf contents, which can exceed the length after edits
+    pub leaf_capacity: usize,
+}
+
 impl Rope {
+    pub fn stats(&self) -> RopeStats {
+        let mut stats = RopeStats::default();
+        let mut cursor = Cursor::new(&self.0, 0);
+        let mut leaf = cursor.get_leaf().map(|(leaf, _)| leaf);
+        while let Some(Bytes(bytes)) = leaf {
+            stats.leaves += 1;
+            if bytes.len() < MIN_LEAF {
+                stats.small_leaves += 1;
+            }
+            stats.leaf_capacity += bytes.capacity();
+            leaf = cursor.next_leaf().map(|(leaf, _)| leaf);
+        }
+        stats
+    }
+
+    // The same contents rebuilt into full-size leaves
+    pub fn compacted(&self) -> Rope {
+        Rope::from(Vec::from(self))
+    }
+
     pub fn len(&self) -> usize {
         self.0.len()
     }
@@ -103,7 +132,7 @@ impl Rope {
         self.0.len() == 0
     }
 
-    pub fn iter_chunks<T: IntervalBounds>(&self, range: T) -> ChunkIter {
+    pub fn iter_chunks<T: IntervalBounds>(&self, range: T) -> ChunkIter<'_> {
         let Interval { start, end } = range.into_interval(self.0.len());
         ChunkIter {
             cursor: Cursor::new(&self.0, start),
@@ -111,7 +140,7 @@ impl Rope {
         }
     }
 
-    pub fn slice_to_cow<T: IntervalBounds>(&self, range: T) -> Cow<[u8]> {
+    pub fn slice_to_cow<T: IntervalBounds>(&self, range: T) -> Cow<'_, [u8]> {
         let mut iter = self.iter_chunks(range);
         let first = iter.next();
         let second = iter.next();
@@ -134,6 +163,29 @@ impl Rope {
         Rope(delta.apply(&self.0))
     }
 
+    // Whether applying `delta` would leave the contents as they are, like replacing bytes
+    // with themselves
+    pub fn is_unchanged_by(&self, delta: &RopeDelta) -> bool {
+        if delta.new_document_len() != self.len() {
+            return false;
+        }
+        let mut pos = 0;
+        for element in &delta.els {
+            match element {
+                DeltaElement::Copy(start, end) if *start == pos => pos = *end,
+                DeltaElement::Copy(..) => return false,
+                DeltaElement::Insert(node) => {
+                    let end = pos + node.len();
+                    if Rope(node.clone()).slice_to_cow(..) != self.slice_to_cow(pos..end) {
+                        return false;
+                    }
+                    pos = end;
+                }
+            }
+        }
+        true
+    }
+
     pub fn without_subset(&self, subset: Subset) -> Rope {
         Rope(subset.delete_from(&self.0))
     }
@@ -141,6 +193,10 @@ impl Rope {
     pub fn into_node(self) -> Node<RopeInfo> {
         self.0
     }
+
+    pub fn from_node(node: Node<RopeInfo>) -> Rope {
+        Rope(node)
+    }
 }
 
 impl From<Vec<u8>> for Rope {
@@ -170,7 +226,7 @@ impl From<Rope> for Vec<u8> {
     }
 }
 
-impl<'a> From<&'a Rope> for Vec<u8> {
+impl From<&Rope> for Vec<u8> {
     fn from(rope: &Rope) -> Self {
         rope.iter_chunks(..).fold(vec![], |mut acc, x| {
             acc.extend_from_slice(x);
@@ -222,3 +278,16 @@ impl fmt::Debug for Rope {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let rope = Rope::from(vec![0u8; 3000]);
+        let stats = rope.stats();
+        assert_eq!(stats.leaves, 3);
+        assert!(stats.leaf_capacity >= 3000);
+    }
+}
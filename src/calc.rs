@@ -0,0 +1,168 @@
+// A small integer expression evaluator for `:calc`.
+// Supports hex (0x) and decimal literals, `%o` for the current offset, parentheses, unary
+// minus and the binary operators `* / + - << >> & ^ |` with C precedence.
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Token {
+    Number(i64),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+const OPERATORS: [&str; 10] = ["<<", ">>", "*", "/", "+", "-", "&", "^", "|", "%o"];
+
+// Binding strength of binary operators, loosest first
+const PRECEDENCE: [&[&str]; 6] = [
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/"],
+];
+
+fn tokenize(expr: &str, offset: usize) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = expr.trim_start();
+    while !rest.is_empty() {
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(if *op == "%o" {
+                Token::Number(offset as i64)
+            } else {
+                Token::Op(op)
+            });
+            rest = &rest[op.len()..];
+        } else if let Some(after) = rest.strip_prefix('(') {
+            tokens.push(Token::Open);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix(')') {
+            tokens.push(Token::Close);
+            rest = after;
+        } else if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            let literal = &rest[..len];
+            let parsed = match literal.strip_prefix("0x") {
+                Some(hex) => i64::from_str_radix(hex, 16),
+                None => literal.parse(),
+            };
+            tokens.push(Token::Number(
+                parsed.map_err(|_| format!("invalid number {}", literal))?,
+            ));
+            rest = &rest[len..];
+        } else {
+            return Err(format!("unexpected {}", rest.chars().next().unwrap()));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn binary(&mut self, level: usize) -> Result<i64, String> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(Token::Op(op)) = self.peek() {
+            if !PRECEDENCE[level].contains(&op) {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = apply(op, lhs, rhs)?;
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(value),
+            Some(Token::Op("-")) => self
+                .unary()?
+                .checked_neg()
+                .ok_or_else(|| "overflow".to_owned()),
+            Some(Token::Open) => {
+                let value = self.binary(0)?;
+                match self.next() {
+                    Some(Token::Close) => Ok(value),
+                    _ => Err("missing )".to_owned()),
+                }
+            }
+            Some(Token::Close) => Err("unexpected )".to_owned()),
+            Some(Token::Op(op)) => Err(format!("unexpected {}", op)),
+            None => Err("unexpected end of expression".to_owned()),
+        }
+    }
+}
+
+fn apply(op: &str, lhs: i64, rhs: i64) -> Result<i64, String> {
+    let shift = |rhs: i64| u32::try_from(rhs).map_err(|_| format!("invalid shift {}", rhs));
+    let result = match op {
+        "*" => lhs.checked_mul(rhs),
+        "/" if rhs == 0 => return Err("division by zero".to_owned()),
+        "/" => lhs.checked_div(rhs),
+        "+" => lhs.checked_add(rhs),
+        "-" => lhs.checked_sub(rhs),
+        "<<" => lhs.checked_shl(shift(rhs)?),
+        ">>" => lhs.checked_shr(shift(rhs)?),
+        "&" => Some(lhs & rhs),
+        "^" => Some(lhs ^ rhs),
+        "|" => Some(lhs | rhs),
+        _ => unreachable!(),
+    };
+    result.ok_or_else(|| "overflow".to_owned())
+}
+
+pub fn evaluate(expr: &str, offset: usize) -> Result<i64, String> {
+    let mut parser = Parser {
+        tokens: tokenize(expr, offset)?,
+        pos: 0,
+    };
+    let value = parser.binary(0)?;
+    match parser.next() {
+        None => Ok(value),
+        Some(Token::Close) => Err("unexpected )".to_owned()),
+        Some(_) => Err("expected an operator".to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(evaluate("1 + 2 * 3", 0), Ok(7));
+        assert_eq!(evaluate("(1 + 2) * 3", 0), Ok(9));
+        assert_eq!(evaluate("1 << 4 | 0x3 & 0x6", 0), Ok(0x12));
+        assert_eq!(evaluate("-0x10 + %o", 0x30), Ok(0x20));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(evaluate("1 / 0", 0).is_err());
+        assert!(evaluate("1 +", 0).is_err());
+        assert!(evaluate("(1", 0).is_err());
+        assert!(evaluate("1 2", 0).is_err());
+        assert!(evaluate("0xg", 0).is_err());
+        assert!(evaluate("1 << 64", 0).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent};
+
+// Long scans check for a pending escape key now and then so that they can be aborted.
+// Only the event loop enables this, so that tests never touch the terminal.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+thread_local! {
+    // Events read while checking for cancellation, to be handled once the scan is done
+    static PENDING_EVENTS: RefCell<VecDeque<Event>> = const { RefCell::new(VecDeque::new()) };
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Cancelled;
+
+impl Cancelled {
+    pub fn info() -> String {
+        "cancelled".to_owned()
+    }
+}
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+// Puts an event in front of the ones waiting to be handled
+pub fn push_pending_event(evt: Event) {
+    PENDING_EVENTS.with(|events| events.borrow_mut().push_front(evt));
+}
+
+pub fn take_pending_event() -> Option<Event> {
+    PENDING_EVENTS.with(|events| events.borrow_mut().pop_front())
+}
+
+pub struct CancelCheck {
+    last_poll: Instant,
+}
+
+impl CancelCheck {
+    pub fn new() -> CancelCheck {
+        CancelCheck {
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn check(&mut self) -> Result<(), Cancelled> {
+        if !ENABLED.load(Ordering::Relaxed) || self.last_poll.elapsed() < POLL_INTERVAL {
+            return Ok(());
+        }
+        self.last_poll = Instant::now();
+
+        while let Ok(true) = event::poll(Duration::ZERO) {
+            match event::read() {
+                Ok(Event::Key(KeyEvent {
+                    code: KeyCode::Esc, ..
+                })) => return Err(Cancelled),
+                Ok(evt) => PENDING_EVENTS.with(|events| events.borrow_mut().push_back(evt)),
+                Err(_) => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for CancelCheck {
+    fn default() -> Self {
+        CancelCheck::new()
+    }
+}
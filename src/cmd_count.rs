@@ -122,6 +122,16 @@ impl State {
             State::None => 1,
         }
     }
+
+    // Like to_count, but caps the count at `limit`, returning a note to show if it had to
+    pub fn to_count_capped(self, limit: usize) -> (usize, Option<String>) {
+        let count = self.to_count();
+        if count > limit {
+            (limit, Some(format!("count clamped to 0x{:x}", limit)))
+        } else {
+            (count, None)
+        }
+    }
 }
 
 impl std::fmt::Display for State {
@@ -137,3 +147,21 @@ impl std::fmt::Display for State {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_capped() {
+        let state = State::Some {
+            hex: true,
+            count: 0xffff_ffff,
+        };
+        assert_eq!(
+            state.to_count_capped(0x100),
+            (0x100, Some("count clamped to 0x100".to_owned()))
+        );
+        assert_eq!(State::None.to_count_capped(0x100), (1, None));
+    }
+}
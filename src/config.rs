@@ -0,0 +1,136 @@
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::keymap::parse_key;
+use crate::modes::{command, normal};
+
+// The user's configuration file. Each line holds one directive; empty lines and lines
+// starting with `#` are ignored:
+//   alias <name> = <command> [arguments]
+//   bind <key> = :<command> [arguments]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Config {
+    aliases: Vec<(String, String)>,
+    bindings: Vec<(String, String)>,
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Default::default()
+    }
+
+    // $TEEHEE_CONFIG, $XDG_CONFIG_HOME/teehee/config, or ~/.config/teehee/config
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os("TEEHEE_CONFIG").filter(|path| !path.is_empty()) {
+            return Some(PathBuf::from(path));
+        }
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_home.join("teehee").join("config"))
+    }
+
+    // A missing file is not an error, it just leaves everything at the defaults
+    pub fn load(path: impl AsRef<Path>) -> Result<Config, String> {
+        match fs::read_to_string(&path) {
+            Ok(contents) => Config::parse(&contents),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::new()),
+            Err(e) => Err(format!("{}: {}", path.as_ref().display(), e)),
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Config, String> {
+        let mut config = Config::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (directive, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match directive {
+                "alias" => {
+                    let (name, expansion) = rest
+                        .split_once('=')
+                        .map(|(name, expansion)| (name.trim(), expansion.trim()))
+                        .filter(|(name, expansion)| {
+                            !name.is_empty() && !name.contains(' ') && !expansion.is_empty()
+                        })
+                        .ok_or_else(|| {
+                            format!("line {}: expected alias <name> = <command>", i + 1)
+                        })?;
+                    config.aliases.push((name.to_owned(), expansion.to_owned()));
+                }
+                "bind" => {
+                    let (key, command_line) = rest
+                        .split_once('=')
+                        .map(|(key, command_line)| {
+                            let command_line = command_line.trim();
+                            let command_line =
+                                command_line.strip_prefix(':').unwrap_or(command_line);
+                            (key.trim(), command_line.trim())
+                        })
+                        .filter(|(_, command_line)| !command_line.is_empty())
+                        .ok_or_else(|| {
+                            format!("line {}: expected bind <key> = :<command>", i + 1)
+                        })?;
+                    parse_key(key).map_err(|e| format!("line {}: {}", i + 1, e))?;
+                    config
+                        .bindings
+                        .push((key.to_owned(), command_line.to_owned()));
+                }
+                _ => return Err(format!("line {}: unknown directive {}", i + 1, directive)),
+            }
+        }
+        Ok(config)
+    }
+
+    // Makes the configuration take effect, returning warnings to show to the user
+    pub fn apply(&self) -> Vec<String> {
+        let aliases = self
+            .aliases
+            .iter()
+            .map(|(name, expansion)| command::register_alias(name, expansion));
+        let bindings = self.bindings.iter().map(|(key, command_line)| {
+            normal::register_binding(key, command_line).unwrap_or_else(Some)
+        });
+        aliases.chain(bindings).flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_aliases() {
+        let config = Config::parse("# comment\n\nalias zap = set encoding cp437\n").unwrap();
+        assert_eq!(
+            config.aliases,
+            vec![("zap".to_owned(), "set encoding cp437".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_parse_bindings() {
+        let config = Config::parse("bind <c-x> = :xor 00\nbind X=set debug\n").unwrap();
+        assert_eq!(
+            config.bindings,
+            vec![
+                ("<c-x>".to_owned(), "xor 00".to_owned()),
+                ("X".to_owned(), "set debug".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Config::parse("alias zap").is_err());
+        assert!(Config::parse("alias = set debug").is_err());
+        assert!(Config::parse("unalias zap").is_err());
+        assert!(Config::parse("bind <c-x> = :").is_err());
+        assert!(Config::parse("bind <nope> = :set debug").is_err());
+    }
+}
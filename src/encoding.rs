@@ -0,0 +1,199 @@
+use lazy_static::lazy_static;
+
+// Glyphs for bytes 0x80..=0xff in code page 437
+const CP437_HIGH: &str = concat!(
+    "ÇüéâäàåçêëèïîìÄÅ",
+    "ÉæÆôöòûùÿÖÜ¢£¥₧ƒ",
+    "áíóúñÑªº¿⌐¬½¼¡«»",
+    "░▒▓│┤╡╢╖╕╣║╗╝╜╛┐",
+    "└┴┬├─┼╞╟╚╔╩╦╠═╬╧",
+    "╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀",
+    "αßΓπΣσµτΦΘΩδ∞φε∩",
+    "≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}",
+);
+
+// Glyphs for bytes 0x01..=0x1f in code page 437. Null stays a dot so that it doesn't
+// look like a space.
+const CP437_LOW: &str = "☺☻♥♦♣♠•◘○◙♂♀♪♫☼►◄↕‼¶§▬↨↑↓→←∟↔▲▼";
+
+lazy_static! {
+    static ref CP437_TABLE: Vec<char> = {
+        let mut table = vec!['.'];
+        table.extend(CP437_LOW.chars());
+        table.extend((0x20..0x7f).map(char::from));
+        table.push('⌂');
+        table.extend(CP437_HIGH.chars());
+        table
+    };
+}
+
+// Which bytes count as printable text in the ASCII column and when coloring bytes
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AsciiClass {
+    // Graphic characters and the space are printable
+    Default,
+    // Only graphic characters are printable, whitespace counts as control characters
+    Strict,
+    // Whitespace is shown as a space and the C1 control range 0x80-0x9f counts as control
+    // characters
+    Extended,
+}
+
+impl AsciiClass {
+    pub fn from_name(name: &str) -> Result<AsciiClass, String> {
+        match name {
+            "default" => Ok(AsciiClass::Default),
+            "strict" => Ok(AsciiClass::Strict),
+            "extended" => Ok(AsciiClass::Extended),
+            _ => Err(format!("unknown ascii class {}", name)),
+        }
+    }
+
+    pub fn is_whitespace(self, byte: u8) -> bool {
+        self != AsciiClass::Strict && byte.is_ascii_whitespace()
+    }
+
+    pub fn is_control(self, byte: u8) -> bool {
+        byte.is_ascii() || (self == AsciiClass::Extended && (0x80..0xa0).contains(&byte))
+    }
+
+    // The class that decides the color of a byte
+    pub fn classify(self, byte: u8) -> ByteClass {
+        if byte == 0x00 {
+            ByteClass::Null
+        } else if byte.is_ascii_graphic() {
+            ByteClass::Printable
+        } else if self.is_whitespace(byte) {
+            ByteClass::Whitespace
+        } else if self.is_control(byte) {
+            ByteClass::Control
+        } else {
+            ByteClass::NonAscii
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ByteClass {
+    Null,
+    Printable,
+    Whitespace,
+    Control,
+    NonAscii,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Encoding {
+    Ascii,
+    Latin1,
+    Cp437,
+}
+
+impl Encoding {
+    pub fn from_name(name: &str) -> Result<Encoding, String> {
+        match name {
+            "ascii" => Ok(Encoding::Ascii),
+            "latin1" => Ok(Encoding::Latin1),
+            "cp437" => Ok(Encoding::Cp437),
+            _ => Err(format!("unknown encoding {}", name)),
+        }
+    }
+
+    // The character displayed for `byte` in the ASCII column
+    pub fn byte_to_char(self, byte: u8, class: AsciiClass) -> char {
+        match self {
+            _ if byte.is_ascii_graphic() => char::from(byte),
+            _ if byte == 0x20 && class != AsciiClass::Strict => ' ',
+            _ if class == AsciiClass::Extended && byte.is_ascii_whitespace() => ' ',
+            Encoding::Latin1 if byte >= 0xa1 => char::from(byte),
+            Encoding::Cp437 => CP437_TABLE[byte as usize],
+            _ => '.',
+        }
+    }
+
+    // Like byte_to_char, but with arrows for tabs and line breaks and a middle dot for the
+    // other control bytes, so that they don't look like a real `.`. Every glyph is a single
+    // column wide.
+    pub fn byte_to_glyph(self, byte: u8, class: AsciiClass) -> char {
+        match byte {
+            b'\t' => '\u{2192}',
+            b'\n' => '\u{21b5}',
+            b'\r' => '\u{2190}',
+            _ => match self.byte_to_char(byte, class) {
+                '.' if byte.is_ascii_control() => '\u{b7}',
+                ch => ch,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cp437_table() {
+        assert_eq!(CP437_TABLE.len(), 0x100);
+        assert_eq!(Encoding::Cp437.byte_to_char(0x01, AsciiClass::Default), '☺');
+        assert_eq!(Encoding::Cp437.byte_to_char(b'A', AsciiClass::Default), 'A');
+        assert_eq!(Encoding::Cp437.byte_to_char(0xc9, AsciiClass::Default), '╔');
+        assert_eq!(Encoding::Cp437.byte_to_char(0xfe, AsciiClass::Default), '■');
+    }
+
+    #[test]
+    fn test_latin1() {
+        assert_eq!(
+            Encoding::Latin1.byte_to_char(0xe9, AsciiClass::Default),
+            'é'
+        );
+        assert_eq!(
+            Encoding::Latin1.byte_to_char(0x85, AsciiClass::Default),
+            '.'
+        );
+        assert_eq!(Encoding::Ascii.byte_to_char(0xe9, AsciiClass::Default), '.');
+    }
+
+    #[test]
+    fn test_ascii_classes() {
+        assert_eq!(Encoding::Ascii.byte_to_char(b' ', AsciiClass::Default), ' ');
+        assert_eq!(Encoding::Ascii.byte_to_char(b' ', AsciiClass::Strict), '.');
+        assert_eq!(
+            Encoding::Ascii.byte_to_char(b'\t', AsciiClass::Default),
+            '.'
+        );
+        assert_eq!(
+            Encoding::Ascii.byte_to_char(b'\t', AsciiClass::Extended),
+            ' '
+        );
+        assert!(!AsciiClass::Default.is_control(0x85));
+        assert!(AsciiClass::Extended.is_control(0x85));
+    }
+
+    #[test]
+    fn test_glyphs() {
+        assert_eq!(
+            Encoding::Ascii.byte_to_glyph(b'\t', AsciiClass::Extended),
+            '→'
+        );
+        assert_eq!(
+            Encoding::Ascii.byte_to_glyph(b'\n', AsciiClass::Default),
+            '↵'
+        );
+        assert_eq!(
+            Encoding::Ascii.byte_to_glyph(0x01, AsciiClass::Default),
+            '·'
+        );
+        assert_eq!(
+            Encoding::Ascii.byte_to_glyph(b'.', AsciiClass::Default),
+            '.'
+        );
+        assert_eq!(
+            Encoding::Ascii.byte_to_glyph(0xe9, AsciiClass::Default),
+            '.'
+        );
+        assert_eq!(
+            Encoding::Cp437.byte_to_glyph(0x01, AsciiClass::Default),
+            '☺'
+        );
+    }
+}
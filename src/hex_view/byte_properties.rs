@@ -1,10 +1,12 @@
+use crate::encoding::AsciiClass;
 use crate::hex_view::{
     colorize_byte, make_padding, OutputColorizer, PrioritizedStyle, Priority, StylingCommand,
 };
+use crate::options::{ViewOptions, MAX_INSPECTOR_WIDTH};
 use crossterm::style::{Attributes, Color};
 use crossterm::{style, ErrorKind};
 use lazy_static::lazy_static;
-use std::convert::TryInto;
+use std::cmp;
 use std::io::Write;
 
 lazy_static! {
@@ -90,16 +92,11 @@ fn utf8_into_char(data: &[u8]) -> Result<char, char> {
     Err('�')
 }
 
-fn bytes_to_4_byte_vec(data: &[u8]) -> Vec<u8> {
-    if data.len() >= 4 {
-        data[0..4].to_vec()
-    } else {
-        let mut res = data.to_vec();
-        while res.len() < 4 {
-            res.insert(0, 0);
-        }
-        res
-    }
+fn bytes_to_array<const N: usize>(data: &[u8]) -> [u8; N] {
+    let mut res = [0; N];
+    let len = cmp::min(N, data.len());
+    res[N - len..].copy_from_slice(&data[..len]);
+    res
 }
 
 fn utf16_into_char(data: &[u8]) -> Result<char, char> {
@@ -124,16 +121,71 @@ fn utf16_into_char(data: &[u8]) -> Result<char, char> {
 pub struct BytePropertiesFormatter<'a> {
     data: &'a [u8],
     line: usize,
+    compact: bool,
+    // Interpret integers as 64-bit and show floats
+    wide: bool,
+    ascii_class: AsciiClass,
 }
 
 impl<'a> BytePropertiesFormatter<'a> {
-    pub fn new(data: &'a [u8]) -> Self {
-        assert!(data.len() <= 4);
-        Self { data, line: 0 }
+    pub fn new(data: &'a [u8], options: &ViewOptions) -> Self {
+        assert!(data.len() <= MAX_INSPECTOR_WIDTH);
+        Self {
+            data,
+            line: 0,
+            compact: options.inspector_compact,
+            wide: options.inspector_width >= 8,
+            ascii_class: options.ascii_class,
+        }
     }
 
     pub fn are_all_printed(&self) -> bool {
-        self.line > (BytePropertiesFormatter::height() - 1)
+        self.line > (self.line_count() - 1)
+    }
+
+    fn line_count(&self) -> usize {
+        Self::line_count_for(self.compact, self.wide)
+    }
+
+    fn line_count_for(compact: bool, wide: bool) -> usize {
+        let base = if compact { 3 } else { 5 };
+        if wide {
+            base + 1
+        } else {
+            base
+        }
+    }
+
+    fn int_bits(&self) -> usize {
+        if self.wide {
+            64
+        } else {
+            32
+        }
+    }
+
+    fn unsigned_value(&self) -> String {
+        if self.wide {
+            u64::from_be_bytes(bytes_to_array(self.data)).to_string()
+        } else {
+            u32::from_be_bytes(bytes_to_array(self.data)).to_string()
+        }
+    }
+
+    fn signed_value(&self) -> String {
+        if self.wide {
+            i64::from_be_bytes(bytes_to_array(self.data)).to_string()
+        } else {
+            i32::from_be_bytes(bytes_to_array(self.data)).to_string()
+        }
+    }
+
+    fn first_byte(&self) -> u8 {
+        if !self.data.is_empty() {
+            self.data[0]
+        } else {
+            0
+        }
     }
 
     pub fn draw_line(
@@ -141,27 +193,139 @@ impl<'a> BytePropertiesFormatter<'a> {
         stdout: &mut impl Write,
         colorizer: &OutputColorizer,
     ) -> Result<(), ErrorKind> {
-        let first_byte = if !self.data.is_empty() {
-            self.data[0]
+        if self.compact {
+            self.draw_compact_line(stdout, colorizer)?;
         } else {
-            0
+            self.draw_full_line(stdout, colorizer)?;
+        }
+
+        self.line += 1;
+
+        Ok(())
+    }
+
+    fn draw_compact_line(
+        &self,
+        stdout: &mut impl Write,
+        colorizer: &OutputColorizer,
+    ) -> Result<(), ErrorKind> {
+        let first_byte = self.first_byte();
+
+        match self.line {
+            0 => {
+                colorizer.draw(stdout, " u8: ", &DEFAULT_STYLE)?;
+                colorizer.draw_hex_byte(
+                    stdout,
+                    first_byte,
+                    self.ascii_class,
+                    &colorize_byte(first_byte, self.ascii_class, &DEFAULT_VALUE_STYLE),
+                )?;
+                colorizer.draw(stdout, "/ ", &DEFAULT_STYLE)?;
+                colorizer.draw(stdout, first_byte, &DEFAULT_VALUE_STYLE)?;
+                colorizer.draw(stdout, " / ", &DEFAULT_STYLE)?;
+                colorizer.draw(stdout, format!("{:03o}", first_byte), &DEFAULT_VALUE_STYLE)?;
+                colorizer.draw(stdout, " / 0b", &DEFAULT_STYLE)?;
+                format_binary_byte(stdout, colorizer, first_byte)?;
+                colorizer.draw(stdout, "  i8: ", &DEFAULT_STYLE)?;
+                colorizer.draw(stdout, first_byte as i8, &DEFAULT_VALUE_STYLE)?;
+            }
+            1 => {
+                colorizer.draw(stdout, format!("u{}: ", self.int_bits()), &DEFAULT_STYLE)?;
+                for byte in self.data.iter() {
+                    colorizer.draw_hex_byte(
+                        stdout,
+                        *byte,
+                        self.ascii_class,
+                        &colorize_byte(*byte, self.ascii_class, &DEFAULT_VALUE_STYLE),
+                    )?;
+                }
+                colorizer.draw(stdout, "/ ", &DEFAULT_STYLE)?;
+                colorizer.draw(stdout, self.unsigned_value(), &DEFAULT_VALUE_STYLE)?;
+                colorizer.draw(stdout, format!("  i{}: ", self.int_bits()), &DEFAULT_STYLE)?;
+                colorizer.draw(stdout, self.signed_value(), &DEFAULT_VALUE_STYLE)?;
+            }
+            2 => self.draw_text_line(stdout, colorizer)?,
+            3 if self.wide => self.draw_float_line(stdout, colorizer)?,
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn draw_float_line(
+        &self,
+        stdout: &mut impl Write,
+        colorizer: &OutputColorizer,
+    ) -> Result<(), ErrorKind> {
+        let single = f32::from_be_bytes(bytes_to_array(self.data)).to_string();
+        let len = cmp::min(single.len(), 12);
+
+        colorizer.draw(stdout, "   f32: ", &DEFAULT_STYLE)?;
+        colorizer.draw(stdout, single, &DEFAULT_VALUE_STYLE)?;
+        colorizer.draw(stdout, make_padding(12 - len), &DEFAULT_STYLE)?;
+        colorizer.draw(stdout, "     f64: ", &DEFAULT_STYLE)?;
+        colorizer.draw(
+            stdout,
+            f64::from_be_bytes(bytes_to_array(self.data)),
+            &DEFAULT_VALUE_STYLE,
+        )
+    }
+
+    fn draw_text_line(
+        &self,
+        stdout: &mut impl Write,
+        colorizer: &OutputColorizer,
+    ) -> Result<(), ErrorKind> {
+        colorizer.draw(stdout, " utf-8: ", &DEFAULT_STYLE)?;
+        let len = match utf8_into_char(self.data) {
+            Ok(c) => {
+                let c = format_char(c);
+                let len = c.len();
+                colorizer.draw(stdout, c, &DEFAULT_VALUE_STYLE)?;
+                len
+            }
+            Err(c) => {
+                colorizer.draw(stdout, c, &INVALID_DATA_STYLE)?;
+                1
+            }
         };
 
+        colorizer.draw(stdout, make_padding(12 - len), &DEFAULT_STYLE)?;
+        colorizer.draw(stdout, "  utf-16: ", &DEFAULT_STYLE)?;
+        match utf16_into_char(self.data) {
+            Ok(c) => colorizer.draw(stdout, format_char(c), &DEFAULT_VALUE_STYLE),
+            Err(c) => colorizer.draw(stdout, c, &INVALID_DATA_STYLE),
+        }
+    }
+
+    fn draw_full_line(
+        &self,
+        stdout: &mut impl Write,
+        colorizer: &OutputColorizer,
+    ) -> Result<(), ErrorKind> {
+        let first_byte = self.first_byte();
+
         match self.line {
             0 => {
                 colorizer.draw(stdout, "hex u8: ", &DEFAULT_STYLE)?;
                 colorizer.draw_hex_byte(
                     stdout,
                     first_byte,
-                    &colorize_byte(first_byte, &DEFAULT_VALUE_STYLE),
+                    self.ascii_class,
+                    &colorize_byte(first_byte, self.ascii_class, &DEFAULT_VALUE_STYLE),
                 )?;
 
-                colorizer.draw(stdout, "          hex u32: ", &DEFAULT_STYLE)?;
+                colorizer.draw(
+                    stdout,
+                    format!("          hex u{}: ", self.int_bits()),
+                    &DEFAULT_STYLE,
+                )?;
                 for byte in self.data.iter() {
                     colorizer.draw_hex_byte(
                         stdout,
                         *byte,
-                        &colorize_byte(*byte, &DEFAULT_VALUE_STYLE),
+                        self.ascii_class,
+                        &colorize_byte(*byte, self.ascii_class, &DEFAULT_VALUE_STYLE),
                     )?;
                 }
             }
@@ -170,7 +334,8 @@ impl<'a> BytePropertiesFormatter<'a> {
                 format_binary_byte(stdout, colorizer, first_byte)?;
 
                 colorizer.draw(stdout, "     bin u32: ", &DEFAULT_STYLE)?;
-                for byte in self.data.iter() {
+                // Wider values wouldn't fit on the line
+                for byte in self.data.iter().take(4) {
                     format_binary_byte(stdout, colorizer, *byte)?;
                     colorizer.draw(stdout, ' ', &DEFAULT_STYLE)?;
                 }
@@ -183,12 +348,12 @@ impl<'a> BytePropertiesFormatter<'a> {
                 colorizer.draw(stdout, byte_literal, &DEFAULT_VALUE_STYLE)?;
 
                 colorizer.draw(stdout, make_padding(12 - len), &DEFAULT_STYLE)?;
-                colorizer.draw(stdout, " dec u32: ", &DEFAULT_STYLE)?;
                 colorizer.draw(
                     stdout,
-                    u32::from_be_bytes(bytes_to_4_byte_vec(self.data).try_into().unwrap()),
-                    &DEFAULT_VALUE_STYLE,
+                    format!(" dec u{}: ", self.int_bits()),
+                    &DEFAULT_STYLE,
                 )?;
+                colorizer.draw(stdout, self.unsigned_value(), &DEFAULT_VALUE_STYLE)?;
             }
             3 => {
                 let byte_literal = format!("{}", first_byte as i8);
@@ -198,45 +363,23 @@ impl<'a> BytePropertiesFormatter<'a> {
                 colorizer.draw(stdout, byte_literal, &DEFAULT_VALUE_STYLE)?;
 
                 colorizer.draw(stdout, make_padding(12 - len), &DEFAULT_STYLE)?;
-                colorizer.draw(stdout, " dec i32: ", &DEFAULT_STYLE)?;
                 colorizer.draw(
                     stdout,
-                    i32::from_be_bytes(bytes_to_4_byte_vec(self.data).try_into().unwrap()),
-                    &DEFAULT_VALUE_STYLE,
+                    format!(" dec i{}: ", self.int_bits()),
+                    &DEFAULT_STYLE,
                 )?;
+                colorizer.draw(stdout, self.signed_value(), &DEFAULT_VALUE_STYLE)?;
             }
-            4 => {
-                colorizer.draw(stdout, " utf-8: ", &DEFAULT_STYLE)?;
-                let len = match utf8_into_char(self.data) {
-                    Ok(c) => {
-                        let c = format_char(c);
-                        let len = c.len();
-                        colorizer.draw(stdout, c, &DEFAULT_VALUE_STYLE)?;
-                        len
-                    }
-                    Err(c) => {
-                        colorizer.draw(stdout, c, &INVALID_DATA_STYLE)?;
-                        1
-                    }
-                };
-
-                colorizer.draw(stdout, make_padding(12 - len), &DEFAULT_STYLE)?;
-                colorizer.draw(stdout, "  utf-16: ", &DEFAULT_STYLE)?;
-                match utf16_into_char(self.data) {
-                    Ok(c) => colorizer.draw(stdout, format_char(c), &DEFAULT_VALUE_STYLE),
-                    Err(c) => colorizer.draw(stdout, c, &INVALID_DATA_STYLE),
-                }?;
-            }
+            4 => self.draw_text_line(stdout, colorizer)?,
+            5 if self.wide => self.draw_float_line(stdout, colorizer)?,
             _ => (),
         }
 
-        self.line += 1;
-
         Ok(())
     }
 
-    pub fn height() -> usize {
-        5
+    pub fn height(options: &ViewOptions) -> usize {
+        Self::line_count_for(options.inspector_compact, options.inspector_width >= 8)
     }
 }
 
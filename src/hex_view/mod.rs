@@ -1,3 +1,4 @@
+use crate::encoding::{AsciiClass, ByteClass, Encoding};
 use crossterm::{
     queue,
     style::{self, Color},
@@ -20,10 +21,9 @@ const COLOR_ASCII_OTHER: Color = Color::Rgb {
 };
 const COLOR_NONASCII: Color = Color::Yellow;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Priority {
     Basic,
-    #[allow(dead_code)]
     Mark,
     Selection,
     Cursor,
@@ -32,7 +32,6 @@ pub enum Priority {
 #[derive(Debug, Clone)]
 pub struct PrioritizedStyle {
     style: style::ContentStyle,
-    #[allow(dead_code)]
     priority: Priority,
 }
 
@@ -41,6 +40,8 @@ pub struct StylingCommand {
     start: Option<PrioritizedStyle>,
     mid: Option<PrioritizedStyle>,
     end: Option<PrioritizedStyle>,
+    // The first or last byte of a selection, underlined in the ascii pane
+    boundary: bool,
 }
 
 impl StylingCommand {
@@ -66,11 +67,17 @@ impl StylingCommand {
 
     #[must_use]
     pub fn with_mid_to_end(self) -> Self {
-        let StylingCommand { start, mid, .. } = self;
+        let StylingCommand {
+            start,
+            mid,
+            boundary,
+            ..
+        } = self;
         Self {
             start,
             mid: None,
             end: mid,
+            boundary,
         }
     }
 
@@ -81,6 +88,34 @@ impl StylingCommand {
             start: None,
             mid: None,
             end,
+            boundary: false,
+        }
+    }
+
+    // Replaces the caret parts of the command with `style`, for drawing the caret in the
+    // pane it isn't in
+    #[must_use]
+    pub fn with_linked_caret(self, style: &PrioritizedStyle) -> Self {
+        let link = |part: Option<PrioritizedStyle>| match part {
+            Some(PrioritizedStyle {
+                priority: Priority::Cursor,
+                ..
+            }) => Some(style.clone()),
+            part => part,
+        };
+        Self {
+            start: link(self.start),
+            mid: link(self.mid),
+            end: link(self.end),
+            boundary: self.boundary,
+        }
+    }
+
+    #[must_use]
+    pub fn with_boundary(self) -> Self {
+        Self {
+            boundary: true,
+            ..self
         }
     }
 
@@ -117,21 +152,17 @@ fn queue_style(stdout: &mut impl Write, style: &style::ContentStyle) -> Result<(
     Ok(())
 }
 
-fn get_byte_color(byte: u8) -> Color {
-    if byte == 0x00 {
-        COLOR_NULL
-    } else if byte.is_ascii_graphic() {
-        COLOR_ASCII_PRINTABLE
-    } else if byte.is_ascii_whitespace() {
-        COLOR_ASCII_WHITESPACE
-    } else if byte.is_ascii() {
-        COLOR_ASCII_OTHER
-    } else {
-        COLOR_NONASCII
+fn get_byte_color(byte: u8, class: AsciiClass) -> Color {
+    match class.classify(byte) {
+        ByteClass::Null => COLOR_NULL,
+        ByteClass::Printable => COLOR_ASCII_PRINTABLE,
+        ByteClass::Whitespace => COLOR_ASCII_WHITESPACE,
+        ByteClass::Control => COLOR_ASCII_OTHER,
+        ByteClass::NonAscii => COLOR_NONASCII,
     }
 }
 
-fn colorize_byte(byte: u8, style_cmd: &StylingCommand) -> StylingCommand {
+fn colorize_byte(byte: u8, class: AsciiClass, style_cmd: &StylingCommand) -> StylingCommand {
     let default_content_style = style::ContentStyle {
         foreground_color: None,
         background_color: None,
@@ -142,7 +173,7 @@ fn colorize_byte(byte: u8, style_cmd: &StylingCommand) -> StylingCommand {
 
     style_cmd.clone().with_start_style(PrioritizedStyle {
         style: style::ContentStyle {
-            foreground_color: Some(get_byte_color(byte)),
+            foreground_color: Some(get_byte_color(byte, class)),
             background_color: start_style.background_color,
             attributes: start_style.attributes,
         },
@@ -155,14 +186,15 @@ pub fn make_padding(len: usize) -> &'static str {
     &"                                                                "[..len]
 }
 
-struct ByteAsciiRepr(u8);
+// The last field turns on the glyphs for whitespace and control bytes
+struct ByteAsciiRepr(u8, Encoding, AsciiClass, bool);
 
 impl fmt::Display for ByteAsciiRepr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.0.is_ascii_graphic() || self.0 == 0x20 {
-            write!(f, "{}", char::from(self.0))
+        if self.3 {
+            write!(f, "{}", self.1.byte_to_glyph(self.0, self.2))
         } else {
-            write!(f, ".")
+            write!(f, "{}", self.1.byte_to_char(self.0, self.2))
         }
     }
 }
@@ -178,9 +210,10 @@ impl OutputColorizer {
         &self,
         stdout: &mut impl Write,
         byte: u8,
+        class: AsciiClass,
         style: &StylingCommand,
     ) -> Result<(), ErrorKind> {
-        let style_cmd = colorize_byte(byte, style);
+        let style_cmd = colorize_byte(byte, class, style);
 
         if let Some(start_cmd) = style_cmd.start_style() {
             queue_style(stdout, start_cmd)?;
@@ -205,15 +238,29 @@ impl OutputColorizer {
         &self,
         stdout: &mut impl Write,
         byte: u8,
+        encoding: Encoding,
+        class: AsciiClass,
+        glyphs: bool,
         style: &StylingCommand,
     ) -> Result<(), ErrorKind> {
-        let style_cmd = colorize_byte(byte, style);
+        let style_cmd = colorize_byte(byte, class, style);
 
         if let Some(start_cmd) = style_cmd.start_style() {
             queue_style(stdout, start_cmd)?;
         }
 
-        queue!(stdout, style::Print(format!("{}", ByteAsciiRepr(byte))))?;
+        if style_cmd.boundary {
+            queue!(stdout, style::SetAttribute(style::Attribute::Underlined))?;
+        }
+
+        queue!(
+            stdout,
+            style::Print(format!("{}", ByteAsciiRepr(byte, encoding, class, glyphs)))
+        )?;
+
+        if style_cmd.boundary {
+            queue!(stdout, style::SetAttribute(style::Attribute::NoUnderline))?;
+        }
 
         if let Some(end_cmd) = style_cmd.end_style() {
             queue_style(stdout, end_cmd)?;
@@ -1,14 +1,16 @@
+use std::borrow::Cow;
 use std::cell::Cell;
 use std::cmp;
 use std::collections::BTreeSet;
-use std::fmt;
 use std::io::Write;
 use std::ops::Range;
 use std::time;
 
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
     execute, queue, style,
     style::{Color, Stylize},
     terminal, QueueableCommand, Result,
@@ -16,28 +18,44 @@ use crossterm::{
 use xi_rope::Interval;
 
 use super::byte_properties::BytePropertiesFormatter;
-use super::{make_padding, PrioritizedStyle, Priority, StylingCommand};
+use super::{make_padding, ByteAsciiRepr, PrioritizedStyle, Priority, StylingCommand};
 use crate::buffer::*;
+use crate::cancel;
 use crate::hex_view::OutputColorizer;
 use crate::modes;
 use crate::modes::mode::{DirtyBytes, Mode, ModeTransition};
-use crate::selection::Direction;
+use crate::modes::search::Pattern;
+use crate::selection::{Direction, Selection};
+use crate::ViewOptions;
 
 const VERTICAL: &str = "│";
+// From empty to fully selected
+const MINIMAP_SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+// Background colors of layers, reused in order when there are more layers
+const LAYER_COLORS: [Color; 4] = [
+    Color::Rgb {
+        r: 64,
+        g: 128,
+        b: 160,
+    },
+    Color::Rgb {
+        r: 160,
+        g: 96,
+        b: 160,
+    },
+    Color::Rgb {
+        r: 96,
+        g: 160,
+        b: 96,
+    },
+    Color::Rgb {
+        r: 176,
+        g: 128,
+        b: 80,
+    },
+];
 const LEFTARROW: &str = "";
 
-struct MixedRepr(u8);
-
-impl fmt::Display for MixedRepr {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.0.is_ascii_graphic() || self.0 == 0x20 {
-            write!(f, "{}", char::from(self.0))
-        } else {
-            write!(f, "<{:02x}>", self.0)
-        }
-    }
-}
-
 trait StatusLinePrompter: Mode {
     fn render_with_size(
         &self,
@@ -310,27 +328,64 @@ impl StatusLinePrompter for modes::command::Command {
 pub struct HexView {
     buffers: Buffers,
     size: (u16, u16),
-    bytes_per_line: usize,
+    options: ViewOptions,
     start_offset: usize,
     last_visible_rows: Cell<usize>,
     last_visible_prompt_col: Cell<usize>,
     last_draw_time: time::Duration,
+    last_operation_time: time::Duration,
+    lookahead_event: Option<Event>,
+    mode_status: Option<String>,
+    // What the match count in `mode_status` was computed for, so that it's only recounted when
+    // the pattern or the selections change
+    mode_status_key: Option<(Pattern, Selection, bool, usize)>,
+    mouse_captured: bool,
+    // When off, the view is drawn over the normal screen and its last frame is left behind
+    alt_screen: bool,
     colorizer: OutputColorizer,
 
     mode: Box<dyn Mode>,
     info: Option<String>,
 }
 
+// Columns reserved for the byte inspector right of the ASCII column
+const INSPECTOR_COLUMNS: usize = 34;
+
+// The largest power-of-two bytes per line whose row fits in `width` columns
+fn fit_bytes_per_line(width: usize) -> usize {
+    // padding + 3 per hex byte + separator + 1 per ASCII byte + padding + separator
+    let row_width = |bpl: usize| 1 + bpl * 3 + 2 + bpl + 1 + 2 + INSPECTOR_COLUMNS;
+    let mut bpl = 1;
+    while row_width(bpl * 2) <= width {
+        bpl *= 2;
+    }
+    bpl
+}
+
+// The size assumed when the terminal can't tell its own, like when output isn't a tty
+const DEFAULT_SIZE: (u16, u16) = (80, 24);
+
 impl HexView {
     pub fn with_buffers(buffers: Buffers) -> HexView {
+        HexView::with_buffers_and_size(buffers, terminal::size().unwrap_or(DEFAULT_SIZE))
+    }
+
+    // A view of `size` columns and rows, whatever size the terminal is
+    pub fn with_buffers_and_size(buffers: Buffers, size: (u16, u16)) -> HexView {
         HexView {
             buffers,
-            bytes_per_line: 0x10,
+            options: ViewOptions::new(),
             start_offset: 0,
-            size: terminal::size().unwrap(),
+            size,
             last_visible_rows: Cell::new(0),
             last_visible_prompt_col: Cell::new(0),
             last_draw_time: Default::default(),
+            last_operation_time: Default::default(),
+            lookahead_event: None,
+            mode_status: None,
+            mode_status_key: None,
+            mouse_captured: false,
+            alt_screen: true,
             colorizer: OutputColorizer::new(),
 
             mode: Box::new(modes::normal::Normal::new()),
@@ -338,8 +393,16 @@ impl HexView {
         }
     }
 
+    pub fn set_info(&mut self, info: String) {
+        self.info = Some(info);
+    }
+
+    pub fn set_alt_screen(&mut self, alt_screen: bool) {
+        self.alt_screen = alt_screen;
+    }
+
     pub fn set_bytes_per_line(&mut self, bpl: usize) {
-        self.bytes_per_line = bpl;
+        self.options.bytes_per_line = bpl;
     }
 
     fn draw_hex_row(
@@ -348,7 +411,8 @@ impl HexView {
         styled_bytes: impl IntoIterator<Item = (u8, StylingCommand)>,
     ) -> Result<()> {
         for (byte, style_cmd) in styled_bytes.into_iter() {
-            self.colorizer.draw_hex_byte(stdout, byte, &style_cmd)?;
+            self.colorizer
+                .draw_hex_byte(stdout, byte, self.options.ascii_class, &style_cmd)?;
         }
         Ok(())
     }
@@ -359,7 +423,14 @@ impl HexView {
         styled_bytes: impl IntoIterator<Item = (u8, StylingCommand)>,
     ) -> Result<()> {
         for (byte, style_cmd) in styled_bytes.into_iter() {
-            self.colorizer.draw_ascii_byte(stdout, byte, &style_cmd)?;
+            self.colorizer.draw_ascii_byte(
+                stdout,
+                byte,
+                self.options.encoding,
+                self.options.ascii_class,
+                self.options.whitespace_glyphs,
+                &style_cmd,
+            )?;
         }
         Ok(())
     }
@@ -374,7 +445,7 @@ impl HexView {
             return None;
         }
         let normalized_offset = offset - self.start_offset;
-        let bytes_per_line = self.bytes_per_line;
+        let bytes_per_line = self.options.bytes_per_line;
         let max_bytes = bytes_per_line * self.size.1 as usize;
         if normalized_offset > max_bytes {
             return None;
@@ -382,6 +453,64 @@ impl HexView {
         Some((normalized_offset / bytes_per_line) as u16)
     }
 
+    // Columns left empty after the hex and the ascii of a row of `row_len` bytes
+    fn row_padding(&self, row_len: usize) -> (usize, usize) {
+        let bytes_per_line = self.options.bytes_per_line;
+        let missing = if row_len == 0 {
+            bytes_per_line
+        } else {
+            (bytes_per_line - row_len) % bytes_per_line
+        };
+        (missing * 3, missing + 1)
+    }
+
+    // A row laid out like draw_row does, without styles and with its offset in front
+    fn row_text(&self, bytes: &[u8], offset: usize) -> String {
+        let (hex_padding, ascii_padding) = self.row_padding(bytes.len());
+        let hex = bytes
+            .iter()
+            .map(|byte| format!("{:02x} ", byte))
+            .collect::<String>();
+        let ascii = bytes
+            .iter()
+            .map(|&byte| {
+                ByteAsciiRepr(
+                    byte,
+                    self.options.encoding,
+                    self.options.ascii_class,
+                    self.options.whitespace_glyphs,
+                )
+                .to_string()
+            })
+            .collect::<String>();
+        let row = format!(
+            "{:08x} {}{}{} {}{}{}",
+            offset,
+            hex,
+            make_padding(hex_padding),
+            VERTICAL,
+            ascii,
+            make_padding(ascii_padding),
+            VERTICAL
+        );
+        row.trim_end().to_owned()
+    }
+
+    // The rows on screen as text, one per line
+    fn visible_rows_text(&self) -> String {
+        let data = &self.buffers.current().data;
+        let visible = self.visible_bytes();
+        let visible = visible.start..cmp::min(visible.end, data.len());
+        let bytes = data.slice_to_cow(visible.clone());
+        bytes
+            .chunks(self.options.bytes_per_line)
+            .enumerate()
+            .map(|(i, row)| {
+                self.row_text(row, visible.start + i * self.options.bytes_per_line) + "\n"
+            })
+            .collect()
+    }
+
     fn draw_row(
         &self,
         stdout: &mut impl Write,
@@ -398,16 +527,24 @@ impl HexView {
             stdout,
             style::Print(" ".to_string()), // Padding
         )?;
+        let caret_in_hex = self.mode.caret_in_hex();
+        let linked_caret_style = &self.linked_caret_style();
+        let pane_commands = |linked: bool| {
+            mark_commands.iter().map(move |cmd| {
+                if linked {
+                    cmd.clone().with_linked_caret(linked_caret_style)
+                } else {
+                    cmd.clone()
+                }
+            })
+        };
+
         self.draw_hex_row(
             stdout,
-            bytes.iter().copied().zip(mark_commands.iter().cloned()),
+            bytes.iter().copied().zip(pane_commands(!caret_in_hex)),
         )?;
 
-        let mut padding_length = if bytes.is_empty() {
-            self.bytes_per_line * 3
-        } else {
-            (self.bytes_per_line - bytes.len()) % self.bytes_per_line * 3
-        };
+        let (mut padding_length, mut ascii_padding_length) = self.row_padding(bytes.len());
 
         if let Some(style_cmd) = &end_style {
             padding_length -= 2;
@@ -423,22 +560,16 @@ impl HexView {
 
         self.draw_ascii_row(
             stdout,
-            bytes.iter().copied().zip(mark_commands.iter().cloned()),
+            bytes.iter().copied().zip(pane_commands(caret_in_hex)),
         )?;
 
-        let mut padding_length = if bytes.is_empty() {
-            self.bytes_per_line
-        } else {
-            (self.bytes_per_line - bytes.len()) % self.bytes_per_line
-        } + 1;
-
         if let Some(style_cmd) = end_style {
-            padding_length -= 1;
+            ascii_padding_length -= 1;
             self.colorizer
                 .draw(stdout, ' ', &style_cmd.take_end_only())?;
         }
 
-        queue!(stdout, style::Print(make_padding(padding_length)))?;
+        queue!(stdout, style::Print(make_padding(ascii_padding_length)))?;
         self.draw_separator(stdout)?;
 
         byte_properties.draw_line(stdout, &self.colorizer)?;
@@ -452,7 +583,7 @@ impl HexView {
         self.start_offset
             ..cmp::min(
                 self.buffers.current().data.len() + 1,
-                self.start_offset + (self.size.1 - 1) as usize * self.bytes_per_line,
+                self.start_offset + (self.size.1 - 1) as usize * self.options.bytes_per_line,
             )
     }
 
@@ -504,7 +635,30 @@ impl HexView {
         PrioritizedStyle {
             style: style::ContentStyle::new()
                 .with(style::Color::Black)
-                .on(style::Color::DarkGrey),
+                .on(self.options.inactive_caret_color),
+            priority: Priority::Cursor,
+        }
+    }
+
+    // The end of the main selection that stays put when extending
+    fn tail_style(&self) -> PrioritizedStyle {
+        PrioritizedStyle {
+            style: style::ContentStyle::new()
+                .with(style::Color::Black)
+                .on(self.options.tail_color),
+            priority: Priority::Selection,
+        }
+    }
+
+    fn linked_caret_style(&self) -> PrioritizedStyle {
+        PrioritizedStyle {
+            style: style::ContentStyle::new()
+                .with(style::Color::White)
+                .on(style::Color::Rgb {
+                    r: 58,
+                    g: 59,
+                    b: 72,
+                }),
             priority: Priority::Cursor,
         }
     }
@@ -516,8 +670,76 @@ impl HexView {
         }
     }
 
+    fn changed_byte_style(&self) -> PrioritizedStyle {
+        PrioritizedStyle {
+            style: style::ContentStyle::new().on(style::Color::Rgb {
+                r: 96,
+                g: 32,
+                b: 32,
+            }),
+            priority: Priority::Mark,
+        }
+    }
+
+    // The style of the last highlight matching at each byte in `visible`
+    fn highlight_styles(&self, visible: Range<usize>) -> Vec<Option<PrioritizedStyle>> {
+        let mut styles = vec![None; visible.len()];
+        let data = &self.buffers.current().data;
+        for highlight in &self.options.highlights {
+            let style = PrioritizedStyle {
+                style: style::ContentStyle::new()
+                    .with(style::Color::Black)
+                    .on(highlight.color),
+                priority: Priority::Mark,
+            };
+            // Include the matches that start or end off screen
+            let reach = highlight.pattern.pieces.len() - 1;
+            let window_start = visible.start.saturating_sub(reach);
+            let window_end = cmp::min(visible.end + reach, data.len());
+            let window = data.slice_to_cow(window_start..window_end);
+            for matched in highlight.pattern.matches_in(&window).unwrap_or_default() {
+                let start = cmp::max(window_start + matched.start, visible.start) - visible.start;
+                let end =
+                    cmp::min(window_start + matched.end, visible.end).saturating_sub(visible.start);
+                for byte_style in styles.get_mut(start..end).unwrap_or_default() {
+                    *byte_style = Some(style.clone());
+                }
+            }
+        }
+        styles
+    }
+
+    // The style of the topmost visible layer covering each byte in `visible`, or else of the
+    // highlight there
+    fn layer_styles(&self, visible: Range<usize>) -> Vec<Option<PrioritizedStyle>> {
+        let mut styles = self.highlight_styles(visible.clone());
+        let layers = self.buffers.current().layers.iter().enumerate();
+        for (i, layer) in layers.filter(|(_, layer)| layer.visible) {
+            let style = PrioritizedStyle {
+                style: style::ContentStyle::new()
+                    .with(style::Color::Black)
+                    .on(LAYER_COLORS[i % LAYER_COLORS.len()]),
+                priority: Priority::Mark,
+            };
+            for region in layer.selection.regions_in_range(visible.start, visible.end) {
+                let start = cmp::max(region.min(), visible.start) - visible.start;
+                let end = cmp::min(region.max() + 1, visible.end) - visible.start;
+                for byte_style in &mut styles[start..end] {
+                    *byte_style = Some(style.clone());
+                }
+            }
+        }
+        styles
+    }
+
     fn mark_commands(&self, visible: Range<usize>) -> Vec<StylingCommand> {
         let mut mark_commands = vec![StylingCommand::default(); visible.len()];
+        let changed_bytes = if self.options.show_changes {
+            self.buffers.current().changed_in_range(visible.clone())
+        } else {
+            vec![]
+        };
+        let layer_styles = self.layer_styles(visible.clone());
         let mut selected_regions = self
             .buffers
             .current()
@@ -578,20 +800,50 @@ impl HexView {
                         .clone()
                         .with_end_style(command_stack[command_stack.len() - 2].clone());
                 }
+                let region = selected_regions[0];
+                if region.min() != region.max() && (region.min() == i || region.max() == i) {
+                    mark_commands[normalized] = mark_commands[normalized].clone().with_boundary();
+                }
+                if region.is_main() && region.tail != region.caret && region.tail == i {
+                    let tail_cmd = mark_commands[normalized].clone();
+                    let after_tail = tail_cmd
+                        .end
+                        .clone()
+                        .unwrap_or_else(|| command_stack.last().unwrap().clone());
+                    mark_commands[normalized] = tail_cmd
+                        .with_start_style(self.tail_style())
+                        .with_end_style(after_tail);
+                }
             }
 
-            if i % self.bytes_per_line == 0 && mark_commands[normalized].start_style().is_none() {
+            if i % self.options.bytes_per_line == 0
+                && mark_commands[normalized].start_style().is_none()
+            {
                 // line starts: restore applied style
                 mark_commands[normalized] = mark_commands[normalized]
                     .clone()
                     .with_start_style(command_stack.last().unwrap().clone());
-            } else if (i + 1) % self.bytes_per_line == 0 {
+            } else if (i + 1) % self.options.bytes_per_line == 0 {
                 // line ends: apply default style
                 mark_commands[normalized] = mark_commands[normalized]
                     .clone()
                     .with_end_style(self.default_style());
             }
 
+            // Selections take precedence over change marks, then layers, then highlights
+            if command_stack.len() == 1 && changed_bytes.get(normalized) == Some(&true) {
+                mark_commands[normalized] = mark_commands[normalized]
+                    .clone()
+                    .with_start_style(self.changed_byte_style())
+                    .with_end_style(self.default_style());
+            } else if let (1, Some(layer_style)) = (command_stack.len(), &layer_styles[normalized])
+            {
+                mark_commands[normalized] = mark_commands[normalized]
+                    .clone()
+                    .with_start_style(layer_style.clone())
+                    .with_end_style(self.default_style());
+            }
+
             if !selected_regions.is_empty() && selected_regions[0].max() == i {
                 // Must be popped after line config
                 command_stack.pop();
@@ -611,7 +863,7 @@ impl HexView {
             length += 3;
         }
         length += 1; // leftarrow
-        length += 2 + self.mode.name().len();
+        length += 2 + self.mode_label().chars().count();
         length += 1; // leftarrow
         length += format!(
             " {} sels ({}) ",
@@ -619,18 +871,135 @@ impl HexView {
             buf.selection.main_selection + 1
         )
         .len();
+        if let Some(pending_status) = self.pending_status() {
+            length += 1; // leftarrow
+            length += pending_status.chars().count();
+        }
+        if let Some(mode_status) = &self.mode_status {
+            length += 1; // leftarrow
+            length += mode_status.len();
+        }
+        if let Some(selection_status) = self.selection_status() {
+            length += 1; // leftarrow
+            length += selection_status.len();
+        }
         length += 1; // leftarrow
-        if !buf.data.is_empty() {
-            length += format!(
-                " {:x}/{:x} ",
-                buf.selection.main_cursor_offset(),
-                buf.data.len() - 1
+        length += self.position_status().len();
+        if let Some(debug) = self.debug_status() {
+            length += 1; // leftarrow
+            length += debug.chars().count();
+        }
+        length
+    }
+
+    // The main cursor's offset, followed by how far into the buffer it is if the ruler is on
+    fn position_status(&self) -> String {
+        let buf = self.buffers.current();
+        if buf.data.is_empty() {
+            return " empty ".to_owned();
+        }
+        let offset = buf.selection.main_cursor_offset();
+        if self.options.ruler {
+            format!(
+                " {:x}/{:x} {}% ",
+                offset,
+                buf.data.len() - 1,
+                cmp::min(offset * 100 / buf.data.len(), 100)
             )
-            .len();
         } else {
-            length += " empty ".len();
+            format!(" {:x}/{:x} ", offset, buf.data.len() - 1)
+        }
+    }
+
+    // The extent of the main selection while it covers more than one byte
+    fn selection_status(&self) -> Option<String> {
+        let main = self.buffers.current().selection.main();
+        if main.len() > 1 {
+            Some(format!(
+                " {:x}..{:x} (0x{:x}) ",
+                main.min(),
+                main.max() + 1,
+                main.len()
+            ))
+        } else {
+            None
+        }
+    }
+
+    // Context for the current mode, shown next to the selection count
+    fn update_mode_status(&mut self) {
+        let search = match self.mode.as_any().downcast_ref::<modes::search::Search>() {
+            Some(search) if !search.pattern.pieces.is_empty() && search.is_search() => search,
+            _ => {
+                self.mode_status = None;
+                self.mode_status_key = None;
+                return;
+            }
+        };
+        let key = (
+            search.pattern.clone(),
+            self.buffers.current().selection.clone(),
+            self.options.search_overlapping,
+            self.options.search_align,
+        );
+        if self.mode_status_key.as_ref() == Some(&key) {
+            return;
+        }
+        let matches = search.pattern.map_selections_to_matches(
+            self.buffers.current(),
+            self.options.search_overlapping,
+            self.options.search_align,
+        );
+        self.mode_status = Some(match matches {
+            Ok(matches) => {
+                self.mode_status_key = Some(key);
+                let count: usize = matches.iter().map(Vec::len).sum();
+                format!(" {} matches ", count)
+            }
+            Err(_) => {
+                // The escape was meant for the search itself
+                cancel::push_pending_event(Event::Key(KeyEvent::new(
+                    KeyCode::Esc,
+                    KeyModifiers::NONE,
+                )));
+                self.mode_status_key = None;
+                " ? matches ".to_owned()
+            }
+        });
+    }
+
+    fn mode_label(&self) -> Cow<'static, str> {
+        if self.options.debug_modes {
+            self.mode.mode_stack().join(" > ").into()
+        } else {
+            self.mode.name()
+        }
+    }
+
+    // A dot while edits are gathered into one undo step, and `rec` while recording a patch
+    fn pending_status(&self) -> Option<String> {
+        let buf = self.buffers.current();
+        let indicators = [
+            buf.has_uncommitted_edits().then_some("\u{25cf}"),
+            buf.recording_patch.then_some("rec"),
+        ];
+        let indicators = indicators.iter().flatten().copied().collect::<Vec<_>>();
+        if indicators.is_empty() {
+            None
+        } else {
+            Some(format!(" {} ", indicators.join(" ")))
+        }
+    }
+
+    fn debug_status(&self) -> Option<String> {
+        if self.options.debug {
+            Some(format!(
+                " draw {:?} op {:?} ",
+                self.last_draw_time, self.last_operation_time
+            ))
+        } else {
+            None
         }
-        length
     }
 
     fn draw_statusline_here(&self, stdout: &mut impl Write) -> Result<()> {
@@ -657,7 +1026,7 @@ impl HexView {
                     .on(Color::Red)
             ),
             style::PrintStyledContent(
-                style::style(format!(" {} ", self.mode.name()))
+                style::style(format!(" {} ", self.mode_label()))
                     .with(Color::AnsiValue(16))
                     .on(Color::DarkYellow)
             ),
@@ -676,32 +1045,151 @@ impl HexView {
                 .on(Color::White)
             ),
         )?;
-        if !buf.data.is_empty() {
+        let mut last_color = Color::White;
+        if let Some(pending_status) = self.pending_status() {
             queue!(
                 stdout,
                 style::PrintStyledContent(
-                    style::style(LEFTARROW).with(Color::Blue).on(Color::White)
+                    style::style(LEFTARROW)
+                        .with(Color::DarkMagenta)
+                        .on(last_color)
                 ),
                 style::PrintStyledContent(
-                    style::style(format!(
-                        " {:x}/{:x} ",
-                        buf.selection.main_cursor_offset(),
-                        buf.data.len() - 1,
-                    ))
-                    .with(Color::White)
-                    .on(Color::Blue),
+                    style::style(pending_status)
+                        .with(Color::White)
+                        .on(Color::DarkMagenta)
                 ),
             )?;
-        } else {
+            last_color = Color::DarkMagenta;
+        }
+        if let Some(mode_status) = &self.mode_status {
+            queue!(
+                stdout,
+                style::PrintStyledContent(
+                    style::style(LEFTARROW).with(Color::DarkCyan).on(last_color)
+                ),
+                style::PrintStyledContent(
+                    style::style(mode_status)
+                        .with(Color::White)
+                        .on(Color::DarkCyan)
+                ),
+            )?;
+            last_color = Color::DarkCyan;
+        }
+        if let Some(selection_status) = self.selection_status() {
             queue!(
                 stdout,
                 style::PrintStyledContent(
-                    style::style(LEFTARROW).with(Color::Blue).on(Color::White)
+                    style::style(LEFTARROW)
+                        .with(Color::DarkGreen)
+                        .on(last_color)
                 ),
                 style::PrintStyledContent(
-                    style::style(" empty ").with(Color::White).on(Color::Blue),
+                    style::style(selection_status)
+                        .with(Color::White)
+                        .on(Color::DarkGreen)
                 ),
             )?;
+            last_color = Color::DarkGreen;
+        }
+        queue!(
+            stdout,
+            style::PrintStyledContent(style::style(LEFTARROW).with(Color::Blue).on(last_color)),
+            style::PrintStyledContent(
+                style::style(self.position_status())
+                    .with(Color::White)
+                    .on(Color::Blue),
+            ),
+        )?;
+        if let Some(debug) = self.debug_status() {
+            queue!(
+                stdout,
+                style::PrintStyledContent(
+                    style::style(LEFTARROW)
+                        .with(Color::DarkGrey)
+                        .on(Color::Blue)
+                ),
+                style::PrintStyledContent(
+                    style::style(debug).with(Color::White).on(Color::DarkGrey),
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn minimap_rows(&self) -> usize {
+        (self.size.1 as usize).saturating_sub(1) // -1 for statusline
+    }
+
+    // The bytes summarized by a row of the minimap
+    fn minimap_range(&self, row: usize) -> Range<usize> {
+        let len = self.buffers.current().data.len();
+        let rows = self.minimap_rows();
+        row * len / rows..(row + 1) * len / rows
+    }
+
+    fn draw_minimap(&self, stdout: &mut impl Write) -> Result<()> {
+        if !self.options.minimap {
+            return Ok(());
+        }
+
+        let selection = &self.buffers.current().selection;
+        let visible = self.visible_bytes();
+        for row in 0..self.minimap_rows() {
+            let range = self.minimap_range(row);
+            let shade = if range.is_empty() {
+                0
+            } else {
+                let selected: usize = selection
+                    .regions_in_range(range.start, range.end - 1)
+                    .iter()
+                    .map(|region| {
+                        cmp::min(region.max() + 1, range.end)
+                            .saturating_sub(cmp::max(region.min(), range.start))
+                    })
+                    .sum();
+                // Round up so that any selected byte shows up
+                (selected * (MINIMAP_SHADES.len() - 1)).div_ceil(range.len())
+            };
+            // Highlight the rows that are on screen
+            let color = if range.start < visible.end && visible.start <= range.end {
+                Color::White
+            } else {
+                Color::DarkGrey
+            };
+            queue!(
+                stdout,
+                cursor::MoveTo(self.size.0.saturating_sub(1), row as u16),
+                style::PrintStyledContent(style::style(MINIMAP_SHADES[shade]).with(color)),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn minimap_click(&mut self, stdout: &mut impl Write, column: u16, row: u16) -> Result<()> {
+        if !self.options.minimap
+            || column != self.size.0.saturating_sub(1)
+            || row as usize >= self.minimap_rows()
+            || self.buffers.current().data.is_empty()
+        {
+            return Ok(());
+        }
+        let offset = self.minimap_range(row as usize).start;
+        let dirty = self.buffers.current_mut().select_at(offset);
+        self.transition_dirty_bytes(stdout, dirty)?;
+        self.draw_minimap(stdout)
+    }
+
+    // Mouse events are only needed for the minimap, and capturing them would
+    // otherwise prevent selecting text in the terminal
+    fn update_mouse_capture(&mut self, stdout: &mut impl Write) -> Result<()> {
+        if self.options.minimap != self.mouse_captured {
+            if self.options.minimap {
+                queue!(stdout, event::EnableMouseCapture)?;
+            } else {
+                queue!(stdout, event::DisableMouseCapture)?;
+            }
+            self.mouse_captured = self.options.minimap;
         }
         Ok(())
     }
@@ -766,6 +1254,32 @@ impl HexView {
         })
     }
 
+    // The bytes at the inspector's pin, or else at the main caret if it's visible
+    fn inspected_bytes<'a>(
+        &'a self,
+        visible_bytes: Range<usize>,
+        visible_bytes_cow: &'a [u8],
+    ) -> Cow<'a, [u8]> {
+        let width = self.options.inspector_width;
+        if let Some(pin) = self.options.inspector_pin {
+            let data = &self.buffers.current().data;
+            let start = cmp::min(pin, data.len());
+            return data.slice_to_cow(start..cmp::min(data.len(), start + width));
+        }
+        self.buffers
+            .current()
+            .selection
+            .regions_in_range(visible_bytes.start, visible_bytes.end)
+            .iter()
+            .find(|region| region.is_main())
+            .map(|v| {
+                let start = v.caret - visible_bytes.start;
+                let end = cmp::min(visible_bytes_cow.len(), start + width);
+                Cow::Borrowed(&visible_bytes_cow[start..end])
+            })
+            .unwrap_or_default()
+    }
+
     fn draw_rows(&self, stdout: &mut impl Write, invalidated_rows: &BTreeSet<u16>) -> Result<()> {
         let visible_bytes = self.visible_bytes();
         let start_index = visible_bytes.start;
@@ -780,39 +1294,23 @@ impl HexView {
         let max_bytes = visible_bytes_cow.len();
         let mark_commands = self.mark_commands(visible_bytes.clone());
 
-        let current_bytes = self
-            .buffers
-            .current()
-            .selection
-            .regions_in_range(visible_bytes.start, visible_bytes.end)
-            .iter()
-            .find(|region| region.is_main())
-            .map(|v| {
-                let start = v.caret - start_index;
-                let end = if start + 4 > visible_bytes_cow.len() {
-                    visible_bytes_cow.len()
-                } else {
-                    start + 4
-                };
-                &visible_bytes_cow[start..end]
-            })
-            .unwrap_or_else(|| &[]);
-
-        let mut byte_properties = BytePropertiesFormatter::new(current_bytes);
+        let current_bytes = self.inspected_bytes(visible_bytes.clone(), &visible_bytes_cow);
+        let mut byte_properties = BytePropertiesFormatter::new(&current_bytes, &self.options);
 
-        for i in visible_bytes.step_by(self.bytes_per_line) {
+        for i in visible_bytes.step_by(self.options.bytes_per_line) {
             if !invalidated_rows.contains(&self.offset_to_row(i).unwrap()) {
                 continue;
             }
 
             let normalized_i = i - start_index;
-            let normalized_end = std::cmp::min(max_bytes, normalized_i + self.bytes_per_line);
+            let normalized_end =
+                std::cmp::min(max_bytes, normalized_i + self.options.bytes_per_line);
             self.draw_row(
                 stdout,
                 &visible_bytes_cow[normalized_i..normalized_end],
                 i,
                 &mark_commands[normalized_i..normalized_end],
-                if i + self.bytes_per_line > self.buffers.current().data.len() {
+                if i + self.options.bytes_per_line > self.buffers.current().data.len() {
                     self.overflow_cursor_style()
                 } else {
                     None
@@ -821,15 +1319,11 @@ impl HexView {
             )?;
         }
 
-        let a = end_index / self.bytes_per_line;
-        let mut offset = (if end_index % self.bytes_per_line == 0 {
-            a
-        } else {
-            a + 1
-        }) * self.bytes_per_line;
+        let mut offset =
+            end_index.div_ceil(self.options.bytes_per_line) * self.options.bytes_per_line;
         while !byte_properties.are_all_printed() {
             self.draw_row(stdout, &[], offset, &[], None, &mut byte_properties)?;
-            offset += self.bytes_per_line;
+            offset += self.options.bytes_per_line;
         }
 
         Ok(())
@@ -856,35 +1350,19 @@ impl HexView {
         let max_bytes = visible_bytes_cow.len();
         let mark_commands = self.mark_commands(visible_bytes.clone());
 
-        let current_bytes = self
-            .buffers
-            .current()
-            .selection
-            .regions_in_range(visible_bytes.start, visible_bytes.end)
-            .iter()
-            .find(|region| region.is_main())
-            .map(|v| {
-                let start = v.caret - start_index;
-                let end = if start + 4 > visible_bytes_cow.len() {
-                    visible_bytes_cow.len()
-                } else {
-                    start + 4
-                };
-                &visible_bytes_cow[start..end]
-            })
-            .unwrap_or_else(|| &[]);
-
-        let mut byte_properties = BytePropertiesFormatter::new(current_bytes);
+        let current_bytes = self.inspected_bytes(visible_bytes.clone(), &visible_bytes_cow);
+        let mut byte_properties = BytePropertiesFormatter::new(&current_bytes, &self.options);
 
-        for i in visible_bytes.step_by(self.bytes_per_line) {
+        for i in visible_bytes.step_by(self.options.bytes_per_line) {
             let normalized_i = i - start_index;
-            let normalized_end = std::cmp::min(max_bytes, normalized_i + self.bytes_per_line);
+            let normalized_end =
+                std::cmp::min(max_bytes, normalized_i + self.options.bytes_per_line);
             self.draw_row(
                 stdout,
                 &visible_bytes_cow[normalized_i..normalized_end],
                 i,
                 &mark_commands[normalized_i..normalized_end],
-                if i + self.bytes_per_line > self.buffers.current().data.len() {
+                if i + self.options.bytes_per_line > self.buffers.current().data.len() {
                     self.overflow_cursor_style()
                 } else {
                     None
@@ -893,23 +1371,19 @@ impl HexView {
             )?;
         }
 
-        let a = end_index / self.bytes_per_line;
-        let mut offset = (if end_index % self.bytes_per_line == 0 {
-            a
-        } else {
-            a + 1
-        }) * self.bytes_per_line;
+        let mut offset =
+            end_index.div_ceil(self.options.bytes_per_line) * self.options.bytes_per_line;
         while !byte_properties.are_all_printed() {
             self.draw_row(stdout, &[], offset, &[], None, &mut byte_properties)?;
-            offset += self.bytes_per_line;
+            offset += self.options.bytes_per_line;
         }
 
-        let new_full_rows =
-            (end_index - start_index + self.bytes_per_line - 1) / self.bytes_per_line;
+        let new_full_rows = (end_index - start_index).div_ceil(self.options.bytes_per_line);
         if new_full_rows != self.last_visible_rows.get() {
             self.last_visible_rows.set(new_full_rows);
         }
 
+        self.draw_minimap(stdout)?;
         self.draw_statusline(stdout)?;
 
         Ok(begin.elapsed())
@@ -919,34 +1393,47 @@ impl HexView {
         match event {
             Event::Resize(x, y) => {
                 self.size = (x, y);
-                self.draw(stdout)?;
-                Ok(())
+                self.maybe_update_offset_and_draw(stdout)
             }
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                ..
+            }) => self.minimap_click(stdout, column, row),
             Event::Key(KeyEvent { code, modifiers }) => match (code, modifiers) {
                 (KeyCode::Char('e'), KeyModifiers::CONTROL) => {
                     let buffer = self.buffers.current_mut();
-                    let max_bytes = buffer.data.len();
-                    let bytes_per_line = self.bytes_per_line;
+                    let max_bytes = self.options.motion_limit(buffer.data.len());
+                    let bytes_per_line = self.options.bytes_per_line;
 
                     buffer.map_selections(|region| {
                         vec![region.simple_move(Direction::Down, bytes_per_line, max_bytes, 1)]
                     });
 
                     self.scroll_down(stdout, 1)?;
-                    self.draw(stdout)?;
+                    self.last_draw_time = self.draw(stdout)?;
                     Ok(())
                 }
                 (KeyCode::Char('y'), KeyModifiers::CONTROL) => {
                     let buffer = self.buffers.current_mut();
-                    let max_bytes = buffer.data.len();
-                    let bytes_per_line = self.bytes_per_line;
+                    let max_bytes = self.options.motion_limit(buffer.data.len());
+                    let bytes_per_line = self.options.bytes_per_line;
 
                     buffer.map_selections(|region| {
                         vec![region.simple_move(Direction::Up, bytes_per_line, max_bytes, 1)]
                     });
 
                     self.scroll_up(stdout, 1)?;
-                    self.draw(stdout)?;
+                    self.last_draw_time = self.draw(stdout)?;
+                    Ok(())
+                }
+                (KeyCode::Char('t'), KeyModifiers::CONTROL) => {
+                    let text = self.visible_rows_text().into_bytes();
+                    self.buffers
+                        .current_mut()
+                        .set_register('"', vec![text], &self.options);
+                    self.info = Some("yanked the rows on screen as text".to_owned());
                     Ok(())
                 }
                 _ => Ok(()),
@@ -961,10 +1448,10 @@ impl HexView {
             return Ok(());
         }
 
-        self.start_offset += 0x10 * line_count;
+        self.start_offset += self.options.bytes_per_line * line_count;
 
         if line_count > (self.size.1 - 1) as usize {
-            self.draw(stdout)?;
+            self.last_draw_time = self.draw(stdout)?;
             Ok(())
         } else {
             queue!(
@@ -978,21 +1465,21 @@ impl HexView {
 
             let mut invalidated_rows: BTreeSet<u16> =
                 (self.size.1 - 1 - line_count as u16..=self.size.1 - 2).collect();
-            invalidated_rows.extend(0..BytePropertiesFormatter::height() as u16);
+            invalidated_rows.extend(0..BytePropertiesFormatter::height(&self.options) as u16);
             self.draw_rows(stdout, &invalidated_rows) // -1 is statusline
         }
     }
 
     fn scroll_up(&mut self, stdout: &mut impl Write, line_count: usize) -> Result<()> {
-        if self.start_offset < 0x10 * line_count {
+        if self.start_offset < self.options.bytes_per_line * line_count {
             // we already at the top the file
             return Ok(());
         }
 
-        self.start_offset -= 0x10 * line_count;
+        self.start_offset -= self.options.bytes_per_line * line_count;
 
         if line_count > (self.size.1 - 1) as usize {
-            self.draw(stdout)?;
+            self.last_draw_time = self.draw(stdout)?;
             Ok(())
         } else {
             queue!(
@@ -1003,11 +1490,35 @@ impl HexView {
             )?;
 
             let invalidated_rows: BTreeSet<u16> =
-                (0..(line_count + BytePropertiesFormatter::height()) as u16).collect();
+                (0..(line_count + BytePropertiesFormatter::height(&self.options)) as u16).collect();
             self.draw_rows(stdout, &invalidated_rows) // -1 is statusline
         }
     }
 
+    fn scrolloff_bytes(&self) -> usize {
+        // Never let the margins cover more than half the screen, or the view would jump around
+        let visible_rows = (self.size.1 as usize).saturating_sub(1);
+        cmp::min(self.options.scrolloff, visible_rows.saturating_sub(1) / 2)
+            * self.options.bytes_per_line
+    }
+
+    // The part of the visible bytes the main cursor may be in without scrolling
+    fn comfortable_bytes(&self) -> Range<usize> {
+        let visible_bytes = self.visible_bytes();
+        let margin = self.scrolloff_bytes();
+        let start = if visible_bytes.start == 0 {
+            0
+        } else {
+            visible_bytes.start + margin
+        };
+        let end = if visible_bytes.end > self.buffers.current().data.len() {
+            visible_bytes.end
+        } else {
+            visible_bytes.end - margin
+        };
+        start..end
+    }
+
     fn maybe_update_offset(&mut self, stdout: &mut impl Write) -> Result<()> {
         if self.buffers.current().data.is_empty() {
             self.start_offset = 0;
@@ -1015,38 +1526,64 @@ impl HexView {
         }
 
         let main_cursor_offset = self.buffers.current().selection.main_cursor_offset();
-        let visible_bytes = self.visible_bytes();
-        let delta = if main_cursor_offset < visible_bytes.start {
-            main_cursor_offset as isize - visible_bytes.start as isize
-        } else if main_cursor_offset >= visible_bytes.end {
-            main_cursor_offset as isize - (visible_bytes.end as isize - 1)
+        let comfortable_bytes = self.comfortable_bytes();
+        let delta = if main_cursor_offset < comfortable_bytes.start {
+            main_cursor_offset as isize - comfortable_bytes.start as isize
+        } else if main_cursor_offset >= comfortable_bytes.end {
+            main_cursor_offset as isize - (comfortable_bytes.end as isize - 1)
         } else {
             return Ok(());
         };
         if delta < 0 {
-            let line_delta =
-                (delta - self.bytes_per_line as isize + 1) / self.bytes_per_line as isize;
-            self.scroll_up(stdout, line_delta.abs() as usize)
+            let line_delta = (delta - self.options.bytes_per_line as isize + 1)
+                / self.options.bytes_per_line as isize;
+            let line_delta = cmp::min(
+                line_delta.unsigned_abs(),
+                self.start_offset / self.options.bytes_per_line,
+            );
+            self.scroll_up(stdout, line_delta)
         } else {
-            let line_delta =
-                (delta + self.bytes_per_line as isize - 1) / self.bytes_per_line as isize;
+            let line_delta = (delta + self.options.bytes_per_line as isize - 1)
+                / self.options.bytes_per_line as isize;
             self.scroll_down(stdout, line_delta as usize)
         }
     }
 
+    fn update_auto_bytes_per_line(&mut self) {
+        if self.options.bytes_per_line_auto {
+            self.options.bytes_per_line = fit_bytes_per_line(self.size.0 as usize);
+        }
+        // The offset may no longer be at the start of a row if bytes per line changed
+        self.start_offset -= self.start_offset % self.options.bytes_per_line;
+    }
+
+    // The pinned bytes may have been deleted
+    fn clear_stale_pin(&mut self) {
+        if matches!(self.options.inspector_pin, Some(pin) if pin >= self.buffers.current().data.len())
+        {
+            self.options.inspector_pin = None;
+        }
+    }
+
     fn maybe_update_offset_and_draw(&mut self, stdout: &mut impl Write) -> Result<()> {
+        self.update_auto_bytes_per_line();
+        self.clear_stale_pin();
         let main_cursor_offset = self.buffers.current().selection.main_cursor_offset();
-        let visible_bytes = self.visible_bytes();
-        if main_cursor_offset < visible_bytes.start {
-            self.start_offset = main_cursor_offset - main_cursor_offset % self.bytes_per_line;
-        } else if main_cursor_offset >= visible_bytes.end {
-            let bytes_per_screen = (self.size.1 as usize - 1) * self.bytes_per_line; // -1 for statusline
-            self.start_offset = (main_cursor_offset - main_cursor_offset % self.bytes_per_line
-                + self.bytes_per_line)
+        let comfortable_bytes = self.comfortable_bytes();
+        let margin = self.scrolloff_bytes();
+        let bytes_per_line = self.options.bytes_per_line;
+        let cursor_row_start = main_cursor_offset - main_cursor_offset % bytes_per_line;
+        if main_cursor_offset < comfortable_bytes.start {
+            self.start_offset = cursor_row_start.saturating_sub(margin);
+        } else if main_cursor_offset >= comfortable_bytes.end {
+            let bytes_per_screen = (self.size.1 as usize - 1) * bytes_per_line; // -1 for statusline
+            let data_len = self.buffers.current().data.len();
+            let last_row_end = data_len - data_len % bytes_per_line + bytes_per_line;
+            self.start_offset = cmp::min(cursor_row_start + bytes_per_line + margin, last_row_end)
                 .saturating_sub(bytes_per_screen);
         }
 
-        self.draw(stdout)?;
+        self.last_draw_time = self.draw(stdout)?;
         Ok(())
     }
 
@@ -1056,9 +1593,39 @@ impl HexView {
         dirty_bytes: DirtyBytes,
     ) -> Result<()> {
         match dirty_bytes {
+            // With no bytes left, the offset jumps back to the start without scrolling. A
+            // highlighted match can also begin on a row before the change.
+            DirtyBytes::ChangeInPlace(_)
+                if self.buffers.current().data.is_empty()
+                    || !self.options.highlights.is_empty() =>
+            {
+                self.maybe_update_offset_and_draw(stdout)
+            }
             DirtyBytes::ChangeInPlace(intervals) => {
+                self.clear_stale_pin();
                 self.maybe_update_offset(stdout)?;
 
+                // Rows past the end of the data are left over from before it got shorter
+                let bytes_per_line = self.options.bytes_per_line;
+                let drawn_end = self.visible_bytes().end.div_ceil(bytes_per_line) * bytes_per_line;
+                let screen_end = self.start_offset + (self.size.1 - 1) as usize * bytes_per_line;
+                let stale: Interval = (drawn_end..screen_end).into();
+                for interval in &intervals {
+                    let cleared = stale.intersect(*interval);
+                    if cleared.is_empty() {
+                        continue;
+                    }
+                    for offset in (cleared.start - cleared.start % bytes_per_line..cleared.end)
+                        .step_by(bytes_per_line)
+                    {
+                        queue!(
+                            stdout,
+                            cursor::MoveTo(0, self.offset_to_row(offset).unwrap()),
+                            terminal::Clear(terminal::ClearType::CurrentLine)
+                        )?;
+                    }
+                }
+
                 let visible: Interval = self.visible_bytes().into();
                 let mut invalidated_rows: BTreeSet<u16> = intervals
                     .into_iter()
@@ -1070,10 +1637,10 @@ impl HexView {
                             intersection.start..intersection.end
                         }
                     })
-                    .map(|byte| ((byte - self.start_offset) / self.bytes_per_line) as u16)
+                    .map(|byte| ((byte - self.start_offset) / self.options.bytes_per_line) as u16)
                     .collect();
 
-                invalidated_rows.extend(0..BytePropertiesFormatter::height() as u16);
+                invalidated_rows.extend(0..BytePropertiesFormatter::height(&self.options) as u16);
                 self.draw_rows(stdout, &invalidated_rows)
             }
             DirtyBytes::ChangeLength => self.maybe_update_offset_and_draw(stdout),
@@ -1100,35 +1667,204 @@ impl HexView {
                 self.info = Some(info);
                 Ok(())
             }
+            ModeTransition::ModeAndDirtyBytesAndInfo(mode, dirty_bytes, info) => {
+                self.mode = mode;
+                self.transition_dirty_bytes(stdout, dirty_bytes)?;
+                self.info = Some(info);
+                Ok(())
+            }
         }
     }
 
+    fn next_event_now(&mut self) -> Result<Option<Event>> {
+        if let Some(evt) = self
+            .lookahead_event
+            .take()
+            .or_else(cancel::take_pending_event)
+        {
+            return Ok(Some(evt));
+        }
+        if event::poll(time::Duration::ZERO)? {
+            return Ok(Some(event::read()?));
+        }
+        Ok(None)
+    }
+
+    // crossterm doesn't report bracketed pastes, so text typed into insert or text entry mode
+    // faster than we can read it is treated as a paste: it is inserted with a single delta and redraw
+    fn read_paste(&mut self, first: &Event) -> Result<Option<String>> {
+        let any_mode = self.mode.as_any();
+        // Only text entry mode types enter and tab as text; insert mode leaves them to be
+        // handled as keys
+        let pasted_char = if any_mode.is::<modes::text::TextEntry>() {
+            modes::text::typed_char
+        } else if any_mode.is::<modes::insert::Insert>() {
+            |evt: &Event| match modes::text::typed_char(evt) {
+                Some('\n' | '\t') => None,
+                ch => ch,
+            }
+        } else {
+            return Ok(None);
+        };
+        let mut text = match pasted_char(first) {
+            Some(ch) => ch.to_string(),
+            None => return Ok(None),
+        };
+        while let Some(evt) = self.next_event_now()? {
+            match pasted_char(&evt) {
+                Some(ch) => text.push(ch),
+                None => {
+                    self.lookahead_event = Some(evt);
+                    break;
+                }
+            }
+        }
+        Ok(if text.chars().count() > 1 {
+            Some(text)
+        } else {
+            None
+        })
+    }
+
     pub fn run_event_loop(mut self, stdout: &mut impl Write) -> Result<()> {
-        execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+        if self.alt_screen {
+            execute!(stdout, terminal::EnterAlternateScreen)?;
+        } else {
+            // Scroll whatever is on the screen into the scrollback, so that clearing the screen
+            // to draw doesn't lose it
+            queue!(stdout, cursor::MoveTo(0, self.size.1 - 1))?;
+            for _ in 0..self.size.1 {
+                queue!(stdout, style::Print("\n"))?;
+            }
+        }
+        execute!(stdout, cursor::Hide)?;
+        let result = self.event_loop(stdout);
 
+        // Restore the terminal even if the loop failed, so that the error can be read
+        if self.mouse_captured {
+            let _ = execute!(stdout, event::DisableMouseCapture);
+        }
+        let restored = if self.alt_screen {
+            execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)
+        } else {
+            // Put the shell prompt below the last frame
+            execute!(
+                stdout,
+                cursor::Show,
+                cursor::MoveTo(0, self.size.1 - 1),
+                style::Print("\r\n")
+            )
+        }
+        .and_then(|_| terminal::disable_raw_mode());
+        result.and(restored)
+    }
+
+    fn event_loop(&mut self, stdout: &mut impl Write) -> Result<()> {
         self.last_draw_time = self.draw(stdout)?;
         terminal::enable_raw_mode()?;
+        cancel::enable();
         stdout.flush()?;
 
         loop {
             if !self.mode.takes_input() {
                 break;
             }
-            let evt = event::read()?;
-            let transition = self
-                .mode
-                .transition(&evt, &mut self.buffers, self.bytes_per_line);
+            let evt = match self
+                .lookahead_event
+                .take()
+                .or_else(cancel::take_pending_event)
+            {
+                Some(evt) => evt,
+                None => match self.options.timeout.filter(|_| self.mode.is_prefix()) {
+                    Some(timeout) if !event::poll(timeout)? => {
+                        // The sequence was abandoned
+                        self.mode = Box::new(modes::normal::Normal::new());
+                        self.draw_statusline(stdout)?;
+                        stdout.flush()?;
+                        continue;
+                    }
+                    _ => event::read()?,
+                },
+            };
+            let pasted = self.read_paste(&evt)?;
+            let begin = time::Instant::now();
+            let transition = match &pasted {
+                Some(text) => self.mode.paste(text, &mut self.buffers, &mut self.options),
+                None => self
+                    .mode
+                    .transition(&evt, &mut self.buffers, &mut self.options),
+            };
+            self.last_operation_time = begin.elapsed();
             if let Some(transition) = transition {
                 self.transition(stdout, transition)?;
             } else {
                 self.handle_event_default(stdout, evt)?;
             }
 
+            self.update_mode_status();
+            self.update_mouse_capture(stdout)?;
+            self.draw_minimap(stdout)?;
             self.draw_statusline(stdout)?;
             stdout.flush()?;
         }
-        execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
-        terminal::disable_raw_mode()?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operations as ops;
+    use crate::selection::SelRegion;
+
+    // What the terminal would show, without the escape sequences
+    fn plain_text(out: &[u8]) -> String {
+        let out = String::from_utf8_lossy(out);
+        let mut text = String::new();
+        let mut chars = out.chars();
+        while let Some(ch) = chars.next() {
+            if ch == '\u{1b}' {
+                // Skips to the final letter of the sequence
+                chars.find(|ch| ch.is_ascii_alphabetic());
+            } else {
+                text.push(ch);
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn test_draw_at_fixed_size() {
+        let buffer = Buffer::from_data_and_path(b"hello world".to_vec(), None::<&str>);
+        let view = HexView::with_buffers_and_size(Buffers::with_buffer(buffer), (120, 10));
+        let mut out = vec![];
+        view.draw(&mut out).unwrap();
+        let text = plain_text(&out);
+        assert!(text.contains("68 65 6c 6c 6f 20 77 6f 72 6c 64"));
+        assert!(text.contains("hello world"));
+    }
+
+    #[test]
+    fn test_insert_redraws_rows_from_insertion() {
+        let buffer = Buffer::from_data_and_path(vec![0; 0x100], None::<&str>);
+        let mut view = HexView::with_buffers_and_size(Buffers::with_buffer(buffer), (120, 30));
+        view.draw(&mut vec![]).unwrap();
+
+        let buffer = view.buffers.current_mut();
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(0xf0, 0xf0)]);
+        let delta = ops::insert(&buffer.data, &buffer.selection, vec![1]);
+        let dirty = buffer.apply_delta(delta);
+        let mut out = vec![];
+        view.transition(&mut out, ModeTransition::DirtyBytes(dirty))
+            .unwrap();
+        let out = String::from_utf8_lossy(&out);
+        // Rows are 1-based in the escape sequences. The rows at the top are redrawn for the
+        // inspector.
+        let inspector_rows = BytePropertiesFormatter::height(&view.options);
+        assert!(!out.contains(&format!("\u{1b}[{};1H", inspector_rows + 1)));
+        assert!(out.contains("\u{1b}[16;1H"));
+        assert!(out.contains("\u{1b}[17;1H"));
+    }
+}
@@ -71,23 +71,52 @@ impl Action {
     }
 }
 
+// The delta that reverts `delta` when applied to `base_rope.apply_delta(delta)`
+pub fn invert_delta(delta: &RopeDelta, base_rope: &Rope) -> RopeDelta {
+    Action::from_delta(delta.clone()).invert(base_rope).delta
+}
+
 #[derive(Clone, Default)]
 pub struct History {
     partial: Option<(Action, Selection)>,
 
     undo: Vec<(Action, Selection)>,
     redo: Vec<(Action, Selection)>,
+    // The depth of the undo stack when the buffer was last saved, or None once that state can't
+    // be reached by undoing or redoing
+    saved: Option<usize>,
 }
 
 impl History {
     pub fn new() -> Self {
-        Default::default()
+        History {
+            saved: Some(0),
+            ..Default::default()
+        }
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.undo.len());
+    }
+
+    // Undos (if negative) or redos needed to get back to the saved state
+    pub fn steps_to_saved(&self) -> Option<isize> {
+        self.saved
+            .map(|saved| saved as isize - self.undo.len() as isize)
+    }
+
+    // A new step drops the redo stack, which may hold the saved state
+    fn drop_redo(&mut self) {
+        if self.saved.is_some_and(|saved| saved > self.undo.len()) {
+            self.saved = None;
+        }
+        self.redo = vec![];
     }
 
     pub fn perform_final(&mut self, current_rope: &Rope, delta: RopeDelta, selection: Selection) {
+        self.drop_redo();
         self.undo
             .push((Action::from_delta(delta).invert(current_rope), selection));
-        self.redo = vec![];
     }
 
     pub fn perform_partial(
@@ -110,10 +139,19 @@ impl History {
         self.partial = Some(replaced);
     }
 
+    // Whether edits are being gathered into one undo step, like the keys typed in insert mode
+    pub fn has_partial(&self) -> bool {
+        self.partial.is_some()
+    }
+
+    pub fn drop_partial(&mut self) {
+        self.partial = None;
+    }
+
     pub fn commit_partial(&mut self) {
         if let Some((partial, selection)) = self.partial.take() {
+            self.drop_redo();
             self.undo.push((partial, selection));
-            self.redo = vec![];
         }
     }
 
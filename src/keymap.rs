@@ -1,13 +1,13 @@
 use std::collections::HashMap;
 
-use crossterm::event::{Event, KeyEvent, KeyModifiers};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 
 #[derive(Debug, PartialEq, Clone)]
-pub struct KeyMap<T: Copy> {
+pub struct KeyMap<T: Clone> {
     pub maps: HashMap<KeyEvent, T>,
 }
 
-impl<T: Copy> KeyMap<T> {
+impl<T: Clone> KeyMap<T> {
     pub fn event_to_action(&self, evt: &Event) -> Option<T> {
         if let Event::Key(evt) = evt {
             self.maps
@@ -15,13 +15,66 @@ impl<T: Copy> KeyMap<T> {
                     modifiers: evt.modifiers & !KeyModifiers::SHIFT,
                     ..*evt
                 })
-                .copied()
+                .cloned()
         } else {
             None
         }
     }
 }
 
+// A key written like in the README: `x`, `<space>`, `<c-x>` or `<a-left>`. Shifted keys are
+// written as the character they produce, since the shift modifier is ignored when matching.
+pub fn parse_key(s: &str) -> Result<KeyEvent, String> {
+    let invalid = || format!("expected a key like x, <space> or <c-x>, got {}", s);
+    let mut chars = s.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return Ok(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+    }
+    let mut name = s
+        .strip_prefix('<')
+        .and_then(|s| s.strip_suffix('>'))
+        .ok_or_else(invalid)?;
+    let mut modifiers = KeyModifiers::NONE;
+    loop {
+        if let Some(rest) = name.strip_prefix("c-") {
+            modifiers |= KeyModifiers::CONTROL;
+            name = rest;
+        } else if let Some(rest) = name.strip_prefix("a-") {
+            modifiers |= KeyModifiers::ALT;
+            name = rest;
+        } else {
+            break;
+        }
+    }
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(ch), None) => KeyCode::Char(ch),
+        _ => match name {
+            "space" => KeyCode::Char(' '),
+            "lt" => KeyCode::Char('<'),
+            "gt" => KeyCode::Char('>'),
+            "enter" | "ret" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "esc" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "del" => KeyCode::Delete,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            _ => match name.strip_prefix('f').map(str::parse) {
+                Some(Ok(n @ 1..=12)) => KeyCode::F(n),
+                _ => return Err(invalid()),
+            },
+        },
+    };
+    Ok(KeyEvent::new(code, modifiers))
+}
+
 macro_rules! normalized_char {
     ($ch:expr) => {
         /*if $ch.is_ascii_uppercase() {
@@ -75,3 +128,34 @@ macro_rules! keys {
     	}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(
+            parse_key("X"),
+            Ok(KeyEvent::new(KeyCode::Char('X'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key("<c-a-x>"),
+            Ok(KeyEvent::new(
+                KeyCode::Char('x'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            ))
+        );
+        assert_eq!(
+            parse_key("<space>"),
+            Ok(KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key("<f5>"),
+            Ok(KeyEvent::new(KeyCode::F(5), KeyModifiers::NONE))
+        );
+        assert!(parse_key("xy").is_err());
+        assert!(parse_key("<c->").is_err());
+        assert!(parse_key("<f13>").is_err());
+    }
+}
@@ -2,6 +2,10 @@
 
 mod buffer;
 mod byte_rope;
+mod calc;
+pub mod cancel;
+pub mod config;
+pub mod encoding;
 pub mod hex_view;
 mod history;
 #[macro_use]
@@ -9,6 +13,9 @@ mod keymap;
 mod cmd_count;
 mod modes;
 mod operations;
+mod options;
+mod patch;
 mod selection;
 
 pub use buffer::{Buffer, Buffers};
+pub use options::ViewOptions;
@@ -0,0 +1,103 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use lazy_static::lazy_static;
+
+use crate::cancel::Cancelled;
+use crate::keymap::KeyMap;
+use crate::modes::{
+    mode::{Mode, ModeTransition},
+    normal::Normal,
+};
+use crate::{cmd_count, Buffers, ViewOptions};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Bracket {
+    pub forward: bool,
+    pub count_state: cmd_count::State,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Action {
+    Change,
+}
+
+fn default_maps() -> KeyMap<Action> {
+    KeyMap {
+        maps: keys!(
+            ('c' => Action::Change)
+        ),
+    }
+}
+
+lazy_static! {
+    static ref DEFAULT_MAPS: KeyMap<Action> = default_maps();
+}
+
+impl Mode for Bracket {
+    fn name(&self) -> Cow<'static, str> {
+        if self.forward {
+            format!("NEXT{}", self.count_state).into()
+        } else {
+            format!("PREV{}", self.count_state).into()
+        }
+    }
+
+    fn transition(
+        &self,
+        evt: &Event,
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+    ) -> Option<ModeTransition> {
+        let buffer = buffers.current_mut();
+        if let Some(action) = DEFAULT_MAPS.event_to_action(evt) {
+            Some(match action {
+                Action::Change => {
+                    let mut offset = None;
+                    let mut from = buffer.selection.main_cursor_offset();
+                    let (count, info) = self
+                        .count_state
+                        .to_count_capped(options.count_limit(buffer.data.len()));
+                    for _ in 0..count {
+                        match buffer.find_change(from, self.forward) {
+                            Ok(Some(next)) => from = next,
+                            Ok(None) => break,
+                            Err(_) => {
+                                return Some(ModeTransition::new_mode_and_info(
+                                    Normal::new(),
+                                    Cancelled::info(),
+                                ))
+                            }
+                        }
+                        offset = Some(from);
+                    }
+
+                    match offset {
+                        Some(offset) => ModeTransition::new_mode_and_dirty(
+                            Normal::new(),
+                            buffer.map_selections(|region| vec![region.jump_to(offset)]),
+                        ),
+                        None => ModeTransition::new_mode_and_info(
+                            Normal::new(),
+                            "no more changes".to_owned(),
+                        ),
+                    }
+                    .with_info(info)
+                }
+            })
+        } else if let Event::Key(_) = evt {
+            Some(ModeTransition::new_mode(Normal::new()))
+        } else {
+            None
+        }
+    }
+
+    fn is_prefix(&self) -> bool {
+        true
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -1,34 +1,52 @@
 use std::borrow::Cow;
 
-use crossterm::event::Event;
-
+use crate::cancel::Cancelled;
 use crate::modes::search::{Pattern, SearchAcceptor};
 use crate::modes::{
-    mode::{Mode, ModeTransition},
-    normal::Normal,
+    mode::ModeTransition,
+    normal::{Normal, EMPTY_BUFFER_INFO, NO_MATCHES_INFO},
 };
 use crate::selection::SelRegion;
-use crate::Buffers;
+use crate::{Buffers, ViewOptions};
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Collapse();
 
 impl SearchAcceptor for Collapse {
-    fn apply_search(&self, pattern: Pattern, buffers: &mut Buffers, _: usize) -> ModeTransition {
+    fn name(&self) -> Cow<'static, str> {
+        "COLLAPSE".into()
+    }
+
+    fn apply_search(
+        &self,
+        pattern: Pattern,
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+    ) -> ModeTransition {
         let buffer = buffers.current_mut();
+        if buffer.data.is_empty() {
+            return ModeTransition::new_mode_and_info(Normal::new(), EMPTY_BUFFER_INFO.to_owned());
+        }
         if pattern.pieces.is_empty() {
             return ModeTransition::new_mode(Normal::new());
         }
-        let matched_ranges = pattern.map_selections_to_matches(buffer);
+        let matched_ranges = match pattern.map_selections_to_matches(
+            buffer,
+            options.search_overlapping,
+            options.search_align,
+        ) {
+            Ok(matched_ranges) => matched_ranges,
+            Err(_) => return ModeTransition::new_mode_and_info(Normal::new(), Cancelled::info()),
+        };
         let matched_len: usize = matched_ranges
             .iter()
             .flatten()
             .map(|r| r.end - r.start)
             .sum();
         if matched_len == 0 {
-            // Nothing selected was matched: refuse to split because it would yield
+            // Nothing selected was matched: refuse to collapse because it would yield
             // an empty selection (invalid)
-            return ModeTransition::new_mode(Normal::new());
+            return ModeTransition::new_mode_and_info(Normal::new(), NO_MATCHES_INFO.to_owned());
         }
 
         let mut remaining_matched_ranges = &matched_ranges[..];
@@ -46,16 +64,42 @@ impl SearchAcceptor for Collapse {
     }
 }
 
-impl Mode for Collapse {
-    fn name(&self) -> Cow<'static, str> {
-        "COLLAPSE".into()
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
 
-    fn transition(&self, _: &Event, _: &mut Buffers, _: usize) -> Option<ModeTransition> {
-        None
-    }
+    #[test]
+    fn test_main_without_matches() {
+        // Nothing matches in the main selection, so the first match after it is main
+        let mut buffer = Buffer::from_data_and_path(b"xyzabc".to_vec(), None::<&str>);
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(0, 2), SelRegion::new(3, 5)]);
+        let mut buffers = Buffers::with_buffer(buffer);
+        Collapse().apply_search(
+            Pattern::from_bytes(b"ab"),
+            &mut buffers,
+            &mut ViewOptions::new(),
+        );
+        let main = buffers.current().selection.main();
+        assert_eq!((main.min(), main.max()), (3, 4));
 
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
+        // Nothing matches in the main selection or after it, so the last match is main
+        let mut buffer = Buffer::from_data_and_path(b"abcxyz".to_vec(), None::<&str>);
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(0, 2), SelRegion::new(3, 5)]);
+        buffer.selection.select_index(1);
+        let mut buffers = Buffers::with_buffer(buffer);
+        Collapse().apply_search(
+            Pattern::from_bytes(b"ab"),
+            &mut buffers,
+            &mut ViewOptions::new(),
+        );
+        let selection = &buffers.current().selection;
+        assert_eq!(selection.len(), 1);
+        assert_eq!((selection.main().min(), selection.main().max()), (0, 1));
+        assert!(selection.main().is_main());
     }
 }
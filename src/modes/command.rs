@@ -1,6 +1,8 @@
 use std::borrow::Cow;
+use std::cmp;
 use std::collections::HashMap;
 use std::fs;
+use std::sync::RwLock;
 
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use lazy_static::lazy_static;
@@ -9,9 +11,9 @@ use maplit::hashmap;
 use crate::keymap::KeyMap;
 use crate::modes::{
     mode::{Mode, ModeTransition},
-    normal::Normal,
+    normal::{Normal, EMPTY_BUFFER_INFO},
 };
-use crate::Buffers;
+use crate::{Buffer, Buffers, ViewOptions};
 
 pub struct Command {
     pub command: String,
@@ -43,11 +45,40 @@ fn default_maps() -> KeyMap<Action> {
 
 mod cmd {
     use super::*;
+    use crate::calc;
+    use crate::cancel::{CancelCheck, Cancelled};
+    use crate::modes;
+    use crate::modes::confirm;
     use crate::modes::mode::DirtyBytes;
     use crate::modes::quitting;
+    use crate::modes::search::Pattern;
+    use crate::operations as ops;
+    use crate::options::{parse_byte, parse_color, parse_hex_bytes, parse_usize, Highlight};
+    use crate::patch::Patch;
+    use crate::selection::{SelRegion, Selection};
+    // Parses an inclusive `<start> <end>` pair of offsets into a region clamped to the buffer.
+    // The boolean tells whether clamping was needed.
+    fn parse_region(buffers: &Buffers, args: &str) -> Result<(SelRegion, bool), String> {
+        let mut offsets = args.split_whitespace().map(parse_usize);
+        let (start, end) = match (offsets.next(), offsets.next(), offsets.next()) {
+            (Some(start), Some(end), None) => (start?, end?),
+            _ => return Err("expected <start> <end>".to_owned()),
+        };
+        let len = buffers.current().data.len();
+        if len == 0 {
+            return Err("buffer is empty".to_owned());
+        }
 
-    pub fn quit(buf: &mut Buffers, _: &str) -> ModeTransition {
-        if buf.iter().any(|x| x.dirty && x.path.is_some()) {
+        let (start, end) = (cmp::min(start, end), cmp::max(start, end));
+        let clamped = end >= len;
+        Ok((
+            SelRegion::new(cmp::min(end, len - 1), cmp::min(start, len - 1)),
+            clamped,
+        ))
+    }
+
+    pub fn quit(buf: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
+        if buf.iter().any(Buffer::has_unsaved_changes) {
             ModeTransition::new_mode_and_info(
                 Normal::new(),
                 "unsaved changes! Run :wq or :q! instead.".into(),
@@ -57,11 +88,20 @@ mod cmd {
         }
     }
 
-    pub fn force_quit(_: &mut Buffers, _: &str) -> ModeTransition {
+    pub fn force_quit(_: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
         ModeTransition::new_mode(quitting::Quitting {})
     }
 
-    pub fn write(buf: &mut Buffers, filename: &str) -> ModeTransition {
+    pub fn write(buf: &mut Buffers, _: &mut ViewOptions, filename: &str) -> ModeTransition {
+        write_current(buf, filename, false)
+    }
+
+    // Like write, but creates missing parent directories first
+    pub fn force_write(buf: &mut Buffers, _: &mut ViewOptions, filename: &str) -> ModeTransition {
+        write_current(buf, filename, true)
+    }
+
+    fn write_current(buf: &mut Buffers, filename: &str, create_dirs: bool) -> ModeTransition {
         let path = if filename.is_empty() {
             buf.current().path.as_deref()
         } else {
@@ -69,54 +109,162 @@ mod cmd {
         };
 
         if let Some(path) = path {
-            if let Err(e) = fs::write(&path, buf.current().data.slice_to_cow(..)) {
+            let parent = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+            if let Some(dir) = parent.filter(|dir| !dir.exists()) {
+                if !create_dirs {
+                    return ModeTransition::new_mode_and_info(
+                        Normal::new(),
+                        format!("{} does not exist; use :w! to create it", dir.display()),
+                    );
+                }
+                if let Err(e) = fs::create_dir_all(dir) {
+                    return ModeTransition::new_mode_and_info(
+                        Normal::new(),
+                        format!("creating {} failed: {}", dir.display(), e),
+                    );
+                }
+            }
+            if let Err(e) = fs::write(path, buf.current().data.slice_to_cow(..)) {
                 return ModeTransition::new_mode_and_info(
                     Normal::new(),
                     format!("write failed: {}", e),
                 );
             }
 
+            let info = format!(
+                "wrote 0x{:x} bytes to {}",
+                buf.current().data.len(),
+                path.display()
+            );
             let owned_path = path.to_owned();
             let buf_mut = buf.current_mut();
-            buf_mut.dirty = false;
+            buf_mut.mark_saved();
             buf_mut.update_path_if_missing(owned_path);
-            ModeTransition::new_mode(Normal::new())
+            // Redraw in case changes are being highlighted
+            ModeTransition::new_mode_dirty_and_info(Normal::new(), DirtyBytes::ChangeLength, info)
         } else {
             ModeTransition::new_mode_and_info(Normal::new(), "buffer has no path".into())
         }
     }
 
-    pub fn write_all(buffers: &mut Buffers, _: &str) -> ModeTransition {
+    pub fn write_all(buffers: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
+        let (mut written, mut written_bytes, mut skipped) = (0, 0, 0);
         for buf in buffers.iter_mut() {
             if let Some(path) = buf.path.as_ref() {
-                if let Err(e) = fs::write(&path, buf.data.slice_to_cow(..)) {
+                if let Err(e) = fs::write(path, buf.data.slice_to_cow(..)) {
                     return ModeTransition::new_mode_and_info(
                         Normal::new(),
                         format!("write failed: {}", e),
                     );
                 }
-                buf.dirty = false;
+                buf.mark_saved();
+                written += 1;
+                written_bytes += buf.data.len();
+            } else {
+                skipped += 1;
             }
         }
-        ModeTransition::new_mode(Normal::new())
+        let mut info = format!(
+            "wrote {} buffer{}, 0x{:x} bytes in total",
+            written,
+            if written == 1 { "" } else { "s" },
+            written_bytes
+        );
+        if skipped > 0 {
+            info.push_str(&format!("; skipped {} without a path", skipped));
+        }
+        ModeTransition::new_mode_dirty_and_info(Normal::new(), DirtyBytes::ChangeLength, info)
     }
 
-    pub fn write_quit(buffers: &mut Buffers, _: &str) -> ModeTransition {
+    pub fn write_quit(buffers: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
         for buf in buffers.iter_mut() {
             if let Some(path) = buf.path.as_ref() {
-                if let Err(e) = fs::write(&path, buf.data.slice_to_cow(..)) {
+                if let Err(e) = fs::write(path, buf.data.slice_to_cow(..)) {
                     return ModeTransition::new_mode_and_info(
                         Normal::new(),
                         format!("write failed: {}", e),
                     );
                 }
-                buf.dirty = false;
+                buf.mark_saved();
             }
         }
         ModeTransition::new_mode(quitting::Quitting {})
     }
 
-    pub fn edit(buffers: &mut Buffers, filename: &str) -> ModeTransition {
+    pub fn stats(buffers: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
+        let data = &buffers.current().data;
+        let stats = data.stats();
+        ModeTransition::new_mode_and_info(
+            Normal::new(),
+            format!(
+                "0x{:x} bytes in {} leaves ({} small), 0x{:x} bytes allocated",
+                data.len(),
+                stats.leaves,
+                stats.small_leaves,
+                stats.leaf_capacity
+            ),
+        )
+    }
+
+    pub fn revert(buffers: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
+        match buffers.current_mut().revert() {
+            Ok(dirty) => ModeTransition::new_mode_and_dirty(Normal::new(), dirty),
+            Err(e) => ModeTransition::new_mode_and_info(Normal::new(), e),
+        }
+    }
+
+    pub fn compact(buffers: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
+        let buffer = buffers.current_mut();
+        let before = buffer.data.stats().leaves;
+        buffer.compact();
+        ModeTransition::new_mode_and_info(
+            Normal::new(),
+            format!(
+                "compacted {} leaves into {}",
+                before,
+                buffer.data.stats().leaves
+            ),
+        )
+    }
+
+    pub fn diff_disk(buffers: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
+        let buffer = buffers.current();
+        let path = match &buffer.path {
+            Some(path) => path,
+            None => {
+                return ModeTransition::new_mode_and_info(
+                    Normal::new(),
+                    "buffer has no path".into(),
+                )
+            }
+        };
+        let on_disk = match fs::read(path) {
+            Ok(on_disk) => on_disk,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return ModeTransition::new_mode_and_info(
+                    Normal::new(),
+                    format!("{} does not exist on disk", path.display()),
+                )
+            }
+            Err(e) => {
+                return ModeTransition::new_mode_and_info(
+                    Normal::new(),
+                    format!("read failed: {}", e),
+                )
+            }
+        };
+        let info = match buffer.diff_against(&on_disk) {
+            Ok((None, _)) => "no differences from disk".to_owned(),
+            Ok((Some(first), count)) => format!(
+                "first difference from disk at 0x{:x}, 0x{:x} bytes differ",
+                first, count
+            ),
+            Err(_) => Cancelled::info(),
+        };
+        ModeTransition::new_mode_and_info(Normal::new(), info)
+    }
+
+    pub fn edit(buffers: &mut Buffers, _: &mut ViewOptions, filename: &str) -> ModeTransition {
         let result = buffers.switch_buffer(filename);
         if let Err(e) = result {
             return ModeTransition::new_mode_and_info(Normal::new(), format!("{}", e));
@@ -124,8 +272,12 @@ mod cmd {
         ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength)
     }
 
-    pub fn delete_buffer(buffers: &mut Buffers, _: &str) -> ModeTransition {
-        if buffers.current().dirty && buffers.current().path.is_some() {
+    pub fn alternate_buffer(buffers: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
+        modes::normal::switch_to_alternate_buffer(buffers)
+    }
+
+    pub fn delete_buffer(buffers: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
+        if buffers.current().has_unsaved_changes() {
             return ModeTransition::new_mode_and_info(
                 Normal::new(),
                 "buffer is dirty, use :db! if you're sure".to_string(),
@@ -135,13 +287,740 @@ mod cmd {
         ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength)
     }
 
-    pub fn force_delete_buffer(buffers: &mut Buffers, _: &str) -> ModeTransition {
+    pub fn force_delete_buffer(
+        buffers: &mut Buffers,
+        _: &mut ViewOptions,
+        _: &str,
+    ) -> ModeTransition {
         buffers.delete_current();
         ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength)
     }
+
+    pub fn carve(buffers: &mut Buffers, _: &mut ViewOptions, args: &str) -> ModeTransition {
+        let (region, clamped) = match parse_region(buffers, args) {
+            Ok(parsed) => parsed,
+            Err(e) => return ModeTransition::new_mode_and_info(Normal::new(), e),
+        };
+        buffers.carve(region.min()..region.max() + 1);
+        let transition =
+            ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength);
+        transition.with_info(clamped.then(|| "range clamped to the end of the buffer".to_owned()))
+    }
+
+    pub fn splice_back(buffers: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
+        match buffers.splice_back() {
+            Ok(()) => ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength),
+            Err(e) => ModeTransition::new_mode_and_info(Normal::new(), e),
+        }
+    }
+
+    pub fn patch_record(buffers: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
+        let buf = buffers.current_mut();
+        if buf.recording_patch {
+            buf.recording_patch = false;
+            return ModeTransition::new_mode_and_info(
+                Normal::new(),
+                format!("recorded {} changes", buf.patch.len()),
+            );
+        }
+        buf.patch = Patch::new();
+        buf.recording_patch = true;
+        ModeTransition::new_mode_and_info(Normal::new(), "recording patch".into())
+    }
+
+    pub fn patch_save(
+        buffers: &mut Buffers,
+        _: &mut ViewOptions,
+        filename: &str,
+    ) -> ModeTransition {
+        if filename.is_empty() {
+            return ModeTransition::new_mode_and_info(Normal::new(), "no filename given".into());
+        }
+        let buf = buffers.current_mut();
+        buf.recording_patch = false;
+        if let Err(e) = fs::write(filename, buf.patch.to_string()) {
+            return ModeTransition::new_mode_and_info(
+                Normal::new(),
+                format!("write failed: {}", e),
+            );
+        }
+        ModeTransition::new_mode(Normal::new())
+    }
+
+    fn read_patch(filename: &str) -> Result<Patch, String> {
+        fs::read_to_string(filename)
+            .map_err(|e| format!("{}", e))
+            .and_then(|contents| Patch::parse(&contents))
+    }
+
+    // Patches can rewrite any part of the buffer, so they are confirmed first
+    pub fn patch_apply(_: &mut Buffers, _: &mut ViewOptions, filename: &str) -> ModeTransition {
+        if let Err(e) = read_patch(filename) {
+            return ModeTransition::new_mode_and_info(Normal::new(), e);
+        }
+        ModeTransition::new_mode_and_info(
+            confirm::Confirm {
+                command: "patch-apply!".to_owned(),
+                args: filename.to_owned(),
+            },
+            format!("apply {} to the buffer? (y/N)", filename),
+        )
+    }
+
+    pub fn force_patch_apply(
+        buffers: &mut Buffers,
+        _: &mut ViewOptions,
+        filename: &str,
+    ) -> ModeTransition {
+        let patch = match read_patch(filename) {
+            Ok(patch) => patch,
+            Err(e) => return ModeTransition::new_mode_and_info(Normal::new(), e),
+        };
+        match buffers.current_mut().apply_patch(&patch) {
+            Ok(dirty) => ModeTransition::new_mode_and_dirty(Normal::new(), dirty),
+            Err(e) => ModeTransition::new_mode_and_info(Normal::new(), e),
+        }
+    }
+
+    pub fn export_sel(
+        buffers: &mut Buffers,
+        _: &mut ViewOptions,
+        filename: &str,
+    ) -> ModeTransition {
+        if filename.is_empty() {
+            return ModeTransition::new_mode_and_info(Normal::new(), "no filename given".into());
+        }
+        match fs::write(filename, buffers.current().selection.to_ranges_text()) {
+            Ok(()) => ModeTransition::new_mode(Normal::new()),
+            Err(e) => {
+                ModeTransition::new_mode_and_info(Normal::new(), format!("write failed: {}", e))
+            }
+        }
+    }
+
+    pub fn import_sel(
+        buffers: &mut Buffers,
+        _: &mut ViewOptions,
+        filename: &str,
+    ) -> ModeTransition {
+        let text = match fs::read_to_string(filename) {
+            Ok(text) => text,
+            Err(e) => return ModeTransition::new_mode_and_info(Normal::new(), format!("{}", e)),
+        };
+        let buffer = buffers.current_mut();
+        let (regions, dropped) = Selection::parse_ranges_text(&text, buffer.data.len());
+        if regions.is_empty() {
+            return ModeTransition::new_mode_and_info(
+                Normal::new(),
+                "no ranges in the buffer to select".to_owned(),
+            );
+        }
+        let dirty = buffer.replace_selections(regions);
+        let info =
+            (dropped > 0).then(|| format!("dropped {} invalid or out of range lines", dropped));
+        ModeTransition::new_mode_and_dirty(Normal::new(), dirty).with_info(info)
+    }
+
+    pub fn select(buffers: &mut Buffers, _: &mut ViewOptions, args: &str) -> ModeTransition {
+        let (region, clamped) = match parse_region(buffers, args) {
+            Ok(parsed) => parsed,
+            Err(e) => return ModeTransition::new_mode_and_info(Normal::new(), e),
+        };
+        let dirty = buffers.current_mut().replace_selection(region);
+        if clamped {
+            ModeTransition::new_mode_dirty_and_info(
+                Normal::new(),
+                dirty,
+                "selection clamped to the end of the buffer".to_owned(),
+            )
+        } else {
+            ModeTransition::new_mode_and_dirty(Normal::new(), dirty)
+        }
+    }
+
+    // Makes the nth selection (counting from 1, like the statusline) the main one
+    pub fn sel(buffers: &mut Buffers, _: &mut ViewOptions, args: &str) -> ModeTransition {
+        let buffer = buffers.current_mut();
+        let len = buffer.selection.len();
+        match parse_usize(args.trim()) {
+            Ok(n @ 1..) if n <= len => {
+                ModeTransition::new_mode_and_dirty(Normal::new(), buffer.select_index(n - 1))
+            }
+            Ok(n) => ModeTransition::new_mode_and_info(
+                Normal::new(),
+                format!("no selection {}, there are {}", n, len),
+            ),
+            Err(e) => ModeTransition::new_mode_and_info(Normal::new(), e),
+        }
+    }
+
+    pub fn add_sel(buffers: &mut Buffers, _: &mut ViewOptions, args: &str) -> ModeTransition {
+        let (region, clamped) = match parse_region(buffers, args) {
+            Ok(parsed) => parsed,
+            Err(e) => return ModeTransition::new_mode_and_info(Normal::new(), e),
+        };
+        let dirty = buffers.current_mut().add_selection(region);
+        if clamped {
+            ModeTransition::new_mode_dirty_and_info(
+                Normal::new(),
+                dirty,
+                "selection clamped to the end of the buffer".to_owned(),
+            )
+        } else {
+            ModeTransition::new_mode_and_dirty(Normal::new(), dirty)
+        }
+    }
+
+    pub fn range(buffers: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
+        let buf = buffers.current();
+        let len = buf.data.len();
+        let (mut min, mut max) = (None, None);
+        let mut cancel = CancelCheck::new();
+        for region in buf.selection.iter() {
+            // The overflow cursor has no data under it
+            let range = cmp::min(region.min(), len)..cmp::min(region.max() + 1, len);
+            for chunk in buf.data.slice_to_cow(range).chunks(0x100000) {
+                if cancel.check().is_err() {
+                    return ModeTransition::new_mode_and_info(Normal::new(), Cancelled::info());
+                }
+                for &byte in chunk {
+                    min = Some(min.map_or(byte, |min| cmp::min(min, byte)));
+                    max = Some(max.map_or(byte, |max| cmp::max(max, byte)));
+                }
+            }
+        }
+
+        let info = match (min, max) {
+            (Some(min), Some(max)) => {
+                format!("min 0x{:02x} ({}), max 0x{:02x} ({})", min, min, max, max)
+            }
+            _ => "nothing selected".to_owned(),
+        };
+        ModeTransition::new_mode_and_info(Normal::new(), info)
+    }
+
+    pub fn calc(buffers: &mut Buffers, _: &mut ViewOptions, expr: &str) -> ModeTransition {
+        let offset = buffers.current().selection.main_cursor_offset();
+        let info = match calc::evaluate(expr, offset) {
+            Ok(value) if value < 0 => format!("-0x{:x} = {}", value.unsigned_abs(), value),
+            Ok(value) => format!("0x{:x} = {}", value, value),
+            Err(e) => e,
+        };
+        ModeTransition::new_mode_and_info(Normal::new(), info)
+    }
+
+    pub fn follow(buffers: &mut Buffers, _: &mut ViewOptions, args: &str) -> ModeTransition {
+        follow_pointer(buffers, args, None)
+    }
+
+    pub fn follow_le(buffers: &mut Buffers, _: &mut ViewOptions, args: &str) -> ModeTransition {
+        follow_pointer(buffers, args, Some(true))
+    }
+
+    pub fn follow_be(buffers: &mut Buffers, _: &mut ViewOptions, args: &str) -> ModeTransition {
+        follow_pointer(buffers, args, Some(false))
+    }
+
+    // Jumps to the offset stored at the cursor. Unless the command fixes the endianness, it
+    // can be given in the arguments.
+    fn follow_pointer(
+        buffers: &mut Buffers,
+        args: &str,
+        fixed_endianness: Option<bool>,
+    ) -> ModeTransition {
+        let mut little_endian = fixed_endianness.unwrap_or(true);
+        let mut width = 4;
+        for arg in args.split_whitespace() {
+            match arg {
+                "le" if fixed_endianness.is_none() => little_endian = true,
+                "be" if fixed_endianness.is_none() => little_endian = false,
+                _ => match parse_usize(arg) {
+                    Ok(w @ 1..=8) => width = w,
+                    Ok(_) => {
+                        return ModeTransition::new_mode_and_info(
+                            Normal::new(),
+                            "pointer width must be between 1 and 8".to_owned(),
+                        )
+                    }
+                    Err(e) => return ModeTransition::new_mode_and_info(Normal::new(), e),
+                },
+            }
+        }
+
+        let buffer = buffers.current_mut();
+        let start = buffer.selection.main_cursor_offset();
+        if start + width > buffer.data.len() {
+            return ModeTransition::new_mode_and_info(
+                Normal::new(),
+                "not enough bytes for a pointer".to_owned(),
+            );
+        }
+        let mut bytes = buffer.data.slice_to_cow(start..start + width).to_vec();
+        if little_endian {
+            bytes.reverse();
+        }
+        let target = bytes
+            .iter()
+            .fold(0u64, |value, &byte| value << 8 | u64::from(byte));
+        match usize::try_from(target) {
+            Ok(target) if target < buffer.data.len() => {
+                ModeTransition::new_mode_and_dirty(Normal::new(), buffer.jump_and_remember(target))
+            }
+            _ => ModeTransition::new_mode_and_info(
+                Normal::new(),
+                format!("pointer out of range: {:x}", target),
+            ),
+        }
+    }
+
+    // Jumps to the offset written as ascii decimal digits starting at the cursor
+    pub fn follow_dec(buffers: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
+        let buffer = buffers.current_mut();
+        let start = buffer.selection.main_cursor_offset();
+        let digits = match buffer.find_run(start, |byte| byte.is_ascii_digit()) {
+            Ok(Some(run)) if run.contains(&start) => start..run.end,
+            Ok(_) => {
+                return ModeTransition::new_mode_and_info(
+                    Normal::new(),
+                    "no number under cursor".to_owned(),
+                )
+            }
+            Err(_) => return ModeTransition::new_mode_and_info(Normal::new(), Cancelled::info()),
+        };
+        let text = buffer.data.slice_to_cow(digits);
+        // Only digits were read, so the parse can only fail by overflowing
+        let target = std::str::from_utf8(&text)
+            .ok()
+            .and_then(|text| text.parse::<usize>().ok())
+            .unwrap_or(usize::MAX);
+        let last = buffer.data.len() - 1;
+        let dirty = buffer.jump_and_remember(cmp::min(target, last));
+        if target > last {
+            ModeTransition::new_mode_dirty_and_info(
+                Normal::new(),
+                dirty,
+                format!("offset out of range, clamped to {:x}", last),
+            )
+        } else {
+            ModeTransition::new_mode_and_dirty(Normal::new(), dirty)
+        }
+    }
+
+    // Jumps to `distance` bytes before the end of the buffer
+    pub fn goto_end(buffers: &mut Buffers, _: &mut ViewOptions, args: &str) -> ModeTransition {
+        let distance = match parse_usize(args.trim()) {
+            Ok(distance) => distance,
+            Err(e) => return ModeTransition::new_mode_and_info(Normal::new(), e),
+        };
+        let buffer = buffers.current_mut();
+        let len = buffer.data.len();
+        let dirty = buffer.jump_and_remember(len.saturating_sub(distance));
+        if distance > len {
+            ModeTransition::new_mode_dirty_and_info(
+                Normal::new(),
+                dirty,
+                format!("buffer is only 0x{:x} bytes long", len),
+            )
+        } else {
+            ModeTransition::new_mode_and_dirty(Normal::new(), dirty)
+        }
+    }
+
+    // Rows count from 0, like offsets
+    pub fn line(buffers: &mut Buffers, options: &mut ViewOptions, args: &str) -> ModeTransition {
+        let row = match parse_usize(args.trim()) {
+            Ok(row) => row,
+            Err(e) => return ModeTransition::new_mode_and_info(Normal::new(), e),
+        };
+        let bytes_per_line = options.bytes_per_line;
+        let buffer = buffers.current_mut();
+        let len = buffer.data.len();
+        let last_row = len.saturating_sub(1) / bytes_per_line;
+        let dirty = buffer.jump_and_remember(cmp::min(row, last_row) * bytes_per_line);
+        if row > last_row {
+            ModeTransition::new_mode_dirty_and_info(
+                Normal::new(),
+                dirty,
+                format!("buffer only has {} rows", last_row + 1),
+            )
+        } else {
+            ModeTransition::new_mode_and_dirty(Normal::new(), dirty)
+        }
+    }
+
+    pub fn inspect(buffers: &mut Buffers, options: &mut ViewOptions, args: &str) -> ModeTransition {
+        let len = buffers.current().data.len();
+        match parse_usize(args.trim()) {
+            Ok(offset) if offset < len => {
+                options.inspector_pin = Some(offset);
+                ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength)
+            }
+            Ok(_) => ModeTransition::new_mode_and_info(
+                Normal::new(),
+                format!("buffer is only 0x{:x} bytes long", len),
+            ),
+            Err(e) => ModeTransition::new_mode_and_info(Normal::new(), e),
+        }
+    }
+
+    pub fn inspect_pin(
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+        _: &str,
+    ) -> ModeTransition {
+        let buffer = buffers.current();
+        if buffer.data.is_empty() {
+            return ModeTransition::new_mode_and_info(Normal::new(), EMPTY_BUFFER_INFO.to_owned());
+        }
+        // The overflow cursor pins the last byte
+        let offset = cmp::min(buffer.selection.main_cursor_offset(), buffer.data.len() - 1);
+        options.inspector_pin = Some(offset);
+        ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength)
+    }
+
+    pub fn inspect_unpin(_: &mut Buffers, options: &mut ViewOptions, _: &str) -> ModeTransition {
+        options.inspector_pin = None;
+        ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength)
+    }
+
+    pub fn layer(buffers: &mut Buffers, _: &mut ViewOptions, name: &str) -> ModeTransition {
+        if name.is_empty() {
+            return ModeTransition::new_mode_and_info(
+                Normal::new(),
+                "expected a layer name".to_owned(),
+            );
+        }
+        buffers.current_mut().save_layer(name);
+        ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength)
+    }
+
+    pub fn layer_toggle(buffers: &mut Buffers, _: &mut ViewOptions, name: &str) -> ModeTransition {
+        match buffers.current_mut().toggle_layer(name) {
+            Ok(_) => ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength),
+            Err(e) => ModeTransition::new_mode_and_info(Normal::new(), e),
+        }
+    }
+
+    pub fn layer_delete(buffers: &mut Buffers, _: &mut ViewOptions, name: &str) -> ModeTransition {
+        match buffers.current_mut().delete_layer(name) {
+            Ok(()) => ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength),
+            Err(e) => ModeTransition::new_mode_and_info(Normal::new(), e),
+        }
+    }
+
+    // `:hl add /<pattern>/ <color>` or `:hl clear`
+    pub fn hl(_: &mut Buffers, options: &mut ViewOptions, args: &str) -> ModeTransition {
+        let (subcommand, rest) = args.split_once(' ').unwrap_or((args, ""));
+        let highlight = match subcommand {
+            "add" => rest
+                .trim()
+                .strip_prefix('/')
+                .and_then(|rest| rest.rsplit_once('/'))
+                .ok_or_else(|| "expected hl add /<pattern>/ <color>".to_owned())
+                .and_then(|(pattern, color)| {
+                    let pattern = Pattern::from_escaped(pattern);
+                    if pattern.pieces.is_empty() {
+                        return Err("empty pattern".to_owned());
+                    }
+                    let color = parse_color(color.trim())?;
+                    Ok(Highlight { pattern, color })
+                }),
+            "clear" => {
+                options.highlights.clear();
+                return ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength);
+            }
+            _ => Err("expected hl add or hl clear".to_owned()),
+        };
+        match highlight {
+            Ok(highlight) => {
+                options.highlights.push(highlight);
+                ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength)
+            }
+            Err(e) => ModeTransition::new_mode_and_info(Normal::new(), e),
+        }
+    }
+
+    pub fn layers(buffers: &mut Buffers, _: &mut ViewOptions, _: &str) -> ModeTransition {
+        let layers = &buffers.current().layers;
+        let info = if layers.is_empty() {
+            "no layers".to_owned()
+        } else {
+            layers
+                .iter()
+                .map(|layer| {
+                    if layer.visible {
+                        layer.name.clone()
+                    } else {
+                        format!("{} (hidden)", layer.name)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        ModeTransition::new_mode_and_info(Normal::new(), info)
+    }
+
+    // Yanks the offset of each selection's caret as text
+    pub fn yank_offset(
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+        args: &str,
+    ) -> ModeTransition {
+        let mut hex = true;
+        let mut register = '"';
+        for arg in args.split_whitespace() {
+            let mut chars = arg.chars();
+            match (arg, chars.next(), chars.next()) {
+                ("hex", ..) => hex = true,
+                ("dec", ..) => hex = false,
+                (_, Some(name), None) => register = name,
+                _ => {
+                    return ModeTransition::new_mode_and_info(
+                        Normal::new(),
+                        format!("expected hex, dec or a register name, got {}", arg),
+                    )
+                }
+            }
+        }
+        let buffer = buffers.current_mut();
+        let offsets: Vec<String> = buffer
+            .selection
+            .iter()
+            .map(|region| {
+                if hex {
+                    format!("0x{:x}", region.caret)
+                } else {
+                    region.caret.to_string()
+                }
+            })
+            .collect();
+        let info = format!("yanked {}", offsets.join(", "));
+        buffer.set_register(
+            register,
+            offsets.into_iter().map(String::into_bytes).collect(),
+            options,
+        );
+        ModeTransition::new_mode_and_info(Normal::new(), info)
+    }
+
+    // Inserts the given hex bytes before each cursor as one edit
+    pub fn insert(buffers: &mut Buffers, _: &mut ViewOptions, args: &str) -> ModeTransition {
+        let bytes = match parse_hex_bytes(args) {
+            Ok(bytes) => bytes,
+            Err(e) => return ModeTransition::new_mode_and_info(Normal::new(), e),
+        };
+        let buffer = buffers.current_mut();
+        let delta = ops::insert(&buffer.data, &buffer.selection, bytes);
+        ModeTransition::new_mode_and_dirty(Normal::new(), buffer.apply_delta(delta))
+    }
+
+    // Inserts `n` copies of a byte before each cursor as one edit
+    pub fn insert_rep(
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+        args: &str,
+    ) -> ModeTransition {
+        let mut parts = args.split_whitespace();
+        let (byte, count) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(byte), Some(count), None) => match (parse_byte(byte), parse_usize(count)) {
+                (Ok(byte), Ok(count)) => (byte, count),
+                (Err(e), _) | (_, Err(e)) => {
+                    return ModeTransition::new_mode_and_info(Normal::new(), e)
+                }
+            },
+            _ => {
+                return ModeTransition::new_mode_and_info(
+                    Normal::new(),
+                    "expected <byte> <count>".to_owned(),
+                )
+            }
+        };
+        let buffer = buffers.current_mut();
+        let limit = options.count_limit(buffer.data.len());
+        let info = (count > limit).then(|| format!("count clamped to 0x{:x}", limit));
+        let delta = ops::insert(
+            &buffer.data,
+            &buffer.selection,
+            vec![byte; cmp::min(count, limit)],
+        );
+        ModeTransition::new_mode_and_dirty(Normal::new(), buffer.apply_delta(delta)).with_info(info)
+    }
+
+    // A register name, or `"` if none is given
+    fn parse_register(args: &str) -> Result<char, String> {
+        let mut chars = args.chars();
+        match (chars.next(), chars.next()) {
+            (None, _) => Ok('"'),
+            (Some(register), None) => Ok(register),
+            _ => Err(format!("expected a register name, got {}", args)),
+        }
+    }
+
+    pub fn yank_join(
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+        args: &str,
+    ) -> ModeTransition {
+        let register = match parse_register(args) {
+            Ok(register) => register,
+            Err(e) => return ModeTransition::new_mode_and_info(Normal::new(), e),
+        };
+        let buffer = buffers.current_mut();
+        buffer.yank_selections_joined(register, options);
+        ModeTransition::new_mode_and_info(
+            Normal::new(),
+            format!(
+                "yanked {} bytes from {} selections",
+                buffer.registers[&register][0].len(),
+                buffer.selection.len()
+            ),
+        )
+    }
+
+    pub fn yank_origin(
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+        args: &str,
+    ) -> ModeTransition {
+        let register = match parse_register(args) {
+            Ok(register) => register,
+            Err(e) => return ModeTransition::new_mode_and_info(Normal::new(), e),
+        };
+        let buffer = buffers.current_mut();
+        buffer.yank_selections_with_origins(register, options);
+        ModeTransition::new_mode_and_info(
+            Normal::new(),
+            format!(
+                "yanked {} selections with their offsets",
+                buffer.selection.len()
+            ),
+        )
+    }
+
+    // Writes the entries of a register yanked with `:yank-origin` back where they came from
+    pub fn paste_at_origin(
+        buffers: &mut Buffers,
+        _: &mut ViewOptions,
+        args: &str,
+    ) -> ModeTransition {
+        let register = match parse_register(args) {
+            Ok(register) => register,
+            Err(e) => return ModeTransition::new_mode_and_info(Normal::new(), e),
+        };
+        let buffer = buffers.current_mut();
+        let (origins, contents) = match (
+            buffer.register_origins.get(&register),
+            buffer.registers.get(&register),
+        ) {
+            (Some(origins), Some(contents)) => (origins, contents),
+            _ => {
+                return ModeTransition::new_mode_and_info(
+                    Normal::new(),
+                    format!("register {} has no recorded offsets", register),
+                )
+            }
+        };
+        let (delta, truncated) = ops::paste_at_origin(&buffer.data, origins, contents);
+        if buffer.data.is_unchanged_by(&delta) {
+            return ModeTransition::new_mode_and_info(
+                Normal::new(),
+                modes::normal::NO_CHANGE_INFO.to_owned(),
+            );
+        }
+        ModeTransition::new_mode_and_dirty(Normal::new(), buffer.apply_delta(delta))
+            .with_info(truncated.then(|| "register cut short at the end of the buffer".to_owned()))
+    }
+
+    // Overwrites the bytes from each cursor with the register, keeping the length of the buffer
+    pub fn stamp(buffers: &mut Buffers, _: &mut ViewOptions, args: &str) -> ModeTransition {
+        let register = match parse_register(args) {
+            Ok(register) => register,
+            Err(e) => return ModeTransition::new_mode_and_info(Normal::new(), e),
+        };
+        let buffer = buffers.current_mut();
+        let contents = match buffer.registers.get(&register) {
+            Some(contents) if !contents.is_empty() => contents,
+            _ => {
+                return ModeTransition::new_mode_and_info(
+                    Normal::new(),
+                    format!("register {} is empty", register),
+                )
+            }
+        };
+        let (delta, truncated) = ops::stamp(&buffer.data, &buffer.selection, contents);
+        if buffer.data.is_unchanged_by(&delta) {
+            return ModeTransition::new_mode_and_info(
+                Normal::new(),
+                modes::normal::NO_CHANGE_INFO.to_owned(),
+            );
+        }
+        // The carets move over the stamped bytes, leaving the tails behind
+        let collapsed = buffer.map_selections(|region| vec![region.collapse()]);
+        let stamped = buffer.apply_delta(delta);
+        ModeTransition::new_mode_and_dirty(Normal::new(), collapsed.merge(stamped)).with_info(
+            truncated.then(|| {
+                "register cut short at the end of the buffer or the next cursor".to_owned()
+            }),
+        )
+    }
+
+    // Commands that open, close or switch buffers, or that leave normal mode
+    const BUFDO_REFUSED: [&str; 16] = [
+        "q",
+        "quit",
+        "q!",
+        "quit!",
+        "wq",
+        "e",
+        "edit",
+        "b#",
+        "db",
+        "delete-buffer",
+        "db!",
+        "delete-buffer!",
+        "patch-apply",
+        "bufdo",
+        "carve",
+        "splice-back",
+    ];
+
+    // Runs a command in every buffer. A failing buffer doesn't stop the others; the
+    // messages of all buffers are reported together.
+    pub fn bufdo(buffers: &mut Buffers, options: &mut ViewOptions, args: &str) -> ModeTransition {
+        let (name, args) = parse_command_line(args.trim());
+        if name.is_empty() {
+            return ModeTransition::new_mode_and_info(
+                Normal::new(),
+                "expected a command".to_owned(),
+            );
+        }
+        if BUFDO_REFUSED.contains(&name.as_str()) {
+            return ModeTransition::new_mode_and_info(
+                Normal::new(),
+                format!(":{} can't be run with :bufdo", name),
+            );
+        }
+        let infos = buffers.for_each_as_current(|buffers| {
+            let transition = run_command(buffers, options, &name, &args);
+            transition
+                .info()
+                .map(|info| format!("{}: {}", buffers.current().name(), info))
+        });
+        let infos = infos.into_iter().flatten().collect::<Vec<_>>();
+        ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength)
+            .with_info((!infos.is_empty()).then(|| infos.join("; ")))
+    }
+
+    pub fn set(_: &mut Buffers, options: &mut ViewOptions, args: &str) -> ModeTransition {
+        let (name, value) = args.split_once(' ').unwrap_or((args, ""));
+        if let Err(e) = options.set(name, value.trim()) {
+            return ModeTransition::new_mode_and_info(Normal::new(), e);
+        }
+        ModeTransition::new_mode_and_dirty(Normal::new(), DirtyBytes::ChangeLength)
+    }
 }
 
-type CommandHandler = fn(&mut Buffers, &str) -> ModeTransition;
+type CommandHandler = fn(&mut Buffers, &mut ViewOptions, &str) -> ModeTransition;
 
 macro_rules! make_commands {
     ($($string:tt => $cmd:ident,)*) => {
@@ -159,21 +1038,122 @@ fn default_commands() -> HashMap<String, CommandHandler> {
         "quit!" => force_quit,
         "w" => write,
         "write" => write,
+        "w!" => force_write,
+        "write!" => force_write,
         "wq" => write_quit,
         "wa" => write_all,
         "write-all" => write_all,
         "e" => edit,
         "edit" => edit,
+        "b#" => alternate_buffer,
         "db" => delete_buffer,
         "delete-buffer" => delete_buffer,
         "db!" => force_delete_buffer,
         "delete-buffer!" => force_delete_buffer,
+        "set" => set,
+        "bufdo" => bufdo,
+        "range" => range,
+        "calc" => calc,
+        "follow" => follow,
+        "follow-le" => follow_le,
+        "follow-be" => follow_be,
+        "follow-dec" => follow_dec,
+        "goto-end" => goto_end,
+        "line" => line,
+        "inspect" => inspect,
+        "inspect-pin" => inspect_pin,
+        "inspect-unpin" => inspect_unpin,
+        "layer" => layer,
+        "layer-toggle" => layer_toggle,
+        "layer-delete" => layer_delete,
+        "layers" => layers,
+        "hl" => hl,
+        "stats" => stats,
+        "compact" => compact,
+        "revert" => revert,
+        "diff-disk" => diff_disk,
+        "yank-offset" => yank_offset,
+        "yank-join" => yank_join,
+        "stamp" => stamp,
+        "yank-origin" => yank_origin,
+        "paste-at-origin" => paste_at_origin,
+        "insert" => insert,
+        "insert-rep" => insert_rep,
+        "export-sel" => export_sel,
+        "import-sel" => import_sel,
+        "select" => select,
+        "sel" => sel,
+        "add-sel" => add_sel,
+        "carve" => carve,
+        "splice-back" => splice_back,
+        "patch-record" => patch_record,
+        "patch-save" => patch_save,
+        "patch-apply" => patch_apply,
+        "patch-apply!" => force_patch_apply,
     ]
 }
 
 lazy_static! {
     static ref DEFAULT_MAPS: KeyMap<Action> = default_maps();
     static ref DEFAULT_COMMANDS: HashMap<String, CommandHandler> = default_commands();
+    // Aliases from the user's config, consulted before the built-in commands
+    static ref USER_ALIASES: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
+}
+
+// Makes `:name args` run `:expansion args`. Shadowing a built-in command works, but returns a
+// warning to show to the user.
+pub fn register_alias(name: &str, expansion: &str) -> Option<String> {
+    USER_ALIASES
+        .write()
+        .unwrap()
+        .insert(name.to_owned(), expansion.to_owned());
+    DEFAULT_COMMANDS
+        .contains_key(name)
+        .then(|| format!("alias {} shadows a built-in command", name))
+}
+
+// Splits a command line into the command's name and arguments, expanding aliases
+fn parse_command_line(line: &str) -> (String, String) {
+    let (name, rest) = line.split_at(line.find(' ').unwrap_or(line.len()));
+    let args = if rest.is_empty() { rest } else { &rest[1..] };
+    let expansion = USER_ALIASES.read().unwrap().get(name).cloned();
+    match expansion {
+        Some(expansion) => {
+            // Aliases expand to built-in commands only, so they can't loop
+            let (name, fixed_args) = expansion.split_once(' ').unwrap_or((&expansion, ""));
+            let args = [fixed_args, args]
+                .iter()
+                .filter(|args| !args.is_empty())
+                .copied()
+                .collect::<Vec<_>>()
+                .join(" ");
+            (name.to_owned(), args)
+        }
+        None => (name.to_owned(), args.to_owned()),
+    }
+}
+
+pub fn run_command(
+    buffers: &mut Buffers,
+    options: &mut ViewOptions,
+    name: &str,
+    args: &str,
+) -> ModeTransition {
+    if let Some(handler) = DEFAULT_COMMANDS.get(name) {
+        handler(buffers, options, args)
+    } else {
+        ModeTransition::new_mode_and_info(Normal::new(), format!("Unknown command {}", name))
+    }
+}
+
+// Runs a line as if it was typed after `:`
+pub fn run_command_line(
+    buffers: &mut Buffers,
+    options: &mut ViewOptions,
+    line: &str,
+) -> ModeTransition {
+    let (name, args) = parse_command_line(line);
+    run_command(buffers, options, &name, &args)
 }
 
 impl Command {
@@ -184,15 +1164,11 @@ impl Command {
         }
     }
 
-    fn finish(&self, buffers: &mut Buffers) -> ModeTransition {
-        let (name, rest) = self
-            .command
-            .split_at(self.command.find(' ').unwrap_or_else(|| self.command.len()));
-        if let Some(handler) = DEFAULT_COMMANDS.get(name) {
-            handler(buffers, if rest.is_empty() { rest } else { &rest[1..] })
-        } else {
-            ModeTransition::new_mode_and_info(Normal::new(), format!("Unknown command {}", name))
+    fn finish(&self, buffers: &mut Buffers, options: &mut ViewOptions) -> ModeTransition {
+        if !self.command.trim().is_empty() {
+            buffers.last_command = Some(self.command.clone());
         }
+        run_command_line(buffers, options, &self.command)
     }
 }
 
@@ -201,7 +1177,12 @@ impl Mode for Command {
         "COMMAND".into()
     }
 
-    fn transition(&self, evt: &Event, buffers: &mut Buffers, _: usize) -> Option<ModeTransition> {
+    fn transition(
+        &self,
+        evt: &Event,
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+    ) -> Option<ModeTransition> {
         if let Some(action) = DEFAULT_MAPS.event_to_action(evt) {
             let mut cursor = self.cursor;
             let mut command = self.command.to_owned();
@@ -224,7 +1205,7 @@ impl Mode for Command {
                 }
                 Action::CursorRight => {}
                 Action::Cancel => return Some(ModeTransition::new_mode(Normal::new())),
-                Action::Finish => return Some(self.finish(buffers)),
+                Action::Finish => return Some(self.finish(buffers, options)),
             }
             Some(ModeTransition::new_mode(Command { command, cursor }))
         } else if let Event::Key(KeyEvent {
@@ -249,3 +1230,17 @@ impl Mode for Command {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bufdo_refuses_carve() {
+        let mut buffers =
+            Buffers::with_buffer(Buffer::from_data_and_path(vec![0; 4], None::<&str>));
+        let transition = run_command_line(&mut buffers, &mut ViewOptions::new(), "bufdo carve 0 1");
+        assert_eq!(transition.info(), Some(":carve can't be run with :bufdo"));
+        assert_eq!(buffers.iter().count(), 1);
+    }
+}
@@ -0,0 +1,52 @@
+use std::borrow::Cow;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+use crate::modes::{
+    command,
+    mode::{Mode, ModeTransition},
+    normal::Normal,
+};
+use crate::{Buffers, ViewOptions};
+
+// Holds a command that could clobber the whole buffer until the user confirms it with `y`.
+// Any other key cancels it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Confirm {
+    pub command: String,
+    pub args: String,
+}
+
+impl Mode for Confirm {
+    fn name(&self) -> Cow<'static, str> {
+        "CONFIRM (y/N)".into()
+    }
+
+    fn transition(
+        &self,
+        evt: &Event,
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+    ) -> Option<ModeTransition> {
+        match evt {
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('y' | 'Y'),
+                modifiers,
+            }) if (*modifiers & !KeyModifiers::SHIFT).is_empty() => Some(command::run_command(
+                buffers,
+                options,
+                &self.command,
+                &self.args,
+            )),
+            Event::Key(_) => Some(ModeTransition::new_mode_and_info(
+                Normal::new(),
+                "cancelled".to_owned(),
+            )),
+            _ => None,
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
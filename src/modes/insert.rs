@@ -6,12 +6,12 @@ use lazy_static::lazy_static;
 
 use crate::keymap::KeyMap;
 use crate::modes::{
-    mode::{Mode, ModeTransition},
+    mode::{DirtyBytes, Mode, ModeTransition},
     normal::Normal,
 };
 use crate::operations as ops;
 use crate::selection::Direction;
-use crate::{Buffer, Buffers};
+use crate::{Buffer, Buffers, ViewOptions};
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum InsertionMode {
@@ -31,6 +31,7 @@ pub struct Insert {
 enum Action {
     InsertNull,
     SwitchInputMode,
+    SwitchSide,
     RemoveLast,
     RemoveThis,
     Exit,
@@ -42,6 +43,7 @@ fn default_maps() -> KeyMap<Action> {
         maps: keys!(
             (ctrl 'n' => Action::InsertNull),
             (ctrl 'o' => Action::SwitchInputMode),
+            (ctrl 'a' => Action::SwitchSide),
             (key KeyCode::Backspace => Action::RemoveLast),
             (key KeyCode::Delete => Action::RemoveThis),
             (key KeyCode::Esc => Action::Exit),
@@ -68,11 +70,11 @@ fn transition_ascii_insertion(
     match mode {
         InsertionMode::Append | InsertionMode::Insert => {
             let delta = ops::insert(&buffer.data, &buffer.selection, inserted_bytes);
-            ModeTransition::DirtyBytes(buffer.apply_incomplete_delta(delta))
+            ModeTransition::DirtyBytes(buffer.apply_typed_delta(delta))
         }
         InsertionMode::Overwrite => {
             let delta = ops::change(&buffer.data, &buffer.selection, inserted_bytes);
-            ModeTransition::DirtyBytes(buffer.apply_incomplete_delta(delta))
+            ModeTransition::DirtyBytes(buffer.apply_typed_delta(delta))
         }
     }
 }
@@ -101,7 +103,7 @@ fn transition_hex_insertion(
                         hex: true,
                         hex_half: Some(to_insert),
                     },
-                    buffer.apply_incomplete_delta_offset_carets(delta, -1, 0),
+                    buffer.apply_half_byte_delta(delta),
                 ))
             }
             InsertionMode::Overwrite => {
@@ -112,7 +114,7 @@ fn transition_hex_insertion(
                         hex: true,
                         hex_half: Some(to_insert),
                     },
-                    buffer.apply_incomplete_delta_offset_carets(delta, -1, 0),
+                    buffer.apply_half_byte_delta(delta),
                 ))
             }
         }
@@ -124,11 +126,50 @@ fn transition_hex_insertion(
                 hex: true,
                 hex_half: None,
             },
-            buffer.apply_incomplete_delta(delta),
+            buffer.apply_typed_delta(delta),
         ))
     }
 }
 
+// Inserts or overwrites with the same bytes at every selection
+pub fn insert_bytes(buffer: &mut Buffer, mode: InsertionMode, bytes: Vec<u8>) {
+    let delta = match mode {
+        InsertionMode::Append | InsertionMode::Insert => {
+            ops::insert(&buffer.data, &buffer.selection, bytes)
+        }
+        InsertionMode::Overwrite => ops::overwrite(&buffer.data, &buffer.selection, bytes),
+    };
+    buffer.apply_typed_delta(delta);
+}
+
+impl Insert {
+    fn paste_hex(&self, text: &str, buffer: &mut Buffer) -> Insert {
+        let mut digits = text.chars().filter(char::is_ascii_hexdigit).peekable();
+        let mut hex_half = self.hex_half;
+        if hex_half.is_some() {
+            if let Some(digit) = digits.next() {
+                transition_hex_insertion(digit, buffer, self.mode, hex_half);
+                hex_half = None;
+            }
+        }
+
+        let digits = digits.collect::<Vec<_>>();
+        let bytes = digits
+            .chunks_exact(2)
+            .map(|pair| (pair[0].to_digit(16).unwrap() << 4 | pair[1].to_digit(16).unwrap()) as u8)
+            .collect::<Vec<_>>();
+        if !bytes.is_empty() {
+            insert_bytes(buffer, self.mode, bytes);
+        }
+        if digits.len() % 2 == 1 {
+            transition_hex_insertion(*digits.last().unwrap(), buffer, self.mode, None);
+            hex_half = Some((digits.last().unwrap().to_digit(16).unwrap() as u8) << 4);
+        }
+
+        Insert { hex_half, ..*self }
+    }
+}
+
 impl Mode for Insert {
     fn name(&self) -> Cow<'static, str> {
         match (self.mode, self.hex) {
@@ -149,8 +190,9 @@ impl Mode for Insert {
         &self,
         evt: &Event,
         buffers: &mut Buffers,
-        bytes_per_line: usize,
+        options: &mut ViewOptions,
     ) -> Option<ModeTransition> {
+        let bytes_per_line = options.bytes_per_line;
         let buffer = buffers.current_mut();
         if let Some(action) = DEFAULT_MAPS.event_to_action(evt) {
             let new_state = if self.hex_half.is_some() {
@@ -162,46 +204,78 @@ impl Mode for Insert {
                 *self
             };
             Some(match action {
+                Action::Exit if self.hex_half.is_some() => {
+                    // A byte with only one digit entered is dropped
+                    let dirty = buffer.discard_half_byte();
+                    buffer.commit_delta();
+                    ModeTransition::new_mode_and_dirty(Normal::new(), dirty)
+                }
                 Action::Exit => {
                     buffer.commit_delta(); // Flush this insertion as a single action
                     ModeTransition::new_mode(Normal::new())
                 }
                 Action::InsertNull => {
-                    let inserted_bytes = vec![0];
+                    let inserted_bytes = vec![options.fill_byte];
                     let delta = ops::insert(&buffer.data, &buffer.selection, inserted_bytes);
-                    ModeTransition::new_mode_and_dirty(
-                        new_state,
-                        buffer.apply_incomplete_delta(delta),
-                    )
+                    ModeTransition::new_mode_and_dirty(new_state, buffer.apply_typed_delta(delta))
+                }
+                Action::SwitchInputMode => ModeTransition::new_mode_and_dirty(
+                    Insert {
+                        mode: self.mode,
+                        hex: !self.hex,
+                        hex_half: None,
+                    },
+                    buffer.discard_half_byte(),
+                ),
+                // Moves the carets to the other end of the selections, like leaving and
+                // entering with `a` instead of `i` or vice versa
+                Action::SwitchSide if self.hex_half.is_some() => ModeTransition::None,
+                Action::SwitchSide => {
+                    let max_bytes = buffer.data.len();
+                    let (mode, dirty) = match self.mode {
+                        InsertionMode::Insert => (
+                            InsertionMode::Append,
+                            buffer.map_selections(|region| {
+                                vec![region.to_forward().simple_extend(
+                                    Direction::Right,
+                                    bytes_per_line,
+                                    max_bytes,
+                                    1,
+                                )]
+                            }),
+                        ),
+                        InsertionMode::Append => (
+                            InsertionMode::Insert,
+                            buffer.map_selections(|region| {
+                                vec![region
+                                    .simple_extend(Direction::Left, bytes_per_line, max_bytes, 1)
+                                    .to_backward()]
+                            }),
+                        ),
+                        InsertionMode::Overwrite => return Some(ModeTransition::None),
+                    };
+                    ModeTransition::new_mode_and_dirty(Insert { mode, ..*self }, dirty)
                 }
-                Action::SwitchInputMode => ModeTransition::new_mode(Insert {
-                    mode: self.mode,
-                    hex: !self.hex,
-                    hex_half: None,
-                }),
                 Action::RemoveLast | Action::RemoveThis if self.hex_half.is_some() => {
                     if buffer.data.is_empty() {
                         return Some(ModeTransition::None);
                     }
                     let delta = ops::delete_cursor(&buffer.data, &buffer.selection);
-                    ModeTransition::new_mode_and_dirty(
-                        new_state,
-                        buffer.apply_incomplete_delta(delta),
-                    )
+                    ModeTransition::new_mode_and_dirty(new_state, buffer.apply_typed_delta(delta))
                 }
                 Action::RemoveLast => {
                     if buffer.data.is_empty() {
                         return Some(ModeTransition::None);
                     }
                     let delta = ops::backspace(&buffer.data, &buffer.selection);
-                    ModeTransition::DirtyBytes(buffer.apply_incomplete_delta(delta))
+                    ModeTransition::DirtyBytes(buffer.apply_typed_delta(delta))
                 }
                 Action::RemoveThis => {
                     if buffer.data.is_empty() {
                         return Some(ModeTransition::None);
                     }
                     let delta = ops::delete_cursor(&buffer.data, &buffer.selection);
-                    ModeTransition::DirtyBytes(buffer.apply_incomplete_delta(delta))
+                    ModeTransition::DirtyBytes(buffer.apply_typed_delta(delta))
                 }
                 Action::Move(direction) => {
                     let is_hex_half = self.hex_half.is_some();
@@ -250,7 +324,206 @@ impl Mode for Insert {
         }
     }
 
+    fn paste(
+        &self,
+        text: &str,
+        buffers: &mut Buffers,
+        _: &mut ViewOptions,
+    ) -> Option<ModeTransition> {
+        let buffer = buffers.current_mut();
+        let new_state = if self.hex {
+            self.paste_hex(text, buffer)
+        } else {
+            insert_bytes(buffer, self.mode, text.as_bytes().to_vec());
+            *self
+        };
+        Some(ModeTransition::new_mode_and_dirty(
+            new_state,
+            DirtyBytes::ChangeLength,
+        ))
+    }
+
+    fn caret_in_hex(&self) -> bool {
+        self.hex
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::SelRegion;
+
+    fn buffers_with_cursor(data: &[u8], offset: usize) -> Buffers {
+        let mut buffer = Buffer::from_data_and_path(data.to_vec(), None::<&str>);
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(offset, offset)]);
+        Buffers::with_buffer(buffer)
+    }
+
+    fn press(mode: &Insert, buffers: &mut Buffers, code: KeyCode) {
+        mode.transition(
+            &Event::Key(KeyEvent::new(code, KeyModifiers::NONE)),
+            buffers,
+            &mut ViewOptions::new(),
+        );
+    }
+
+    fn data(buffers: &Buffers) -> Vec<u8> {
+        Vec::from(&buffers.current().data)
+    }
+
+    const INSERT: Insert = Insert {
+        mode: InsertionMode::Insert,
+        hex: false,
+        hex_half: None,
+    };
+
+    #[test]
+    fn test_backspace_past_end_is_noop() {
+        let mut buffers = buffers_with_cursor(b"ab", 2);
+        press(&INSERT, &mut buffers, KeyCode::Backspace);
+        assert_eq!(data(&buffers), b"ab");
+        press(&INSERT, &mut buffers, KeyCode::Delete);
+        assert_eq!(data(&buffers), b"ab");
+    }
+
+    #[test]
+    fn test_insert_past_end_appends() {
+        let mut buffers = buffers_with_cursor(b"ab", 2);
+        press(&INSERT, &mut buffers, KeyCode::Char('c'));
+        press(&INSERT, &mut buffers, KeyCode::Char('d'));
+        assert_eq!(data(&buffers), b"abcd");
+
+        // The appended bytes can be removed again, but not the ones before them
+        for _ in 0..3 {
+            press(&INSERT, &mut buffers, KeyCode::Backspace);
+        }
+        assert_eq!(data(&buffers), b"ab");
+    }
+
+    #[test]
+    fn test_backspace_in_append() {
+        let mut buffers = buffers_with_cursor(b"ab", 1);
+        let append = Insert {
+            mode: InsertionMode::Append,
+            ..INSERT
+        };
+        buffers
+            .current_mut()
+            .map_selections(|region| vec![region.simple_extend(Direction::Right, 0x10, 2, 1)]);
+        press(&append, &mut buffers, KeyCode::Char('c'));
+        assert_eq!(data(&buffers), b"abc");
+        press(&append, &mut buffers, KeyCode::Backspace);
+        assert_eq!(data(&buffers), b"ab");
+    }
+
+    #[test]
+    fn test_paste_ascii() {
+        let mut buffers = buffers_with_cursor(b"ab", 1);
+        INSERT.paste("xyz\n", &mut buffers, &mut ViewOptions::new());
+        assert_eq!(data(&buffers), b"axyz\nb");
+    }
+
+    #[test]
+    fn test_paste_overwrite() {
+        let mut buffers = buffers_with_cursor(b"abcd", 1);
+        let overwrite = Insert {
+            mode: InsertionMode::Overwrite,
+            ..INSERT
+        };
+        overwrite.paste("xy", &mut buffers, &mut ViewOptions::new());
+        assert_eq!(data(&buffers), b"axyd");
+    }
+
+    #[test]
+    fn test_paste_hex() {
+        let mut buffers = buffers_with_cursor(b"ab", 1);
+        let hex = Insert {
+            hex: true,
+            ..INSERT
+        };
+        let transition = hex.paste("de ad b", &mut buffers, &mut ViewOptions::new());
+        assert_eq!(data(&buffers), b"a\xde\xad\xb0b");
+
+        // The odd digit is left as a half byte that the next digit completes
+        let mode = match transition {
+            Some(ModeTransition::ModeAndDirtyBytes(mode, _)) => mode,
+            _ => panic!("expected a mode transition"),
+        };
+        assert!(mode.has_half_cursor());
+        mode.paste("e", &mut buffers, &mut ViewOptions::new());
+        assert_eq!(data(&buffers), b"a\xde\xad\xbeb");
+    }
+
+    #[test]
+    fn test_switch_side() {
+        let mut buffers = buffers_with_cursor(b"ab", 0);
+        let transition = INSERT.transition(
+            &Event::Key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL)),
+            &mut buffers,
+            &mut ViewOptions::new(),
+        );
+        let mode = match transition {
+            Some(ModeTransition::ModeAndDirtyBytes(mode, _)) => mode,
+            _ => panic!("expected a mode transition"),
+        };
+        assert_eq!(mode.name(), "APPEND (ascii)");
+        mode.transition(
+            &Event::Key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE)),
+            &mut buffers,
+            &mut ViewOptions::new(),
+        );
+        assert_eq!(data(&buffers), b"axb");
+    }
+
+    #[test]
+    fn test_escape_discards_half_byte() {
+        let hex = Insert {
+            hex: true,
+            ..INSERT
+        };
+        let mut buffers = buffers_with_cursor(b"ab", 1);
+        press(&hex, &mut buffers, KeyCode::Char('f'));
+        assert_eq!(data(&buffers), b"a\xf0b");
+        let half = Insert {
+            hex_half: Some(0xf0),
+            ..hex
+        };
+        press(&half, &mut buffers, KeyCode::Esc);
+        assert_eq!(data(&buffers), b"ab");
+        assert_eq!(buffers.current().selection.main_cursor_offset(), 1);
+
+        // Undoing the insertion doesn't bring the half byte back
+        buffers.current_mut().perform_undo();
+        assert_eq!(data(&buffers), b"ab");
+    }
+
+    #[test]
+    fn test_escape_restores_overwritten_half_byte() {
+        let overwrite = Insert {
+            mode: InsertionMode::Overwrite,
+            hex: true,
+            ..INSERT
+        };
+        let half = |digit| Insert {
+            hex_half: Some(digit),
+            ..overwrite
+        };
+        let mut buffers = buffers_with_cursor(b"abc", 1);
+        press(&overwrite, &mut buffers, KeyCode::Char('1'));
+        press(&half(0x10), &mut buffers, KeyCode::Char('2'));
+        press(&overwrite, &mut buffers, KeyCode::Char('f'));
+        assert_eq!(data(&buffers), b"a\x12\xf3");
+        press(&half(0xf0), &mut buffers, KeyCode::Esc);
+        assert_eq!(data(&buffers), b"a\x12c");
+
+        // The completed byte is still undone as a whole
+        buffers.current_mut().perform_undo();
+        assert_eq!(data(&buffers), b"abc");
+    }
+}
@@ -7,41 +7,52 @@ use lazy_static::lazy_static;
 use crate::keymap::KeyMap;
 use crate::modes::{
     mode::{Mode, ModeTransition},
-    normal::Normal,
+    normal::{self, Normal},
 };
+use crate::operations as ops;
 use crate::selection::Direction;
-use crate::Buffers;
+use crate::{cmd_count, Buffers, ViewOptions};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct JumpTo {
     pub extend: bool,
+    pub count_state: cmd_count::State,
 }
 
-fn default_maps() -> KeyMap<Direction> {
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Action {
+    Boundary(Direction),
+    ReplaceWithRegister { register: char },
+    ResetToCaret,
+}
+
+fn default_maps() -> KeyMap<Action> {
     KeyMap {
         maps: keys!(
-            (key KeyCode::Left => Direction::Left),
-            ('h' => Direction::Left),
-            (key KeyCode::Down => Direction::Down),
-            ('j' => Direction::Down),
-            (key KeyCode::Up => Direction::Up),
-            ('k' => Direction::Up),
-            (key KeyCode::Right => Direction::Right),
-            ('l' => Direction::Right)
+            (key KeyCode::Left => Action::Boundary(Direction::Left)),
+            ('h' => Action::Boundary(Direction::Left)),
+            (key KeyCode::Down => Action::Boundary(Direction::Down)),
+            ('j' => Action::Boundary(Direction::Down)),
+            (key KeyCode::Up => Action::Boundary(Direction::Up)),
+            ('k' => Action::Boundary(Direction::Up)),
+            (key KeyCode::Right => Action::Boundary(Direction::Right)),
+            ('l' => Action::Boundary(Direction::Right)),
+            ('r' => Action::ReplaceWithRegister{register: '"'}),
+            (';' => Action::ResetToCaret)
         ),
     }
 }
 
 lazy_static! {
-    static ref DEFAULT_MAPS: KeyMap<Direction> = default_maps();
+    static ref DEFAULT_MAPS: KeyMap<Action> = default_maps();
 }
 
 impl Mode for JumpTo {
     fn name(&self) -> Cow<'static, str> {
         if self.extend {
-            "EXTEND".into()
+            format!("EXTEND{}", self.count_state).into()
         } else {
-            "JUMP".into()
+            format!("JUMP{}", self.count_state).into()
         }
     }
 
@@ -49,20 +60,85 @@ impl Mode for JumpTo {
         &self,
         evt: &Event,
         buffers: &mut Buffers,
-        bytes_per_line: usize,
+        options: &mut ViewOptions,
     ) -> Option<ModeTransition> {
+        let bytes_per_line = options.bytes_per_line;
         let buffer = buffers.current_mut();
-        if let Some(direction) = DEFAULT_MAPS.event_to_action(evt) {
+        if let cmd_count::Transition::Update(new_state) = self.count_state.transition(evt) {
+            Some(ModeTransition::new_mode(JumpTo {
+                count_state: new_state,
+                ..*self
+            }))
+        } else if let Some(Action::ReplaceWithRegister { register }) =
+            DEFAULT_MAPS.event_to_action(evt)
+        {
+            let contents = match buffer.registers.get(&register) {
+                Some(contents) if !contents.is_empty() => contents,
+                _ => {
+                    return Some(ModeTransition::new_mode_and_info(
+                        Normal::new(),
+                        "register is empty".to_owned(),
+                    ))
+                }
+            };
+            // A count picks the entry that the first selection gets, and cycling goes on from it
+            let first = match self.count_state {
+                cmd_count::State::None => 0,
+                cmd_count::State::Some { count, .. } if (1..=contents.len()).contains(&count) => {
+                    count - 1
+                }
+                cmd_count::State::Some { .. } => {
+                    return Some(ModeTransition::new_mode_and_info(
+                        Normal::new(),
+                        format!(
+                            "expected a count from 1 to {}, the entries in the register",
+                            contents.len()
+                        ),
+                    ))
+                }
+            };
+            let mut contents = contents.clone();
+            contents.rotate_left(first);
+            let delta = ops::replace_with_register(&buffer.data, &buffer.selection, &contents);
+            Some(normal::apply_replacement(buffer, delta))
+        } else if let Some(Action::ResetToCaret) = DEFAULT_MAPS.event_to_action(evt) {
+            // A count picks the selection to keep instead of the main one
+            let index = match self.count_state {
+                cmd_count::State::None => buffer.selection.main_selection,
+                cmd_count::State::Some { count, .. }
+                    if (1..=buffer.selection.len()).contains(&count) =>
+                {
+                    count - 1
+                }
+                cmd_count::State::Some { .. } => {
+                    return Some(ModeTransition::new_mode_and_info(
+                        Normal::new(),
+                        format!(
+                            "expected a count from 1 to {}, the number of selections",
+                            buffer.selection.len()
+                        ),
+                    ))
+                }
+            };
+            Some(ModeTransition::new_mode_and_dirty(
+                Normal::new(),
+                buffer.retain_caret(index),
+            ))
+        } else if let Some(Action::Boundary(direction)) = DEFAULT_MAPS.event_to_action(evt) {
             let max_bytes = buffer.data.len();
+            let count = match self.count_state {
+                cmd_count::State::None => None,
+                cmd_count::State::Some { count, .. } => Some(count),
+            };
             Some(ModeTransition::new_mode_and_dirty(
                 Normal::new(),
                 if self.extend {
                     buffer.map_selections(|region| {
-                        vec![region.extend_to_boundary(direction, bytes_per_line, max_bytes)]
+                        vec![region.extend_to_boundary(direction, bytes_per_line, max_bytes, count)]
                     })
                 } else {
                     buffer.map_selections(|region| {
-                        vec![region.jump_to_boundary(direction, bytes_per_line, max_bytes)]
+                        vec![region.jump_to_boundary(direction, bytes_per_line, max_bytes, count)]
                     })
                 },
             ))
@@ -73,7 +149,155 @@ impl Mode for JumpTo {
         }
     }
 
+    fn is_prefix(&self) -> bool {
+        true
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::SelRegion;
+    use crate::Buffer;
+
+    fn jump(len: usize, caret: usize, keys: &str) -> usize {
+        let mut buffer = Buffer::from_data_and_path(vec![0; len], None::<&str>);
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(caret, caret)]);
+        let mut buffers = Buffers::with_buffer(buffer);
+        let mut options = ViewOptions::new();
+
+        let mut mode: Box<dyn Mode> = Box::new(JumpTo {
+            extend: false,
+            count_state: cmd_count::State::None,
+        });
+        for ch in keys.chars() {
+            let evt = Event::Key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+            match mode.transition(&evt, &mut buffers, &mut options) {
+                Some(ModeTransition::NewMode(next)) => mode = next,
+                Some(ModeTransition::ModeAndDirtyBytes(next, _)) => mode = next,
+                _ => panic!("unexpected transition"),
+            }
+        }
+        buffers.current().selection.main_cursor_offset()
+    }
+
+    #[test]
+    fn test_uncounted_jumps() {
+        assert_eq!(jump(0x25, 0x05, "j"), 0x24);
+        assert_eq!(jump(0x25, 0x15, "k"), 0x00);
+        assert_eq!(jump(0x25, 0x15, "h"), 0x10);
+        assert_eq!(jump(0x25, 0x15, "l"), 0x1f);
+    }
+
+    #[test]
+    fn test_counted_jump_down() {
+        assert_eq!(jump(0x25, 0x05, "1j"), 0x1f);
+        assert_eq!(jump(0x25, 0x05, "2j"), 0x24);
+        assert_eq!(jump(0x25, 0x05, "9j"), 0x24);
+        assert_eq!(jump(0x25, 0x15, "1j"), 0x24);
+        // From the overflow cursor
+        assert_eq!(jump(0x25, 0x25, "3j"), 0x24);
+    }
+
+    #[test]
+    fn test_counted_jump_up_and_lines() {
+        assert_eq!(jump(0x25, 0x25, "1k"), 0x10);
+        assert_eq!(jump(0x25, 0x15, "5k"), 0x00);
+        assert_eq!(jump(0x25, 0x05, "2l"), 0x1f);
+        assert_eq!(jump(0x25, 0x05, "9l"), 0x24);
+        assert_eq!(jump(0x25, 0x21, "2h"), 0x10);
+        assert_eq!(jump(0x25, 0x21, "1h"), 0x20);
+    }
+
+    #[test]
+    fn test_reset_to_caret() {
+        let mut buffer = Buffer::from_data_and_path(vec![0; 0x10], None::<&str>);
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(1, 0), SelRegion::new(5, 8)]);
+        buffer.selection.select_index(1);
+        let mut buffers = Buffers::with_buffer(buffer);
+
+        let mode = JumpTo {
+            extend: false,
+            count_state: cmd_count::State::None,
+        };
+        let evt = Event::Key(KeyEvent::new(KeyCode::Char(';'), KeyModifiers::NONE));
+        mode.transition(&evt, &mut buffers, &mut ViewOptions::new());
+        let selection = &buffers.current().selection;
+        assert_eq!(selection.len(), 1);
+        assert_eq!((selection.main().caret, selection.main().tail), (5, 5));
+    }
+
+    #[test]
+    fn test_counted_reset_to_caret() {
+        let mut buffer = Buffer::from_data_and_path(vec![0; 0x10], None::<&str>);
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(1, 0), SelRegion::new(5, 8)]);
+        let mut buffers = Buffers::with_buffer(buffer);
+        let evt = Event::Key(KeyEvent::new(KeyCode::Char(';'), KeyModifiers::NONE));
+        let counted = |count| JumpTo {
+            extend: false,
+            count_state: cmd_count::State::Some { hex: false, count },
+        };
+
+        let transition = counted(3).transition(&evt, &mut buffers, &mut ViewOptions::new());
+        assert_eq!(
+            transition.unwrap().info(),
+            Some("expected a count from 1 to 2, the number of selections")
+        );
+        assert_eq!(buffers.current().selection.len(), 2);
+
+        counted(2).transition(&evt, &mut buffers, &mut ViewOptions::new());
+        let selection = &buffers.current().selection;
+        assert_eq!(selection.len(), 1);
+        assert_eq!((selection.main().caret, selection.main().tail), (5, 5));
+    }
+
+    #[test]
+    fn test_replace_with_register_cycles() {
+        let replace = |count_state| {
+            let mut buffer = Buffer::from_data_and_path(b"aabbcc".to_vec(), None::<&str>);
+            buffer.selection.map_selections(|_| {
+                vec![
+                    SelRegion::new(1, 0),
+                    SelRegion::new(3, 2),
+                    SelRegion::new(5, 4),
+                ]
+            });
+            buffer
+                .registers
+                .insert('"', vec![b"x".to_vec(), b"yz".to_vec()]);
+            let mut buffers = Buffers::with_buffer(buffer);
+
+            let mode = JumpTo {
+                extend: false,
+                count_state,
+            };
+            let evt = Event::Key(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::NONE));
+            let info = mode
+                .transition(&evt, &mut buffers, &mut ViewOptions::new())
+                .and_then(|transition| transition.info().map(str::to_owned));
+            (Vec::from(&buffers.current().data), info)
+        };
+        assert_eq!(replace(cmd_count::State::None).0, b"xyzx");
+
+        // A count starts cycling from that entry
+        let counted = |count| cmd_count::State::Some { hex: false, count };
+        assert_eq!(replace(counted(2)).0, b"yzxyz");
+        assert_eq!(
+            replace(counted(3)),
+            (
+                b"aabbcc".to_vec(),
+                Some("expected a count from 1 to 2, the entries in the register".to_owned())
+            )
+        );
+    }
+}
@@ -15,7 +15,7 @@ pub mod quitting {
             &self,
             _: &crossterm::event::Event,
             _: &mut crate::buffer::Buffers,
-            _: usize,
+            _: &mut crate::ViewOptions,
         ) -> Option<crate::modes::mode::ModeTransition> {
             unreachable!();
         }
@@ -25,12 +25,20 @@ pub mod quitting {
     }
 }
 
+pub mod bracket;
 pub mod collapse;
 pub mod command;
+pub mod confirm;
 pub mod insert;
 pub mod jumpto;
 pub mod mode;
 pub mod normal;
+pub mod operator;
 pub mod replace;
+pub mod replay;
+pub mod run;
 pub mod search;
 pub mod split;
+pub mod substitute;
+pub mod text;
+pub mod visual;
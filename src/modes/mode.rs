@@ -2,7 +2,7 @@ use crossterm::event::Event;
 use std::borrow::Cow;
 use xi_rope::Interval;
 
-use crate::Buffers;
+use crate::{Buffers, ViewOptions};
 
 // A mode should OWN all data related to it. Hence we bound it by 'static.
 pub trait Mode: 'static {
@@ -12,15 +12,39 @@ pub trait Mode: 'static {
         &self,
         event: &Event,
         buffers: &mut Buffers,
-        bytes_per_line: usize,
+        options: &mut ViewOptions,
     ) -> Option<ModeTransition>;
 
+    // Called with a burst of text that arrived at once, so that it can be inserted
+    // without handling each character separately
+    fn paste(
+        &self,
+        _text: &str,
+        _buffers: &mut Buffers,
+        _options: &mut ViewOptions,
+    ) -> Option<ModeTransition> {
+        None
+    }
+
+    // The names of this mode and of the modes it will hand over to, outermost first
+    fn mode_stack(&self) -> Vec<Cow<'static, str>> {
+        vec![self.name()]
+    }
     fn takes_input(&self) -> bool {
         true
     }
+    // Whether the mode only waits for the next key of a sequence, like after `g`. With
+    // `timeoutlen` set, the sequence is dropped if that key doesn't come in time.
+    fn is_prefix(&self) -> bool {
+        false
+    }
     fn has_half_cursor(&self) -> bool {
         false
     }
+    // Whether the caret is in the hex pane; the other pane only gets a subtler highlight
+    fn caret_in_hex(&self) -> bool {
+        true
+    }
     fn as_any(&self) -> &dyn std::any::Any;
 }
 
@@ -30,12 +54,26 @@ pub enum DirtyBytes {
     ChangeLength,
 }
 
+impl DirtyBytes {
+    // The bytes dirtied by both changes
+    pub fn merge(self, other: DirtyBytes) -> DirtyBytes {
+        match (self, other) {
+            (DirtyBytes::ChangeInPlace(mut intervals), DirtyBytes::ChangeInPlace(other)) => {
+                intervals.extend(other);
+                DirtyBytes::ChangeInPlace(intervals)
+            }
+            _ => DirtyBytes::ChangeLength,
+        }
+    }
+}
+
 pub enum ModeTransition {
     None,
     NewMode(Box<dyn Mode>),
     DirtyBytes(DirtyBytes),
     ModeAndDirtyBytes(Box<dyn Mode>, DirtyBytes),
     ModeAndInfo(Box<dyn Mode>, String),
+    ModeAndDirtyBytesAndInfo(Box<dyn Mode>, DirtyBytes, String),
 }
 
 impl ModeTransition {
@@ -50,4 +88,47 @@ impl ModeTransition {
     pub fn new_mode_and_info(mode: impl Mode, info: String) -> ModeTransition {
         ModeTransition::ModeAndInfo(Box::new(mode), info)
     }
+
+    pub fn new_mode_dirty_and_info(
+        mode: impl Mode,
+        dirty: DirtyBytes,
+        info: String,
+    ) -> ModeTransition {
+        ModeTransition::ModeAndDirtyBytesAndInfo(Box::new(mode), dirty, info)
+    }
+
+    // Replaces the mode this transition switches to, if any
+    pub fn map_mode(self, f: impl FnOnce(Box<dyn Mode>) -> Box<dyn Mode>) -> ModeTransition {
+        match self {
+            ModeTransition::NewMode(mode) => ModeTransition::NewMode(f(mode)),
+            ModeTransition::ModeAndDirtyBytes(mode, dirty) => {
+                ModeTransition::ModeAndDirtyBytes(f(mode), dirty)
+            }
+            ModeTransition::ModeAndInfo(mode, info) => ModeTransition::ModeAndInfo(f(mode), info),
+            ModeTransition::ModeAndDirtyBytesAndInfo(mode, dirty, info) => {
+                ModeTransition::ModeAndDirtyBytesAndInfo(f(mode), dirty, info)
+            }
+            transition => transition,
+        }
+    }
+
+    pub fn info(&self) -> Option<&str> {
+        match self {
+            ModeTransition::ModeAndInfo(_, info)
+            | ModeTransition::ModeAndDirtyBytesAndInfo(_, _, info) => Some(info),
+            _ => None,
+        }
+    }
+
+    // Attaches an info note to a transition that switches modes; other transitions
+    // are returned unchanged
+    pub fn with_info(self, info: Option<String>) -> ModeTransition {
+        match (self, info) {
+            (ModeTransition::NewMode(mode), Some(info)) => ModeTransition::ModeAndInfo(mode, info),
+            (ModeTransition::ModeAndDirtyBytes(mode, dirty), Some(info)) => {
+                ModeTransition::ModeAndDirtyBytesAndInfo(mode, dirty, info)
+            }
+            (transition, _) => transition,
+        }
+    }
 }
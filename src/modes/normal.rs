@@ -1,16 +1,22 @@
 use std::borrow::Cow;
+use std::cmp;
 use std::collections::HashMap;
+use std::sync::RwLock;
 
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use lazy_static::lazy_static;
 
-use crate::keymap::KeyMap;
+use crate::byte_rope::RopeDelta;
+use crate::cancel::Cancelled;
+use crate::keymap::{parse_key, KeyMap};
+use crate::modes::operator::Operator;
+use crate::modes::search::Pattern;
 use crate::operations as ops;
-use crate::selection::Direction;
+use crate::selection::{Direction, SelRegion};
 use crate::{
     cmd_count, modes,
     modes::mode::{DirtyBytes, Mode, ModeTransition},
-    Buffers,
+    Buffer, Buffers, ViewOptions,
 };
 
 use super::insert::InsertionMode;
@@ -20,33 +26,47 @@ pub struct Normal {
     count_state: cmd_count::State,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone)]
 enum Action {
     Move(Direction),
     Extend(Direction),
     SplitMode,
+    SelectRun,
     JumpToMode,
+    BracketMode { forward: bool },
     ExtendToMode,
     CollapseMode { hex: bool },
+    SubstituteMode { hex: bool },
     CommandMode,
+    ReplayMode,
     SwapCaret,
     CollapseSelection,
     Delete { register: char },
     Yank { register: char },
     Paste { after: bool, register: char },
     Change { hex: bool, register: char },
+    OperatorMode(Operator),
     Insert { hex: bool },
     Append { hex: bool },
     Overwrite { hex: bool },
+    TextEntry { overwrite: bool },
     RemoveMain,
     RetainMain,
     SelectPrev,
     SelectNext,
     SelectAll,
+    SelectRows,
     ReplaceMode { hex: bool },
-    Measure,
+    Measure { all: bool },
+    SearchSelection { forward: bool },
+    SearchNext { forward: bool },
+    VisualMode { linewise: bool },
+    AlternateBuffer,
+    JumpBack,
     Undo,
     Redo,
+    // A command line bound to a key in the config, run as if typed after `:`
+    RunCommand(String),
 }
 
 fn default_maps() -> KeyMap<Action> {
@@ -66,16 +86,30 @@ fn default_maps() -> KeyMap<Action> {
             ('L' => Action::Extend(Direction::Right)),
             ('g' => Action::JumpToMode),
             ('G' => Action::ExtendToMode),
+            (']' => Action::BracketMode{forward: true}),
+            ('[' => Action::BracketMode{forward: false}),
             (alt 's' => Action::SplitMode),
             (':' => Action::CommandMode),
+            ('@' => Action::ReplayMode),
             (';' => Action::CollapseSelection),
             (alt ';' => Action::SwapCaret),
             ('%' => Action::SelectAll),
+            ('X' => Action::SelectRows),
+            (alt 'x' => Action::SelectRun),
             (' ' => Action::RetainMain),
             (alt ' ' => Action::RemoveMain),
             ('(' => Action::SelectPrev),
             (')' => Action::SelectNext),
-            ('M' => Action::Measure),
+            ('M' => Action::Measure{all: false}),
+            (alt 'M' => Action::Measure{all: true}),
+            ('*' => Action::SearchSelection{forward: true}),
+            ('#' => Action::SearchSelection{forward: false}),
+            ('v' => Action::VisualMode{linewise: false}),
+            ('V' => Action::VisualMode{linewise: true}),
+            ('n' => Action::SearchNext{forward: true}),
+            ('N' => Action::SearchNext{forward: false}),
+            (ctrl '6' => Action::AlternateBuffer),
+            (ctrl 'o' => Action::JumpBack),
             ('u' => Action::Undo),
             ('U' => Action::Redo),
 
@@ -85,6 +119,10 @@ fn default_maps() -> KeyMap<Action> {
             ('y' => Action::Yank{register: '"'}),
             ('c' => Action::Change{hex: false, register: '"'}),
             ('C' => Action::Change{hex: true, register: '"'}),
+            (alt 'd' => Action::OperatorMode(Operator::Delete)),
+            (alt 'y' => Action::OperatorMode(Operator::Yank)),
+            (alt 'c' => Action::OperatorMode(Operator::Change{hex: false})),
+            (alt 'C' => Action::OperatorMode(Operator::Change{hex: true})),
 
             ('i' => Action::Insert{hex: false}),
             ('I' => Action::Insert{hex: true}),
@@ -94,15 +132,113 @@ fn default_maps() -> KeyMap<Action> {
             ('R' => Action::ReplaceMode{hex: true}),
             ('o' => Action::Overwrite{hex: false}),
             ('O' => Action::Overwrite{hex: true}),
+            (alt 'i' => Action::TextEntry{overwrite: false}),
+            (alt 'o' => Action::TextEntry{overwrite: true}),
 
             ('s' => Action::CollapseMode{hex: false}),
-            ('S' => Action::CollapseMode{hex: true})
+            ('S' => Action::CollapseMode{hex: true}),
+            (alt '/' => Action::SubstituteMode{hex: false}),
+            (alt '?' => Action::SubstituteMode{hex: true})
         ),
     }
 }
 
+// Selects the next match of the last pattern after (or before) the main selection
+fn jump_to_match(buffers: &mut Buffers, forward: bool, align: usize) -> ModeTransition {
+    let pattern = match &buffers.last_pattern {
+        Some(pattern) => pattern.clone(),
+        None => return ModeTransition::new_mode_and_info(Normal::new(), "no pattern".to_owned()),
+    };
+    let buffer = buffers.current_mut();
+    match pattern.find_next(buffer, buffer.selection.main().min(), forward, align) {
+        Err(_) => ModeTransition::new_mode_and_info(Normal::new(), Cancelled::info()),
+        Ok(Some((range, wrapped))) => {
+            let dirty = buffer.replace_selection(SelRegion::new(range.end - 1, range.start));
+            if wrapped {
+                ModeTransition::new_mode_dirty_and_info(
+                    Normal::new(),
+                    dirty,
+                    "search wrapped".to_owned(),
+                )
+            } else {
+                ModeTransition::new_mode_and_dirty(Normal::new(), dirty)
+            }
+        }
+        Ok(None) => {
+            ModeTransition::new_mode_and_info(Normal::new(), "pattern not found".to_owned())
+        }
+    }
+}
+
+// Applies a delta that replaces bytes, noting when it leaves them all as they were
+pub fn apply_replacement(buffer: &mut Buffer, delta: RopeDelta) -> ModeTransition {
+    if buffer.data.is_unchanged_by(&delta) {
+        return ModeTransition::new_mode_and_info(Normal::new(), NO_CHANGE_INFO.to_owned());
+    }
+    ModeTransition::new_mode_and_dirty(Normal::new(), buffer.apply_delta(delta))
+}
+
+// Yanks the selected bytes into `register` and deletes them
+pub fn delete_selections(buffer: &mut Buffer, register: char, options: &ViewOptions) -> DirtyBytes {
+    buffer.yank_selections(register, options);
+    let delta = ops::deletion(&buffer.data, &buffer.selection);
+    buffer.apply_delta(delta)
+}
+
+// Yanks the selected bytes into `register`, then replaces them in insert mode
+pub fn change_selections(
+    buffer: &mut Buffer,
+    hex: bool,
+    register: char,
+    options: &ViewOptions,
+) -> ModeTransition {
+    buffer.yank_selections(register, options);
+    let insert = modes::insert::Insert {
+        hex,
+        mode: InsertionMode::Insert,
+        hex_half: None,
+    };
+    if !buffer.data.is_empty() {
+        let delta = ops::deletion(&buffer.data, &buffer.selection);
+        ModeTransition::new_mode_and_dirty(insert, buffer.apply_delta(delta))
+    } else {
+        ModeTransition::new_mode(insert)
+    }
+}
+
+pub fn switch_to_alternate_buffer(buffers: &mut Buffers) -> ModeTransition {
+    if !buffers.switch_to_previous() {
+        return ModeTransition::new_mode_and_info(Normal::new(), "no alternate buffer".to_owned());
+    }
+    let name = buffers.current().name();
+    ModeTransition::new_mode_dirty_and_info(Normal::new(), DirtyBytes::ChangeLength, name)
+}
+
+pub const EMPTY_BUFFER_INFO: &str = "buffer is empty";
+pub const NO_CHANGE_INFO: &str = "no change";
+pub const NO_MATCHES_INFO: &str = "no matches, selection unchanged";
+
 lazy_static! {
     static ref DEFAULT_MAPS: KeyMap<Action> = default_maps();
+    // Bindings from the user's config, consulted before the default keys
+    static ref USER_MAPS: RwLock<KeyMap<Action>> = RwLock::new(KeyMap {
+        maps: HashMap::new()
+    });
+}
+
+// Makes `key` run `command_line` as if typed after `:`. Rebinding a default key works, but
+// returns a warning to show to the user.
+pub fn register_binding(key_name: &str, command_line: &str) -> Result<Option<String>, String> {
+    let key = parse_key(key_name)?;
+    USER_MAPS
+        .write()
+        .unwrap()
+        .maps
+        .insert(key, Action::RunCommand(command_line.to_owned()));
+    Ok(DEFAULT_MAPS
+        .maps
+        .contains_key(&key)
+        .then(|| format!("binding for {} replaces a default key", key_name)))
 }
 
 impl Mode for Normal {
@@ -114,19 +250,35 @@ impl Mode for Normal {
         &self,
         event: &Event,
         buffers: &mut Buffers,
-        bytes_per_line: usize,
+        options: &mut ViewOptions,
     ) -> Option<ModeTransition> {
+        let bytes_per_line = options.bytes_per_line;
         let buffer = buffers.current_mut();
         if let cmd_count::Transition::Update(new_state) = self.count_state.transition(event) {
             Some(ModeTransition::new_mode(Normal {
                 count_state: new_state,
             }))
-        } else if let Some(action) = DEFAULT_MAPS.event_to_action(event) {
+        } else if let Some(action) = USER_MAPS
+            .read()
+            .unwrap()
+            .event_to_action(event)
+            .or_else(|| DEFAULT_MAPS.event_to_action(event))
+        {
             Some(match action {
+                Action::Delete { .. }
+                | Action::ReplaceMode { .. }
+                | Action::SplitMode
+                | Action::CollapseMode { .. }
+                | Action::SubstituteMode { .. }
+                    if buffer.data.is_empty() =>
+                {
+                    ModeTransition::new_mode_and_info(Normal::new(), EMPTY_BUFFER_INFO.to_owned())
+                }
                 Action::JumpToMode => match self.count_state {
-                    cmd_count::State::None => {
-                        ModeTransition::new_mode(modes::jumpto::JumpTo { extend: false })
-                    }
+                    cmd_count::State::None => ModeTransition::new_mode(modes::jumpto::JumpTo {
+                        extend: false,
+                        count_state: cmd_count::State::None,
+                    }),
                     cmd_count::State::Some { count: offset, .. } => {
                         ModeTransition::new_mode_and_dirty(
                             Normal::new(),
@@ -135,9 +287,10 @@ impl Mode for Normal {
                     }
                 },
                 Action::ExtendToMode => match self.count_state {
-                    cmd_count::State::None => {
-                        ModeTransition::new_mode(modes::jumpto::JumpTo { extend: true })
-                    }
+                    cmd_count::State::None => ModeTransition::new_mode(modes::jumpto::JumpTo {
+                        extend: true,
+                        count_state: cmd_count::State::None,
+                    }),
                     cmd_count::State::Some { count: offset, .. } => {
                         ModeTransition::new_mode_and_dirty(
                             Normal::new(),
@@ -145,7 +298,14 @@ impl Mode for Normal {
                         )
                     }
                 },
+                Action::BracketMode { forward } => {
+                    ModeTransition::new_mode(modes::bracket::Bracket {
+                        forward,
+                        count_state: self.count_state,
+                    })
+                }
                 Action::SplitMode => ModeTransition::new_mode(modes::split::Split::new()),
+                Action::SelectRun => ModeTransition::new_mode(modes::run::SelectRun()),
                 Action::Insert { hex } => ModeTransition::new_mode_and_dirty(
                     modes::insert::Insert {
                         hex,
@@ -181,8 +341,19 @@ impl Mode for Normal {
                     mode: InsertionMode::Overwrite,
                     hex_half: None,
                 }),
+                Action::TextEntry { overwrite: false } => ModeTransition::new_mode_and_dirty(
+                    modes::text::TextEntry {
+                        mode: InsertionMode::Insert,
+                    },
+                    buffer.map_selections(|region| vec![region.to_backward()]),
+                ),
+                Action::TextEntry { overwrite: true } => {
+                    ModeTransition::new_mode(modes::text::TextEntry {
+                        mode: InsertionMode::Overwrite,
+                    })
+                }
                 Action::Move(direction) => {
-                    let max_bytes = buffer.data.len();
+                    let max_bytes = options.motion_limit(buffer.data.len());
                     ModeTransition::new_mode_and_dirty(
                         Normal::new(),
                         buffer.map_selections(|region| {
@@ -196,7 +367,7 @@ impl Mode for Normal {
                     )
                 }
                 Action::Extend(direction) => {
-                    let max_bytes = buffer.data.len();
+                    let max_bytes = options.motion_limit(buffer.data.len());
                     ModeTransition::new_mode_and_dirty(
                         Normal::new(),
                         buffer.map_selections(|region| {
@@ -216,47 +387,42 @@ impl Mode for Normal {
                     buffer.map_selections(|region| vec![region.collapse()]),
                 ),
                 Action::Delete { register } => {
-                    buffer.yank_selections(register);
-                    if !buffer.data.is_empty() {
-                        let delta = ops::deletion(&buffer.data, &buffer.selection);
-                        ModeTransition::DirtyBytes(buffer.apply_delta(delta))
-                    } else {
-                        ModeTransition::None
-                    }
+                    ModeTransition::DirtyBytes(delete_selections(buffer, register, options))
                 }
                 Action::Change { hex, register } => {
-                    buffer.yank_selections(register);
-                    if !buffer.data.is_empty() {
-                        let delta = ops::deletion(&buffer.data, &buffer.selection);
-                        ModeTransition::new_mode_and_dirty(
-                            modes::insert::Insert {
-                                hex,
-                                mode: InsertionMode::Insert,
-                                hex_half: None,
-                            },
-                            buffer.apply_delta(delta),
-                        )
-                    } else {
-                        ModeTransition::new_mode(modes::insert::Insert {
-                            hex,
-                            mode: InsertionMode::Insert,
-                            hex_half: None,
-                        })
-                    }
+                    change_selections(buffer, hex, register, options)
+                }
+                Action::OperatorMode(operator) => {
+                    ModeTransition::new_mode(modes::operator::OperatorPending::new(operator))
                 }
                 Action::Yank { register } => {
-                    buffer.yank_selections(register);
+                    buffer.yank_selections(register, options);
                     ModeTransition::None
                 }
                 Action::Paste { register, after } => {
+                    let (count, info) = self
+                        .count_state
+                        .to_count_capped(options.count_limit(buffer.data.len()));
                     let delta = ops::paste(
                         &buffer.data,
                         &buffer.selection,
                         buffer.registers.get(&register).unwrap_or(&vec![vec![]]),
                         after,
-                        self.count_state.to_count(),
+                        count,
                     );
-                    ModeTransition::DirtyBytes(buffer.apply_delta(delta))
+                    let dirty = buffer.apply_delta(delta);
+                    match info {
+                        Some(info) => {
+                            ModeTransition::new_mode_dirty_and_info(Normal::new(), dirty, info)
+                        }
+                        None => ModeTransition::DirtyBytes(dirty),
+                    }
+                }
+                Action::RemoveMain if buffer.selection.len() == 1 => {
+                    ModeTransition::new_mode_and_info(
+                        Normal::new(),
+                        "can't remove the only selection".to_owned(),
+                    )
                 }
                 // selection indexing in the UI starts at 1
                 // hence we check for count > 0 and offset by -1
@@ -284,14 +450,21 @@ impl Mode for Normal {
                 },
 
                 // new_mode to clear count
-                Action::SelectNext => ModeTransition::new_mode_and_dirty(
-                    Normal::new(),
-                    buffer.select_next(self.count_state.to_count()),
-                ),
-                Action::SelectPrev => ModeTransition::new_mode_and_dirty(
-                    Normal::new(),
-                    buffer.select_prev(self.count_state.to_count()),
-                ),
+                Action::SelectNext => {
+                    let count = self.count_state.to_count();
+                    let selection = &buffer.selection;
+                    let wrapped =
+                        selection.len() > 1 && selection.main_selection + count >= selection.len();
+                    ModeTransition::new_mode_and_dirty(Normal::new(), buffer.select_next(count))
+                        .with_info(wrapped.then(|| "wrapped past the last selection".to_owned()))
+                }
+                Action::SelectPrev => {
+                    let count = self.count_state.to_count();
+                    let selection = &buffer.selection;
+                    let wrapped = selection.len() > 1 && count > selection.main_selection;
+                    ModeTransition::new_mode_and_dirty(Normal::new(), buffer.select_prev(count))
+                        .with_info(wrapped.then(|| "wrapped past the first selection".to_owned()))
+                }
                 Action::SelectAll => {
                     buffer.selection.select_all(buffer.data.len());
                     ModeTransition::DirtyBytes(DirtyBytes::ChangeInPlace(vec![(0..buffer
@@ -299,18 +472,77 @@ impl Mode for Normal {
                         .len())
                         .into()]))
                 }
+                Action::SelectRows => {
+                    let max_bytes = buffer.data.len();
+                    ModeTransition::new_mode_and_dirty(
+                        Normal::new(),
+                        buffer.map_selections(|region| {
+                            vec![region.select_rows(
+                                bytes_per_line,
+                                max_bytes,
+                                self.count_state.to_count(),
+                            )]
+                        }),
+                    )
+                }
                 Action::CollapseMode { hex } => ModeTransition::new_mode(
                     modes::search::Search::new(modes::collapse::Collapse(), hex),
                 ),
-                Action::Measure => ModeTransition::new_mode_and_info(
-                    Normal::new(),
-                    format!(
-                        "{} = 0x{:x} bytes",
-                        buffer.selection.main().len(),
-                        buffer.selection.main().len()
+                Action::SubstituteMode { hex } => {
+                    let limit = match self.count_state {
+                        cmd_count::State::None => None,
+                        count_state => Some(count_state.to_count()),
+                    };
+                    ModeTransition::new_mode(modes::search::Search::new(
+                        modes::substitute::Substitute { hex, limit },
+                        hex,
+                    ))
+                }
+                Action::Measure { all } => {
+                    let count = if all {
+                        buffer.selection.len()
+                    } else {
+                        cmp::min(self.count_state.to_count(), buffer.selection.len())
+                    };
+                    let len = buffer.selection.len_bytes_from_main(count);
+                    ModeTransition::new_mode_and_info(
+                        Normal::new(),
+                        if count == 1 {
+                            format!("{} = 0x{:x} bytes", len, len)
+                        } else {
+                            format!("{} = 0x{:x} bytes in {} selections", len, len, count)
+                        },
+                    )
+                }
+                Action::SearchSelection { forward } => {
+                    let main = buffer.selection.main();
+                    if buffer.data.len() <= main.max() {
+                        return Some(ModeTransition::new_mode_and_info(
+                            Normal::new(),
+                            "nothing selected to search for".to_owned(),
+                        ));
+                    }
+                    let pattern =
+                        Pattern::from_bytes(&buffer.data.slice_to_cow(main.min()..=main.max()));
+                    buffers.last_pattern = Some(pattern);
+                    jump_to_match(buffers, forward, options.search_align)
+                }
+                Action::SearchNext { forward } => {
+                    jump_to_match(buffers, forward, options.search_align)
+                }
+                Action::VisualMode { linewise } => {
+                    modes::visual::Visual::enter(buffers, options, linewise)
+                }
+                Action::AlternateBuffer => switch_to_alternate_buffer(buffers),
+                Action::JumpBack => match buffer.jump_back() {
+                    Some(dirty) => ModeTransition::new_mode_and_dirty(Normal::new(), dirty),
+                    None => ModeTransition::new_mode_and_info(
+                        Normal::new(),
+                        "no earlier position".to_owned(),
                     ),
-                ),
+                },
                 Action::CommandMode => ModeTransition::new_mode(modes::command::Command::new()),
+                Action::ReplayMode => ModeTransition::new_mode(modes::replay::Replay()),
                 Action::Undo => buffer.perform_undo().map_or_else(
                     || {
                         ModeTransition::new_mode_and_info(
@@ -329,6 +561,9 @@ impl Mode for Normal {
                     },
                     |dirty| ModeTransition::new_mode_and_dirty(Normal::new(), dirty),
                 ),
+                Action::RunCommand(line) => {
+                    modes::command::run_command_line(buffers, options, &line)
+                }
             })
         } else {
             None
@@ -345,4 +580,184 @@ impl Normal {
             count_state: cmd_count::State::None,
         }
     }
+
+    pub fn with_count(count_state: cmd_count::State) -> Normal {
+        Normal { count_state }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::{
+        collapse::Collapse,
+        search::{Pattern, PatternPiece, SearchAcceptor},
+        split::Split,
+    };
+
+    fn press(buffers: &mut Buffers, code: KeyCode, modifiers: KeyModifiers) -> ModeTransition {
+        Normal::new()
+            .transition(
+                &Event::Key(KeyEvent::new(code, modifiers)),
+                buffers,
+                &mut ViewOptions::new(),
+            )
+            .expect("key should be handled")
+    }
+
+    fn info(transition: ModeTransition) -> Option<String> {
+        match transition {
+            ModeTransition::ModeAndInfo(_, info) => Some(info),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_motions_without_append_cursor() {
+        let mut buffers =
+            Buffers::with_buffer(Buffer::from_data_and_path(vec![0; 4], None::<&str>));
+        let mut options = ViewOptions::new();
+        options.set("append-cursor", "off").unwrap();
+        for ch in ['j', 'l', 'l', 'l', 'l'] {
+            let evt = Event::Key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+            Normal::new().transition(&evt, &mut buffers, &mut options);
+        }
+        assert_eq!(buffers.current().selection.main_cursor_offset(), 3);
+    }
+
+    #[test]
+    fn test_empty_buffer_refuses_edits() {
+        let mut buffers = Buffers::new();
+        for (code, modifiers) in [
+            (KeyCode::Char('d'), KeyModifiers::NONE),
+            (KeyCode::Char('r'), KeyModifiers::NONE),
+            (KeyCode::Char('R'), KeyModifiers::NONE),
+            (KeyCode::Char('s'), KeyModifiers::ALT),
+            (KeyCode::Char('s'), KeyModifiers::NONE),
+            (KeyCode::Char('S'), KeyModifiers::NONE),
+        ] {
+            let transition = press(&mut buffers, code, modifiers);
+            assert_eq!(info(transition).as_deref(), Some(EMPTY_BUFFER_INFO));
+        }
+        assert!(buffers.current().data.is_empty());
+    }
+
+    #[test]
+    fn test_empty_buffer_selection_ops() {
+        let mut buffers = Buffers::new();
+        for ch in [
+            'h', 'j', 'k', 'l', 'H', 'L', 'X', '%', ';', 'y', 'M', 'u', 'U', ')',
+        ] {
+            press(&mut buffers, KeyCode::Char(ch), KeyModifiers::NONE);
+        }
+        assert!(buffers.current().data.is_empty());
+        let main = buffers.current().selection.main();
+        assert_eq!((main.caret, main.tail), (0, 0));
+
+        press(&mut buffers, KeyCode::Char('p'), KeyModifiers::NONE);
+        assert!(buffers.current().data.is_empty());
+    }
+
+    #[test]
+    fn test_no_match_keeps_selection() {
+        let mut buffer = Buffer::from_data_and_path(b"abcabc".to_vec(), None::<&str>);
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(0, 2), SelRegion::new(5, 3)]);
+        let before = buffer.selection.clone();
+        let mut buffers = Buffers::with_buffer(buffer);
+        let mut options = ViewOptions::new();
+
+        let pattern = Pattern::from_bytes(b"x");
+        let transition = Collapse().apply_search(pattern.clone(), &mut buffers, &mut options);
+        assert_eq!(info(transition).as_deref(), Some(NO_MATCHES_INFO));
+        assert_eq!(buffers.current().selection, before);
+        let transition = Split::new().apply_search(pattern, &mut buffers, &mut options);
+        assert_eq!(info(transition).as_deref(), Some(NO_MATCHES_INFO));
+        assert_eq!(buffers.current().selection, before);
+    }
+
+    #[test]
+    fn test_empty_buffer_search() {
+        let mut buffers = Buffers::new();
+        let pattern = Pattern {
+            pieces: vec![PatternPiece::Literal(0)],
+        };
+        assert_eq!(
+            pattern
+                .map_selections_to_matches(buffers.current(), false, 1)
+                .unwrap(),
+            vec![vec![]]
+        );
+        let wildcard = Pattern {
+            pieces: vec![PatternPiece::Wildcard],
+        };
+        assert_eq!(
+            wildcard
+                .map_selections_to_matches(buffers.current(), false, 1)
+                .unwrap(),
+            vec![vec![]]
+        );
+
+        let mut options = ViewOptions::new();
+        let transition = Split::new().apply_search(pattern.clone(), &mut buffers, &mut options);
+        assert_eq!(info(transition).as_deref(), Some(EMPTY_BUFFER_INFO));
+        let transition = Collapse().apply_search(pattern, &mut buffers, &mut options);
+        assert_eq!(info(transition).as_deref(), Some(EMPTY_BUFFER_INFO));
+    }
+
+    #[test]
+    fn test_bound_command() {
+        let mut buffers =
+            Buffers::with_buffer(Buffer::from_data_and_path(vec![0; 4], None::<&str>));
+        assert_eq!(register_binding("<f12>", "insert ab"), Ok(None));
+        press(&mut buffers, KeyCode::F(12), KeyModifiers::NONE);
+        assert_eq!(
+            buffers.current().data.slice_to_cow(..),
+            &[0xab, 0, 0, 0, 0][..]
+        );
+    }
+
+    #[test]
+    fn test_selection_ring_wraps() {
+        let mut buffer = Buffer::from_data_and_path(vec![0; 8], None::<&str>);
+        buffer.selection.map_selections(|_| {
+            vec![
+                SelRegion::new(0, 0),
+                SelRegion::new(2, 2),
+                SelRegion::new(4, 4),
+            ]
+        });
+        let mut buffers = Buffers::with_buffer(buffer);
+        let mut next = || {
+            press(&mut buffers, KeyCode::Char(')'), KeyModifiers::NONE)
+                .info()
+                .map(str::to_owned)
+        };
+        assert_eq!(next(), None);
+        assert_eq!(next(), None);
+        assert_eq!(next().as_deref(), Some("wrapped past the last selection"));
+
+        let transition = press(&mut buffers, KeyCode::Char('('), KeyModifiers::NONE);
+        assert_eq!(transition.info(), Some("wrapped past the first selection"));
+        assert_eq!(buffers.current().selection.main_selection, 2);
+    }
+
+    #[test]
+    fn test_paste_at_end_cursor() {
+        for code in ['P', 'p'] {
+            let mut buffer = Buffer::from_data_and_path(b"ab".to_vec(), None::<&str>);
+            buffer
+                .selection
+                .map_selections(|_| vec![SelRegion::new(2, 2)]);
+            buffer.registers.insert('"', vec![b"xyz".to_vec()]);
+            let mut buffers = Buffers::with_buffer(buffer);
+            press(&mut buffers, KeyCode::Char(code), KeyModifiers::NONE);
+            let main = buffers.current().selection.main();
+            assert_eq!(buffers.current().data.slice_to_cow(..), &b"abxyz"[..]);
+            // Like a cursor on a byte stays on that byte, the cursor stays past the end,
+            // after the pasted bytes
+            assert_eq!((main.caret, main.tail), (5, 5), "{}", code);
+        }
+    }
 }
@@ -0,0 +1,206 @@
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::HashMap;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use lazy_static::lazy_static;
+
+use crate::keymap::KeyMap;
+use crate::modes::{
+    mode::{Mode, ModeTransition},
+    normal::{self, Normal, EMPTY_BUFFER_INFO},
+};
+use crate::selection::{Direction, SelRegion};
+use crate::{cmd_count, Buffers, ViewOptions};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Operator {
+    Delete,
+    Yank,
+    Change { hex: bool },
+}
+
+// Waits for a count and a motion after an operator key, like `d3l` in vim, and applies the
+// operator to the bytes the motion covers
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct OperatorPending {
+    pub operator: Operator,
+    pub count_state: cmd_count::State,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Action {
+    Motion(Direction),
+}
+
+fn default_maps() -> KeyMap<Action> {
+    KeyMap {
+        maps: keys!(
+            ('h' => Action::Motion(Direction::Left)),
+            (key KeyCode::Left => Action::Motion(Direction::Left)),
+            ('j' => Action::Motion(Direction::Down)),
+            (key KeyCode::Down => Action::Motion(Direction::Down)),
+            ('k' => Action::Motion(Direction::Up)),
+            (key KeyCode::Up => Action::Motion(Direction::Up)),
+            ('l' => Action::Motion(Direction::Right)),
+            (key KeyCode::Right => Action::Motion(Direction::Right))
+        ),
+    }
+}
+
+lazy_static! {
+    static ref DEFAULT_MAPS: KeyMap<Action> = default_maps();
+}
+
+// Left and right cover `count` bytes ending or starting at the caret. Up and down cover the
+// caret's row and `count` more rows in that direction.
+fn motion_region(
+    region: SelRegion,
+    direction: Direction,
+    bytes_per_line: usize,
+    max_size: usize,
+    count: usize,
+) -> SelRegion {
+    // The overflow cursor operates on the last byte
+    let caret = cmp::min(region.caret, max_size - 1);
+    let cursor = SelRegion::new(caret, caret);
+    match direction {
+        Direction::Left | Direction::Right => {
+            cursor.simple_extend(direction, bytes_per_line, max_size - 1, count - 1)
+        }
+        Direction::Up => cursor
+            .simple_move(Direction::Up, bytes_per_line, max_size, count)
+            .select_rows(bytes_per_line, max_size, count + 1),
+        Direction::Down => cursor.select_rows(bytes_per_line, max_size, count + 1),
+    }
+}
+
+impl Mode for OperatorPending {
+    fn name(&self) -> Cow<'static, str> {
+        let operator = match self.operator {
+            Operator::Delete => "DELETE",
+            Operator::Yank => "YANK",
+            Operator::Change { hex: false } => "CHANGE",
+            Operator::Change { hex: true } => "CHANGE (hex)",
+        };
+        format!("{}{}", operator, self.count_state).into()
+    }
+
+    fn transition(
+        &self,
+        evt: &Event,
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+    ) -> Option<ModeTransition> {
+        let buffer = buffers.current_mut();
+        if let cmd_count::Transition::Update(new_state) = self.count_state.transition(evt) {
+            Some(ModeTransition::new_mode(OperatorPending {
+                count_state: new_state,
+                ..*self
+            }))
+        } else if let Some(action) = DEFAULT_MAPS.event_to_action(evt) {
+            if buffer.data.is_empty() {
+                return Some(ModeTransition::new_mode_and_info(
+                    Normal::new(),
+                    EMPTY_BUFFER_INFO.to_owned(),
+                ));
+            }
+            let Action::Motion(direction) = action;
+            let max_size = buffer.data.len();
+            let (count, info) = self
+                .count_state
+                .to_count_capped(options.count_limit(max_size));
+            let count = cmp::max(count, 1);
+            let selection_dirty = buffer.map_selections(|region| {
+                vec![motion_region(
+                    region,
+                    direction,
+                    options.bytes_per_line,
+                    max_size,
+                    count,
+                )]
+            });
+            Some(
+                match self.operator {
+                    Operator::Delete => ModeTransition::new_mode_and_dirty(
+                        Normal::new(),
+                        normal::delete_selections(buffer, '"', options),
+                    ),
+                    Operator::Yank => {
+                        buffer.yank_selections('"', options);
+                        ModeTransition::new_mode_and_dirty(Normal::new(), selection_dirty)
+                    }
+                    Operator::Change { hex } => {
+                        normal::change_selections(buffer, hex, '"', options)
+                    }
+                }
+                .with_info(info),
+            )
+        } else if let Event::Key(_) = evt {
+            Some(ModeTransition::new_mode(Normal::new()))
+        } else {
+            None
+        }
+    }
+
+    fn is_prefix(&self) -> bool {
+        true
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl OperatorPending {
+    pub fn new(operator: Operator) -> OperatorPending {
+        OperatorPending {
+            operator,
+            count_state: cmd_count::State::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    fn operate(operator_key: char, keys: &str) -> Buffers {
+        let mut buffer = Buffer::from_data_and_path((0..0x20).collect(), None::<&str>);
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(0x12, 0x12)]);
+        let mut buffers = Buffers::with_buffer(buffer);
+        let mut options = ViewOptions::new();
+
+        let mut mode: Box<dyn Mode> = Box::new(Normal::new());
+        let operator_evt = KeyEvent::new(KeyCode::Char(operator_key), KeyModifiers::ALT);
+        let key_evts = keys
+            .chars()
+            .map(|ch| KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+        for evt in std::iter::once(operator_evt).chain(key_evts) {
+            match mode.transition(&Event::Key(evt), &mut buffers, &mut options) {
+                Some(ModeTransition::NewMode(next))
+                | Some(ModeTransition::ModeAndDirtyBytes(next, _)) => mode = next,
+                _ => panic!("unexpected transition"),
+            }
+        }
+        buffers
+    }
+
+    #[test]
+    fn test_count_after_operator() {
+        let buffers = operate('d', "3l");
+        let buffer = buffers.current();
+        assert_eq!(buffer.data.len(), 0x1d);
+        assert_eq!(buffer.registers[&'"'], vec![vec![0x12, 0x13, 0x14]]);
+
+        // The caret's row, and the one below which doesn't exist
+        let buffers = operate('d', "j");
+        assert_eq!(buffers.current().data.len(), 0x10);
+
+        let buffers = operate('y', "k");
+        assert_eq!(buffers.current().registers[&'"'][0].len(), 0x20);
+    }
+}
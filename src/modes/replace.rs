@@ -7,10 +7,10 @@ use lazy_static::lazy_static;
 use crate::keymap::KeyMap;
 use crate::modes::{
     mode::{Mode, ModeTransition},
-    normal::Normal,
+    normal::{self, Normal},
 };
 use crate::operations as ops;
-use crate::Buffers;
+use crate::{Buffers, ViewOptions};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Replace {
@@ -44,7 +44,12 @@ impl Mode for Replace {
         }
     }
 
-    fn transition(&self, evt: &Event, buffers: &mut Buffers, _: usize) -> Option<ModeTransition> {
+    fn transition(
+        &self,
+        evt: &Event,
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+    ) -> Option<ModeTransition> {
         let buffer = buffers.current_mut();
         if let Event::Key(KeyEvent {
             code: KeyCode::Char(ch),
@@ -54,11 +59,9 @@ impl Mode for Replace {
             if let Some(action) = DEFAULT_MAPS.event_to_action(evt) {
                 return match action {
                     Action::Null => {
-                        let delta = ops::replace(&buffer.data, &buffer.selection, 0);
-                        Some(ModeTransition::new_mode_and_dirty(
-                            Normal::new(),
-                            buffer.apply_delta(delta),
-                        ))
+                        let delta =
+                            ops::replace(&buffer.data, &buffer.selection, options.fill_byte);
+                        Some(normal::apply_replacement(buffer, delta))
                     }
                 };
             }
@@ -69,10 +72,7 @@ impl Mode for Replace {
 
             if !self.hex {
                 let delta = ops::replace(&buffer.data, &buffer.selection, *ch as u8); // lossy!
-                Some(ModeTransition::new_mode_and_dirty(
-                    Normal::new(),
-                    buffer.apply_delta(delta),
-                ))
+                Some(normal::apply_replacement(buffer, delta))
             } else if self.hex_half.is_none() {
                 if !ch.is_ascii_hexdigit() {
                     return Some(ModeTransition::new_mode(Normal::new()));
@@ -90,10 +90,7 @@ impl Mode for Replace {
 
                 let replacing_ch = (ch.to_digit(16).unwrap() as u8) | self.hex_half.unwrap();
                 let delta = ops::replace(&buffer.data, &buffer.selection, replacing_ch); // lossy!
-                Some(ModeTransition::new_mode_and_dirty(
-                    Normal::new(),
-                    buffer.apply_delta(delta),
-                ))
+                Some(normal::apply_replacement(buffer, delta))
             }
         } else if let Event::Key(_) = evt {
             Some(ModeTransition::new_mode(Normal::new()))
@@ -102,6 +99,10 @@ impl Mode for Replace {
         }
     }
 
+    fn caret_in_hex(&self) -> bool {
+        self.hex
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
@@ -0,0 +1,110 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use lazy_static::lazy_static;
+
+use crate::keymap::KeyMap;
+use crate::modes::{
+    command,
+    mode::{Mode, ModeTransition},
+    normal::Normal,
+};
+use crate::{Buffers, ViewOptions};
+
+// Waits for the key after `@`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Replay();
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Action {
+    LastCommand,
+}
+
+fn default_maps() -> KeyMap<Action> {
+    KeyMap {
+        maps: keys!(
+            (':' => Action::LastCommand)
+        ),
+    }
+}
+
+lazy_static! {
+    static ref DEFAULT_MAPS: KeyMap<Action> = default_maps();
+}
+
+impl Mode for Replay {
+    fn name(&self) -> Cow<'static, str> {
+        "REPLAY".into()
+    }
+
+    fn transition(
+        &self,
+        evt: &Event,
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+    ) -> Option<ModeTransition> {
+        if let Some(action) = DEFAULT_MAPS.event_to_action(evt) {
+            Some(match action {
+                Action::LastCommand => match buffers.last_command.clone() {
+                    Some(line) => command::run_command_line(buffers, options, &line),
+                    None => ModeTransition::new_mode_and_info(
+                        Normal::new(),
+                        "no command to repeat".to_owned(),
+                    ),
+                },
+            })
+        } else if let Event::Key(_) = evt {
+            Some(ModeTransition::new_mode(Normal::new()))
+        } else {
+            None
+        }
+    }
+
+    fn is_prefix(&self) -> bool {
+        true
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::Buffer;
+    use crate::modes::command::Command;
+
+    fn press(mode: &dyn Mode, buffers: &mut Buffers, ch: char) -> ModeTransition {
+        mode.transition(
+            &Event::Key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE)),
+            buffers,
+            &mut ViewOptions::new(),
+        )
+        .expect("key should be handled")
+    }
+
+    #[test]
+    fn test_repeat_last_command() {
+        let mut buffers =
+            Buffers::with_buffer(Buffer::from_data_and_path(vec![0; 4], None::<&str>));
+        let transition = press(&Replay(), &mut buffers, ':');
+        assert_eq!(transition.info(), Some("no command to repeat"));
+
+        let command = Command {
+            command: "insert ab".to_owned(),
+            cursor: 9,
+        };
+        command.transition(
+            &Event::Key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)),
+            &mut buffers,
+            &mut ViewOptions::new(),
+        );
+        press(&Replay(), &mut buffers, ':');
+        assert_eq!(
+            buffers.current().data.slice_to_cow(..),
+            &[0xab, 0xab, 0, 0, 0, 0][..]
+        );
+    }
+}
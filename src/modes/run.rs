@@ -0,0 +1,145 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use lazy_static::lazy_static;
+
+use crate::cancel::Cancelled;
+use crate::encoding::ByteClass;
+use crate::keymap::KeyMap;
+use crate::modes::{
+    mode::{Mode, ModeTransition},
+    normal::{Normal, EMPTY_BUFFER_INFO},
+};
+use crate::selection::SelRegion;
+use crate::{Buffers, ViewOptions};
+
+// Selects the run of bytes of a class under (or after) each cursor, picked by the next key
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SelectRun();
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Action {
+    // Printable characters and whitespace, like a string
+    Text,
+    Class(ByteClass),
+}
+
+fn default_maps() -> KeyMap<Action> {
+    KeyMap {
+        maps: keys!(
+            ('p' => Action::Text),
+            ('w' => Action::Class(ByteClass::Whitespace)),
+            ('z' => Action::Class(ByteClass::Null)),
+            ('c' => Action::Class(ByteClass::Control)),
+            ('n' => Action::Class(ByteClass::NonAscii))
+        ),
+    }
+}
+
+lazy_static! {
+    static ref DEFAULT_MAPS: KeyMap<Action> = default_maps();
+}
+
+impl Action {
+    fn matches(self, class: ByteClass) -> bool {
+        match self {
+            Action::Text => matches!(class, ByteClass::Printable | ByteClass::Whitespace),
+            Action::Class(wanted) => class == wanted,
+        }
+    }
+}
+
+impl Mode for SelectRun {
+    fn name(&self) -> Cow<'static, str> {
+        "RUN".into()
+    }
+
+    fn transition(
+        &self,
+        evt: &Event,
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+    ) -> Option<ModeTransition> {
+        let buffer = buffers.current_mut();
+        if let Some(action) = DEFAULT_MAPS.event_to_action(evt) {
+            if buffer.data.is_empty() {
+                return Some(ModeTransition::new_mode_and_info(
+                    Normal::new(),
+                    EMPTY_BUFFER_INFO.to_owned(),
+                ));
+            }
+            let ascii_class = options.ascii_class;
+            let pred = |byte| action.matches(ascii_class.classify(byte));
+            let runs = match buffer
+                .selection
+                .iter()
+                .map(|region| buffer.find_run(region.caret, pred))
+                .collect::<Result<Vec<_>, Cancelled>>()
+            {
+                Ok(runs) => runs,
+                Err(_) => {
+                    return Some(ModeTransition::new_mode_and_info(
+                        Normal::new(),
+                        Cancelled::info(),
+                    ))
+                }
+            };
+            if runs.iter().all(Option::is_none) {
+                return Some(ModeTransition::new_mode_and_info(
+                    Normal::new(),
+                    "no run found".to_owned(),
+                ));
+            }
+            // Cursors without a run after them stay where they are
+            let mut runs = runs.into_iter();
+            Some(ModeTransition::new_mode_and_dirty(
+                Normal::new(),
+                buffer.map_selections(|region| {
+                    vec![match runs.next().flatten() {
+                        Some(run) => SelRegion::new(run.end - 1, run.start),
+                        None => region,
+                    }]
+                }),
+            ))
+        } else if let Event::Key(_) = evt {
+            Some(ModeTransition::new_mode(Normal::new()))
+        } else {
+            None
+        }
+    }
+
+    fn is_prefix(&self) -> bool {
+        true
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    #[test]
+    fn test_select_run() {
+        let mut buffer = Buffer::from_data_and_path(b"\0\0ab c\n\x01\0xyz".to_vec(), None::<&str>);
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(0, 0), SelRegion::new(4, 4)]);
+        let mut buffers = Buffers::with_buffer(buffer);
+        let evt = Event::Key(KeyEvent::new(KeyCode::Char('p'), KeyModifiers::NONE));
+        SelectRun().transition(&evt, &mut buffers, &mut ViewOptions::new());
+        // The first cursor isn't in a run, so it selects the next one, which the second cursor
+        // is already in
+        let selected = buffers
+            .current()
+            .selection
+            .iter()
+            .map(|region| (region.min(), region.max()))
+            .collect::<Vec<_>>();
+        assert_eq!(selected, vec![(2, 6)]);
+    }
+}
@@ -1,18 +1,20 @@
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use jetscii::ByteSubstring;
 use lazy_static::lazy_static;
-use regex::bytes::RegexBuilder;
+use regex::bytes::{Regex, RegexBuilder};
 use std::borrow::Cow;
 use std::cell::RefCell;
+use std::cmp;
 use std::collections::HashMap;
 use std::ops::Range;
 
+use crate::cancel::{CancelCheck, Cancelled};
 use crate::keymap::KeyMap;
 use crate::modes::{
     mode::{Mode, ModeTransition},
     normal::Normal,
 };
-use crate::{Buffer, Buffers};
+use crate::{Buffer, Buffers, ViewOptions};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum PatternPiece {
@@ -61,63 +63,287 @@ impl Pattern {
             .collect::<Option<Vec<_>>>()
     }
 
-    pub fn map_selections_to_matches(&self, buffer: &Buffer) -> Vec<Vec<Range<usize>>> {
-        if let Some(basic_subslice) = self.as_basic_slice() {
-            buffer
-                .selection
-                .iter()
-                .map(|x| {
-                    let mut base = x.min();
-                    let mut matched_ranges = vec![];
-                    let byte_substring = ByteSubstring::new(&basic_subslice);
-
-                    let data = buffer.data.slice_to_cow(base..=x.max());
-                    let mut slice_base = 0;
-
-                    while let Some(start) = byte_substring.find(&data[slice_base..]) {
-                        let match_abs_start = base + start;
-                        matched_ranges
-                            .push(match_abs_start..match_abs_start + basic_subslice.len());
-                        base = match_abs_start + basic_subslice.len();
-                        slice_base = slice_base + start + basic_subslice.len();
+    // Spells each literal as its two hex digits, as if they had been typed in ascii mode.
+    // Only the high digit of the literal at `half` has been typed.
+    fn spelled_in_hex(&self, half: Option<usize>) -> Pattern {
+        let mut pieces = vec![];
+        for (i, piece) in self.pieces.iter().enumerate() {
+            match piece {
+                PatternPiece::Literal(byte) => {
+                    let digits = format!("{:02x}", byte);
+                    let typed = if half == Some(i) { 1 } else { 2 };
+                    pieces.extend(digits[..typed].bytes().map(PatternPiece::Literal));
+                }
+                PatternPiece::Wildcard => pieces.push(PatternPiece::Wildcard),
+            }
+        }
+        Pattern { pieces }
+    }
+
+    // Reads literal hex digits as the bytes they spell, undoing `spelled_in_hex`. Spaces
+    // between bytes are skipped.
+    fn parsed_as_hex(&self) -> Option<Pattern> {
+        let mut pieces = vec![];
+        let mut high = None;
+        for piece in &self.pieces {
+            match (piece, high) {
+                (PatternPiece::Literal(b' '), None) => {}
+                (PatternPiece::Literal(ch), _) => {
+                    let digit = (*ch as char).to_digit(16)? as u8;
+                    match high.take() {
+                        Some(high) => pieces.push(PatternPiece::Literal(high << 4 | digit)),
+                        None => high = Some(digit),
                     }
-                    matched_ranges
-                })
-                .collect::<Vec<_>>()
+                }
+                (PatternPiece::Wildcard, None) => pieces.push(PatternPiece::Wildcard),
+                (PatternPiece::Wildcard, Some(_)) => return None,
+            }
+        }
+        match high {
+            Some(_) => None,
+            None => Some(Pattern { pieces }),
+        }
+    }
+
+    // Reads the text as typed in ascii mode, backslash escapes included
+    pub fn from_escaped(text: &str) -> Pattern {
+        let mut bytes = vec![];
+        let mut escape: Option<Vec<u8>> = None;
+        for &byte in text.as_bytes() {
+            match escape.take() {
+                None if byte == b'\\' => escape = Some(vec![]),
+                None => bytes.push(byte),
+                Some(mut typed) => {
+                    typed.push(byte);
+                    match parse_escape(&typed) {
+                        Escape::Byte(escaped) => bytes.push(escaped),
+                        Escape::Incomplete => escape = Some(typed),
+                        Escape::Invalid => {
+                            bytes.push(b'\\');
+                            bytes.extend(typed);
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(typed) = escape {
+            bytes.push(b'\\');
+            bytes.extend(typed);
+        }
+        Pattern::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Pattern {
+        Pattern {
+            pieces: bytes.iter().copied().map(PatternPiece::Literal).collect(),
+        }
+    }
+
+    fn matcher(&self) -> Matcher {
+        let len = self.pieces.len();
+        if let Some(basic_subslice) = self.as_basic_slice() {
+            return Matcher::Literal(basic_subslice);
+        }
+
+        let expr = self
+            .pieces
+            .iter()
+            .map(|x| match x {
+                PatternPiece::Wildcard => Cow::from("."),
+                PatternPiece::Literal(c) => Cow::from(format!("\\x{:02x}", c)),
+            })
+            .collect::<String>();
+        let mut builder = RegexBuilder::new(&expr);
+        builder.unicode(false);
+        Matcher::Regex(builder.build().expect("Failed to create pattern"), len)
+    }
+
+    // Finds the matches in each selected region separately. A match never extends past the
+    // region it starts in, so a pattern straddling two adjacent regions is not found.
+    // With `overlapping`, a match may start inside the previous one (`aa` matches `aaaa` three
+    // times instead of twice). Matches starting at an offset that isn't a multiple of `align`
+    // are discarded.
+    pub fn map_selections_to_matches(
+        &self,
+        buffer: &Buffer,
+        overlapping: bool,
+        align: usize,
+    ) -> Result<Vec<Vec<Range<usize>>>, Cancelled> {
+        // The overflow cursor (and the only cursor of an empty buffer) has no data under it
+        let len = buffer.data.len();
+        let matcher = self.matcher();
+        let mut cancel = CancelCheck::new();
+
+        buffer
+            .selection
+            .iter()
+            .map(|x| {
+                let range = cmp::min(x.min(), len)..cmp::min(x.max() + 1, len);
+                let base = range.start;
+                // An unaligned match could hide an aligned one overlapping it
+                let matches = matcher
+                    .find_all(
+                        &buffer.data.slice_to_cow(range),
+                        overlapping || align > 1,
+                        &mut cancel,
+                    )?
+                    .into_iter()
+                    .map(|r| base + r.start..base + r.end)
+                    .collect();
+                Ok(keep_aligned(matches, align, overlapping))
+            })
+            .collect()
+    }
+
+    // Finds the possibly overlapping matches in `data`, for drawing a few rows of it
+    pub fn matches_in(&self, data: &[u8]) -> Result<Vec<Range<usize>>, Cancelled> {
+        self.matcher().find_all(data, true, &mut CancelCheck::new())
+    }
+
+    // Finds the closest match starting after (or before) `from`, wrapping around the ends of
+    // the buffer. The boolean tells whether the search wrapped.
+    pub fn find_next(
+        &self,
+        buffer: &Buffer,
+        from: usize,
+        forward: bool,
+        align: usize,
+    ) -> Result<Option<(Range<usize>, bool)>, Cancelled> {
+        let matches = self.matcher().find_all(
+            &buffer.data.slice_to_cow(..),
+            true,
+            &mut CancelCheck::new(),
+        )?;
+        let matches = keep_aligned(matches, align, true);
+        let next = if forward {
+            matches.iter().find(|r| r.start > from)
         } else {
-            let expr = self
-                .pieces
-                .iter()
-                .map(|x| match x {
-                    PatternPiece::Wildcard => Cow::from("."),
-                    PatternPiece::Literal(c) => Cow::from(format!("\\x{:02x}", c)),
-                })
-                .collect::<String>();
-            let mut builder = RegexBuilder::new(&expr);
-            builder.unicode(false);
-            let matcher = builder.build().expect("Failed to create pattern");
-
-            buffer
-                .selection
-                .iter()
-                .map(|x| {
-                    matcher
-                        .find_iter(&buffer.data.slice_to_cow(x.min()..=x.max()))
-                        .map(|r| (x.min() + r.start())..(x.min() + r.end()))
-                        .collect::<Vec<_>>()
-                })
-                .collect::<Vec<_>>()
+            matches.iter().rev().find(|r| r.start < from)
+        };
+        Ok(match next {
+            Some(next) => Some((next.clone(), false)),
+            None if forward => matches.first().map(|r| (r.clone(), true)),
+            None => matches.last().map(|r| (r.clone(), true)),
+        })
+    }
+}
+
+// Drops the matches that don't start at a multiple of `align`. Without `overlapping`, the ones
+// starting inside an earlier kept match are dropped as well.
+fn keep_aligned(matches: Vec<Range<usize>>, align: usize, overlapping: bool) -> Vec<Range<usize>> {
+    if align <= 1 {
+        return matches;
+    }
+    let mut kept: Vec<Range<usize>> = vec![];
+    for r in matches {
+        let overlaps = kept.last().is_some_and(|last| r.start < last.end);
+        if r.start % align == 0 && (overlapping || !overlaps) {
+            kept.push(r);
         }
     }
+    kept
+}
+
+// Data is scanned in chunks so that the search can be cancelled
+const SEARCH_CHUNK_SIZE: usize = 0x100000;
+
+enum Matcher {
+    Literal(Vec<u8>),
+    // Patterns always have a fixed length
+    Regex(Regex, usize),
 }
 
-pub trait SearchAcceptor: Mode {
+impl Matcher {
+    fn len(&self) -> usize {
+        match self {
+            Matcher::Literal(bytes) => bytes.len(),
+            Matcher::Regex(_, len) => *len,
+        }
+    }
+
+    fn find_at(&self, data: &[u8], from: usize) -> Option<usize> {
+        match self {
+            Matcher::Literal(bytes) => ByteSubstring::new(bytes)
+                .find(&data[from..])
+                .map(|start| from + start),
+            Matcher::Regex(regex, _) => regex.find_at(data, from).map(|m| m.start()),
+        }
+    }
+
+    fn find_all(
+        &self,
+        data: &[u8],
+        overlapping: bool,
+        cancel: &mut CancelCheck,
+    ) -> Result<Vec<Range<usize>>, Cancelled> {
+        let len = self.len();
+        let step = if overlapping { 1 } else { len };
+        let mut matched_ranges = vec![];
+        let mut from = 0;
+        while from < data.len() {
+            cancel.check()?;
+            // Matches starting in this chunk may extend into the next one
+            let chunk_end = cmp::min(from.saturating_add(SEARCH_CHUNK_SIZE), data.len());
+            let window = &data[..cmp::min(chunk_end + len - 1, data.len())];
+            match self.find_at(window, from) {
+                Some(start) => {
+                    matched_ranges.push(start..start + len);
+                    from = start + step;
+                }
+                None => from = chunk_end,
+            }
+        }
+        Ok(matched_ranges)
+    }
+}
+
+enum Escape {
+    Byte(u8),
+    Incomplete,
+    Invalid,
+}
+
+// Reads what was typed after a backslash in ascii mode
+fn parse_escape(typed: &[u8]) -> Escape {
+    match typed {
+        b"n" => Escape::Byte(b'\n'),
+        b"t" => Escape::Byte(b'\t'),
+        b"r" => Escape::Byte(b'\r'),
+        b"0" => Escape::Byte(0),
+        b"\\" => Escape::Byte(b'\\'),
+        b"x" => Escape::Incomplete,
+        [b'x', digit] if digit.is_ascii_hexdigit() => Escape::Incomplete,
+        [b'x', high, low] => match ((*high as char).to_digit(16), (*low as char).to_digit(16)) {
+            (Some(high), Some(low)) => Escape::Byte((high << 4 | low) as u8),
+            _ => Escape::Invalid,
+        },
+        _ => Escape::Invalid,
+    }
+}
+
+// What a finished search prompt hands its pattern to. The prompt handles all the keys, so an
+// acceptor doesn't need to be a mode of its own.
+pub trait SearchAcceptor: 'static {
+    // The name shown for the prompt
+    fn name(&self) -> Cow<'static, str>;
+
     fn apply_search(
         &self,
         pattern: Pattern,
         buffers: &mut Buffers,
-        bytes_per_line: usize,
+        options: &mut ViewOptions,
     ) -> ModeTransition;
+
+    // The mode to go back to when the search is cancelled, which is the one it was started from
+    fn cancelled(&self) -> Box<dyn Mode> {
+        Box::new(Normal::new())
+    }
+
+    // Whether the typed bytes are searched for. Prompts that only reuse the search line, like the
+    // replacement of a substitution, neither repeat nor replace the last pattern.
+    fn is_search(&self) -> bool {
+        true
+    }
 }
 
 pub struct Search {
@@ -125,6 +351,9 @@ pub struct Search {
     pub cursor: usize,
     pub hex: bool,
     pub hex_half: Option<u8>,
+    // Where the backslash of an escape being typed in ascii mode is. The escape stays in the
+    // pattern as typed until it's complete.
+    pub escape_start: Option<usize>,
     pub next: RefCell<Option<Box<dyn SearchAcceptor>>>,
 }
 
@@ -137,6 +366,7 @@ enum Action {
     CursorLeft,
     CursorRight,
     SwitchInputMode,
+    Reinterpret,
     Finish,
     Cancel,
 }
@@ -151,6 +381,7 @@ fn default_maps() -> KeyMap<Action> {
             (key KeyCode::Left => Action::CursorLeft),
             (key KeyCode::Right => Action::CursorRight),
             (ctrl 'o' => Action::SwitchInputMode ),
+            (ctrl 'r' => Action::Reinterpret),
             (ctrl 'n' => Action::InsertNull),
             (ctrl 'w' => Action::InsertWilcard)
         ),
@@ -167,10 +398,19 @@ impl Search {
             next: RefCell::new(Some(Box::new(next))),
             hex,
             hex_half: None,
+            escape_start: None,
             cursor: 0,
             pattern: Pattern::default(),
         }
     }
+
+    // Whether the prompt takes a pattern to search for, rather than reusing the search line
+    pub fn is_search(&self) -> bool {
+        self.next
+            .borrow()
+            .as_ref()
+            .is_some_and(|next| next.is_search())
+    }
 }
 
 impl Mode for Search {
@@ -178,16 +418,21 @@ impl Mode for Search {
         self.next.borrow().as_ref().unwrap().name()
     }
 
+    fn mode_stack(&self) -> Vec<Cow<'static, str>> {
+        vec![self.name(), "SEARCH".into()]
+    }
+
     fn transition(
         &self,
         evt: &Event,
         buffers: &mut Buffers,
-        bytes_per_line: usize,
+        options: &mut ViewOptions,
     ) -> Option<ModeTransition> {
         if let Some(action) = DEFAULT_MAPS.event_to_action(evt) {
             let mut cursor = self.cursor;
             let mut pattern = self.pattern.to_owned();
             let mut hex = self.hex;
+            let mut info = None;
 
             if self.hex_half.is_some() {
                 // hex insertion in progress: leave it as-is and skip to the next char
@@ -216,22 +461,72 @@ impl Mode for Search {
                 Action::SwitchInputMode => {
                     hex = !hex;
                 }
-                Action::Cancel => return Some(ModeTransition::new_mode(Normal::new())),
-                Action::Finish => {
-                    return Some(self.next.borrow().as_ref().unwrap().apply_search(
-                        pattern,
-                        buffers,
-                        bytes_per_line,
+                // Retypes the pattern as if it had been entered in the other input mode
+                Action::Reinterpret if self.hex => {
+                    pattern = self
+                        .pattern
+                        .spelled_in_hex(self.hex_half.map(|_| self.cursor));
+                    cursor = pattern.pieces.len();
+                    hex = false;
+                }
+                Action::Reinterpret => match self.pattern.parsed_as_hex() {
+                    Some(parsed) => {
+                        pattern = parsed;
+                        cursor = pattern.pieces.len();
+                        hex = true;
+                    }
+                    None => info = Some("pattern isn't whole hex bytes".to_owned()),
+                },
+                Action::Cancel => {
+                    return Some(ModeTransition::NewMode(
+                        self.next.borrow().as_ref().unwrap().cancelled(),
                     ))
                 }
+                Action::Finish if !self.is_search() => {
+                    return Some(
+                        self.next
+                            .borrow()
+                            .as_ref()
+                            .unwrap()
+                            .apply_search(pattern, buffers, options),
+                    );
+                }
+                Action::Finish => {
+                    // Like in vim, an empty pattern repeats the last search
+                    let pattern = if pattern.pieces.is_empty() {
+                        match &buffers.last_pattern {
+                            Some(last_pattern) => last_pattern.clone(),
+                            None => {
+                                return Some(ModeTransition::new_mode_and_info(
+                                    Normal::new(),
+                                    "no previous search".to_owned(),
+                                ))
+                            }
+                        }
+                    } else {
+                        pattern
+                    };
+                    buffers.last_pattern = Some(pattern.clone());
+                    return Some(
+                        self.next
+                            .borrow()
+                            .as_ref()
+                            .unwrap()
+                            .apply_search(pattern, buffers, options),
+                    );
+                }
             }
-            Some(ModeTransition::new_mode(Search {
-                pattern,
-                cursor,
-                hex,
-                hex_half: None, // after any action that doesn't insert a hex half, the hex half should be reset
-                next: RefCell::new(self.next.replace(None)),
-            })) // The old state won't be valid after this
+            Some(
+                ModeTransition::new_mode(Search {
+                    pattern,
+                    cursor,
+                    hex,
+                    hex_half: None, // after any action that doesn't insert a hex half, the hex half should be reset
+                    escape_start: None,
+                    next: RefCell::new(self.next.replace(None)),
+                })
+                .with_info(info),
+            ) // The old state won't be valid after this
         } else if let Event::Key(KeyEvent {
             code: KeyCode::Char(ch),
             modifiers,
@@ -243,8 +538,34 @@ impl Mode for Search {
             let mut pattern = self.pattern.to_owned();
             let mut cursor = self.cursor;
             let mut hex_half = self.hex_half;
+            let mut escape_start = None;
             if !self.hex {
                 cursor = pattern.insert_literal(cursor, *ch as u8);
+                match self.escape_start {
+                    None if *ch == '\\' => escape_start = Some(cursor - 1),
+                    None => {}
+                    Some(start) => {
+                        // Only literals can have been typed since the backslash
+                        let typed = pattern.pieces[start + 1..cursor]
+                            .iter()
+                            .filter_map(|piece| match piece {
+                                PatternPiece::Literal(byte) => Some(*byte),
+                                PatternPiece::Wildcard => None,
+                            })
+                            .collect::<Vec<_>>();
+                        match parse_escape(&typed) {
+                            Escape::Byte(byte) => {
+                                pattern
+                                    .pieces
+                                    .splice(start..cursor, [PatternPiece::Literal(byte)]);
+                                cursor = start + 1;
+                            }
+                            Escape::Incomplete => escape_start = Some(start),
+                            // Left in the pattern as typed
+                            Escape::Invalid => {}
+                        }
+                    }
+                }
             } else {
                 if !ch.is_ascii_hexdigit() {
                     return None;
@@ -262,6 +583,7 @@ impl Mode for Search {
                 pattern,
                 cursor,
                 hex_half,
+                escape_start,
                 hex: self.hex,
                 next: RefCell::new(self.next.replace(None)),
             })) // The old state won't be valid after this
@@ -274,3 +596,197 @@ impl Mode for Search {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::SelRegion;
+
+    fn buffer_with_all_selected(data: &[u8]) -> Buffer {
+        let mut buffer = Buffer::from_data_and_path(data.to_vec(), None::<&str>);
+        buffer.selection.select_all(data.len());
+        buffer
+    }
+
+    #[test]
+    fn test_non_overlapping_matches() {
+        let buffer = buffer_with_all_selected(b"aaaa");
+        assert_eq!(
+            Pattern::from_bytes(b"aa")
+                .map_selections_to_matches(&buffer, false, 1)
+                .unwrap(),
+            vec![vec![0..2, 2..4]]
+        );
+    }
+
+    #[test]
+    fn test_overlapping_matches() {
+        let buffer = buffer_with_all_selected(b"aaaa");
+        assert_eq!(
+            Pattern::from_bytes(b"aa")
+                .map_selections_to_matches(&buffer, true, 1)
+                .unwrap(),
+            vec![vec![0..2, 1..3, 2..4]]
+        );
+
+        let wildcard = Pattern {
+            pieces: vec![PatternPiece::Literal(b'a'), PatternPiece::Wildcard],
+        };
+        assert_eq!(
+            wildcard
+                .map_selections_to_matches(&buffer, true, 1)
+                .unwrap(),
+            vec![vec![0..2, 1..3, 2..4]]
+        );
+        assert_eq!(
+            wildcard
+                .map_selections_to_matches(&buffer, false, 1)
+                .unwrap(),
+            vec![vec![0..2, 2..4]]
+        );
+    }
+
+    #[test]
+    fn test_aligned_matches() {
+        let buffer = buffer_with_all_selected(b"xaaaaaaa");
+        let pattern = Pattern::from_bytes(b"aa");
+        // The unaligned match at 1 doesn't stop the one at 2 from being found
+        assert_eq!(
+            pattern
+                .map_selections_to_matches(&buffer, false, 2)
+                .unwrap(),
+            vec![vec![2..4, 4..6, 6..8]]
+        );
+        assert_eq!(
+            pattern
+                .map_selections_to_matches(&buffer, false, 4)
+                .unwrap(),
+            vec![vec![4..6]]
+        );
+        assert_eq!(
+            pattern.find_next(&buffer, 0, true, 4).unwrap(),
+            Some((4..6, false))
+        );
+    }
+
+    #[test]
+    fn test_matches_stay_within_regions() {
+        let mut buffer = buffer_with_all_selected(b"abab");
+        buffer.selection.map_selections(|region| {
+            vec![
+                SelRegion::new(region.min(), region.min() + 2),
+                SelRegion::new(3, 3),
+            ]
+        });
+        assert_eq!(
+            Pattern::from_bytes(b"ab")
+                .map_selections_to_matches(&buffer, false, 1)
+                .unwrap(),
+            vec![vec![0..2], vec![]]
+        );
+    }
+
+    #[test]
+    fn test_reinterpret() {
+        let typed = Pattern {
+            pieces: vec![
+                PatternPiece::Literal(b'4'),
+                PatternPiece::Literal(b'1'),
+                PatternPiece::Literal(b' '),
+                PatternPiece::Wildcard,
+                PatternPiece::Literal(b'f'),
+                PatternPiece::Literal(b'F'),
+            ],
+        };
+        let parsed = typed.parsed_as_hex().unwrap();
+        assert_eq!(
+            parsed.pieces,
+            vec![
+                PatternPiece::Literal(0x41),
+                PatternPiece::Wildcard,
+                PatternPiece::Literal(0xff),
+            ]
+        );
+        assert_eq!(parsed.spelled_in_hex(None).parsed_as_hex(), Some(parsed));
+        assert_eq!(
+            Pattern::from_bytes(&[0xab, 0xc0]).spelled_in_hex(Some(1)),
+            Pattern::from_bytes(b"abc")
+        );
+        assert_eq!(Pattern::from_bytes(b"abc").parsed_as_hex(), None);
+        assert_eq!(Pattern::from_bytes(b"xy").parsed_as_hex(), None);
+    }
+
+    #[test]
+    fn test_find_next() {
+        let buffer = buffer_with_all_selected(b"abxab");
+        let pattern = Pattern::from_bytes(b"ab");
+        assert_eq!(
+            pattern.find_next(&buffer, 0, true, 1).unwrap(),
+            Some((3..5, false))
+        );
+        assert_eq!(
+            pattern.find_next(&buffer, 3, true, 1).unwrap(),
+            Some((0..2, true))
+        );
+        assert_eq!(
+            pattern.find_next(&buffer, 3, false, 1).unwrap(),
+            Some((0..2, false))
+        );
+        assert_eq!(
+            pattern.find_next(&buffer, 0, false, 1).unwrap(),
+            Some((3..5, true))
+        );
+        assert_eq!(
+            Pattern::from_bytes(b"c").find_next(&buffer, 0, true, 1),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_empty_pattern_repeats_last_search() {
+        let mut buffers = Buffers::with_buffer(buffer_with_all_selected(b"abcabc"));
+        let enter = Event::Key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+
+        let search = Search::new(crate::modes::collapse::Collapse(), false);
+        match search.transition(&enter, &mut buffers, &mut ViewOptions::new()) {
+            Some(ModeTransition::ModeAndInfo(_, info)) => assert_eq!(info, "no previous search"),
+            _ => panic!("expected an info message"),
+        }
+
+        buffers.last_pattern = Some(Pattern::from_bytes(b"bc"));
+        let search = Search::new(crate::modes::collapse::Collapse(), false);
+        search.transition(&enter, &mut buffers, &mut ViewOptions::new());
+        let selected = buffers
+            .current()
+            .selection
+            .iter()
+            .map(|region| (region.min(), region.max()))
+            .collect::<Vec<_>>();
+        assert_eq!(selected, vec![(1, 2), (4, 5)]);
+    }
+
+    #[test]
+    fn test_ascii_escapes() {
+        let mut buffers = Buffers::new();
+        let mut mode: Box<dyn Mode> =
+            Box::new(Search::new(crate::modes::collapse::Collapse(), false));
+        for ch in "a\\r\\n\\\\n\\x41\\q".chars() {
+            let evt = Event::Key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+            match mode.transition(&evt, &mut buffers, &mut ViewOptions::new()) {
+                Some(ModeTransition::NewMode(next)) => mode = next,
+                _ => panic!("expected the search to go on"),
+            }
+        }
+        let search = mode.as_any().downcast_ref::<Search>().unwrap();
+        // An unknown escape is left as typed
+        assert_eq!(search.pattern, Pattern::from_bytes(b"a\r\n\\nA\\q"));
+    }
+
+    #[test]
+    fn test_from_escaped() {
+        assert_eq!(
+            Pattern::from_escaped("a\\x00\\\\\\q\\x4"),
+            Pattern::from_bytes(b"a\0\\\\q\\x4")
+        );
+    }
+}
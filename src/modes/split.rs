@@ -5,14 +5,15 @@ use std::collections::HashMap;
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use lazy_static::lazy_static;
 
+use crate::cancel::Cancelled;
 use crate::keymap::KeyMap;
 use crate::modes::{
     mode::{Mode, ModeTransition},
-    normal::Normal,
+    normal::{Normal, EMPTY_BUFFER_INFO, NO_MATCHES_INFO},
     search::{Pattern, PatternPiece, Search, SearchAcceptor},
 };
 use crate::selection::SelRegion;
-use crate::{cmd_count, Buffers};
+use crate::{cmd_count, Buffers, ViewOptions};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Split {
@@ -46,21 +47,46 @@ lazy_static! {
 }
 
 impl SearchAcceptor for Split {
-    fn apply_search(&self, pattern: Pattern, buffers: &mut Buffers, _: usize) -> ModeTransition {
+    fn name(&self) -> Cow<'static, str> {
+        Mode::name(self)
+    }
+
+    fn apply_search(
+        &self,
+        pattern: Pattern,
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+    ) -> ModeTransition {
         let buffer = buffers.current_mut();
+        if buffer.data.is_empty() {
+            return ModeTransition::new_mode_and_info(Normal::new(), EMPTY_BUFFER_INFO.to_owned());
+        }
         if pattern.pieces.is_empty() {
             return ModeTransition::new_mode(Normal::new());
         }
-        let matched_ranges = pattern.map_selections_to_matches(buffer);
+        // Overlapping matches would be cut out of the selection twice, so they are never used here
+        let matched_ranges =
+            match pattern.map_selections_to_matches(buffer, false, options.search_align) {
+                Ok(matched_ranges) => matched_ranges,
+                Err(_) => {
+                    return ModeTransition::new_mode_and_info(Normal::new(), Cancelled::info())
+                }
+            };
         let matched_len: usize = matched_ranges
             .iter()
             .flatten()
             .map(|r| r.end - r.start)
             .sum();
+        if matched_len == 0 {
+            return ModeTransition::new_mode_and_info(Normal::new(), NO_MATCHES_INFO.to_owned());
+        }
         if matched_len == buffer.selection.len_bytes() {
             // Everything selected was matched: refuse to split because it would yield
             // an empty selection (invalid)
-            return ModeTransition::new_mode(Normal::new());
+            return ModeTransition::new_mode_and_info(
+                Normal::new(),
+                "split would select nothing".to_owned(),
+            );
         }
 
         let mut remaining_matched_ranges = &matched_ranges[..];
@@ -94,6 +120,11 @@ impl SearchAcceptor for Split {
             }),
         )
     }
+
+    // Back to choosing how to split, with the count typed before the search
+    fn cancelled(&self) -> Box<dyn Mode> {
+        Box::new(*self)
+    }
 }
 
 impl Mode for Split {
@@ -105,7 +136,7 @@ impl Mode for Split {
         &self,
         evt: &Event,
         buffers: &mut Buffers,
-        bytes_per_line: usize,
+        options: &mut ViewOptions,
     ) -> Option<ModeTransition> {
         let buffer = buffers.current_mut();
         if let cmd_count::Transition::Update(new_state) = self.count_state.transition(evt) {
@@ -113,8 +144,10 @@ impl Mode for Split {
                 count_state: new_state,
             }))
         } else if let Some(action) = DEFAULT_MAPS.event_to_action(evt) {
-            let count = self.count_state.to_count();
-            Some(match action {
+            let (count, info) = self
+                .count_state
+                .to_count_capped(options.count_limit(buffer.data.len()));
+            let transition = match action {
                 Action::Width(width) => ModeTransition::new_mode_and_dirty(
                     Normal::new(),
                     buffer.map_selections(|region| {
@@ -129,15 +162,16 @@ impl Mode for Split {
                 ),
                 Action::Null => self.apply_search(
                     Pattern {
-                        pieces: std::iter::repeat(PatternPiece::Literal(0u8))
-                            .take(count)
-                            .collect(),
+                        pieces: std::iter::repeat_n(PatternPiece::Literal(0u8), count).collect(),
                     },
                     buffers,
-                    bytes_per_line,
+                    options,
                 ),
-                Action::Search { hex } => ModeTransition::new_mode(Search::new(*self, hex)),
-            })
+                Action::Search { hex } => {
+                    return Some(ModeTransition::new_mode(Search::new(*self, hex)))
+                }
+            };
+            Some(transition.with_info(info))
         } else if let Event::Key(_) = evt {
             Some(ModeTransition::new_mode(Normal::new()))
         } else {
@@ -145,6 +179,10 @@ impl Mode for Split {
         }
     }
 
+    fn is_prefix(&self) -> bool {
+        true
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
@@ -157,3 +195,68 @@ impl Split {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    fn split_on_commas(data: &[u8], regions: Vec<SelRegion>) -> Vec<(usize, usize, bool)> {
+        let mut buffer = Buffer::from_data_and_path(data.to_vec(), None::<&str>);
+        let mut regions = Some(regions);
+        buffer
+            .selection
+            .map_selections(|_| regions.take().unwrap_or_default());
+        let mut buffers = Buffers::with_buffer(buffer);
+        Split::new().apply_search(
+            Pattern::from_bytes(b","),
+            &mut buffers,
+            &mut ViewOptions::new(),
+        );
+        buffers
+            .current()
+            .selection
+            .iter()
+            .map(|region| (region.min(), region.max(), region.is_main()))
+            .collect()
+    }
+
+    #[test]
+    fn test_main_after_split() {
+        // The first piece of the old main selection becomes the main one
+        assert_eq!(
+            split_on_commas(b"aa,bb,cc", vec![SelRegion::new(0, 7)]),
+            vec![(0, 1, true), (3, 4, false), (6, 7, false)]
+        );
+
+        // The old main selection was all separator, so the next piece is main
+        assert_eq!(
+            split_on_commas(b",ab,cd", vec![SelRegion::new(0, 0), SelRegion::new(1, 5)]),
+            vec![(1, 2, true), (4, 5, false)]
+        );
+    }
+
+    #[test]
+    fn test_cancel_search_returns_to_launching_mode() {
+        let mut buffers =
+            Buffers::with_buffer(Buffer::from_data_and_path(vec![0; 4], None::<&str>));
+        let mut options = ViewOptions::new();
+        let mut press = |mode: &dyn Mode, code| {
+            let evt = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+            match mode.transition(&evt, &mut buffers, &mut options) {
+                Some(ModeTransition::NewMode(next)) => next,
+                _ => panic!("expected a new mode"),
+            }
+        };
+
+        let search = press(&Split::new(), KeyCode::Char('/'));
+        assert!(search.as_any().is::<Search>());
+        let cancelled = press(search.as_ref(), KeyCode::Esc);
+        assert!(cancelled.as_any().is::<Split>());
+
+        let search = press(&Normal::new(), KeyCode::Char('s'));
+        assert!(search.as_any().is::<Search>());
+        let cancelled = press(search.as_ref(), KeyCode::Esc);
+        assert!(cancelled.as_any().is::<Normal>());
+    }
+}
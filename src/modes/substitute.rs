@@ -0,0 +1,223 @@
+use std::borrow::Cow;
+
+use crate::cancel::Cancelled;
+use crate::modes::search::{Pattern, PatternPiece, Search, SearchAcceptor};
+use crate::modes::{
+    mode::ModeTransition,
+    normal::{Normal, EMPTY_BUFFER_INFO, NO_CHANGE_INFO, NO_MATCHES_INFO},
+};
+use crate::operations as ops;
+use crate::{Buffers, ViewOptions};
+
+// Takes the pattern to replace, then prompts for what to replace it with
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Substitute {
+    pub hex: bool,
+    // How many matches to replace in each selection, or None for all of them
+    pub limit: Option<usize>,
+}
+
+// Takes the replacement and substitutes it for the matches of the pattern in each selection
+#[derive(Debug, PartialEq, Clone)]
+pub struct SubstituteWith {
+    pub pattern: Pattern,
+    pub limit: Option<usize>,
+}
+
+impl SearchAcceptor for Substitute {
+    fn name(&self) -> Cow<'static, str> {
+        "SUBSTITUTE".into()
+    }
+
+    fn apply_search(
+        &self,
+        pattern: Pattern,
+        buffers: &mut Buffers,
+        _: &mut ViewOptions,
+    ) -> ModeTransition {
+        if buffers.current().data.is_empty() {
+            return ModeTransition::new_mode_and_info(Normal::new(), EMPTY_BUFFER_INFO.to_owned());
+        }
+        if pattern.pieces.is_empty() {
+            return ModeTransition::new_mode(Normal::new());
+        }
+        ModeTransition::new_mode(Search::new(
+            SubstituteWith {
+                pattern,
+                limit: self.limit,
+            },
+            self.hex,
+        ))
+    }
+}
+
+impl SearchAcceptor for SubstituteWith {
+    fn name(&self) -> Cow<'static, str> {
+        "REPLACE WITH".into()
+    }
+
+    fn apply_search(
+        &self,
+        replacement: Pattern,
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+    ) -> ModeTransition {
+        let replacement = replacement
+            .pieces
+            .iter()
+            .map(|piece| match piece {
+                PatternPiece::Literal(byte) => Some(*byte),
+                PatternPiece::Wildcard => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        let replacement = match replacement {
+            Some(replacement) => replacement,
+            None => {
+                return ModeTransition::new_mode_and_info(
+                    Normal::new(),
+                    "the replacement can't contain wildcards".to_owned(),
+                )
+            }
+        };
+
+        let buffer = buffers.current_mut();
+        // Overlapping matches can't all be replaced, so they are never searched for
+        let matched_ranges =
+            match self
+                .pattern
+                .map_selections_to_matches(buffer, false, options.search_align)
+            {
+                Ok(matched_ranges) => matched_ranges,
+                Err(_) => {
+                    return ModeTransition::new_mode_and_info(Normal::new(), Cancelled::info())
+                }
+            };
+        let replacements = matched_ranges
+            .into_iter()
+            .flat_map(|ranges| ranges.into_iter().take(self.limit.unwrap_or(usize::MAX)))
+            .map(|range| {
+                let mut bytes = replacement.clone();
+                if options.substitute_keep_wildcards {
+                    let matched = buffer.data.slice_to_cow(range.clone());
+                    for (i, piece) in self.pattern.pieces.iter().enumerate() {
+                        if let (PatternPiece::Wildcard, Some(byte)) = (piece, bytes.get_mut(i)) {
+                            *byte = matched[i];
+                        }
+                    }
+                }
+                (range, bytes)
+            })
+            .collect::<Vec<_>>();
+        if replacements.is_empty() {
+            return ModeTransition::new_mode_and_info(Normal::new(), NO_MATCHES_INFO.to_owned());
+        }
+
+        let delta = ops::substitute(&buffer.data, &replacements);
+        if buffer.data.is_unchanged_by(&delta) {
+            return ModeTransition::new_mode_and_info(Normal::new(), NO_CHANGE_INFO.to_owned());
+        }
+        ModeTransition::new_mode_dirty_and_info(
+            Normal::new(),
+            buffer.apply_delta_covering(delta),
+            format!("replaced {} matches", replacements.len()),
+        )
+    }
+
+    fn is_search(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::SelRegion;
+    use crate::Buffer;
+
+    fn substitute(
+        buffer: Buffer,
+        pattern: Pattern,
+        replacement: &[u8],
+        limit: Option<usize>,
+        options: &mut ViewOptions,
+    ) -> (Buffers, Option<String>) {
+        let mut buffers = Buffers::with_buffer(buffer);
+        let info = SubstituteWith { pattern, limit }
+            .apply_search(Pattern::from_bytes(replacement), &mut buffers, options)
+            .info()
+            .map(str::to_owned);
+        (buffers, info)
+    }
+
+    fn regions(buffers: &Buffers) -> Vec<(usize, usize)> {
+        buffers
+            .current()
+            .selection
+            .iter()
+            .map(|region| (region.min(), region.max()))
+            .collect()
+    }
+
+    #[test]
+    fn test_substitute_in_selections() {
+        let mut buffer = Buffer::from_data_and_path(b"abxabxab".to_vec(), None::<&str>);
+        buffer
+            .selection
+            .map_selections(|_| vec![SelRegion::new(0, 4), SelRegion::new(6, 7)]);
+        let (buffers, info) = substitute(
+            buffer,
+            Pattern::from_bytes(b"ab"),
+            b"WXYZ",
+            None,
+            &mut ViewOptions::new(),
+        );
+        assert_eq!(info.as_deref(), Some("replaced 3 matches"));
+        assert_eq!(
+            buffers.current().data.slice_to_cow(..),
+            &b"WXYZxWXYZxWXYZ"[..]
+        );
+        assert_eq!(regions(&buffers), vec![(0, 8), (10, 13)]);
+
+        // The count limits the matches replaced in each selection
+        let mut buffer = Buffer::from_data_and_path(b"abxabxab".to_vec(), None::<&str>);
+        buffer.selection.select_all(8);
+        let (buffers, _) = substitute(
+            buffer,
+            Pattern::from_bytes(b"ab"),
+            b"",
+            Some(2),
+            &mut ViewOptions::new(),
+        );
+        assert_eq!(buffers.current().data.slice_to_cow(..), &b"xxab"[..]);
+        assert_eq!(regions(&buffers), vec![(0, 3)]);
+    }
+
+    #[test]
+    fn test_substitute_wildcards() {
+        let pattern = Pattern {
+            pieces: vec![PatternPiece::Literal(b'a'), PatternPiece::Wildcard],
+        };
+        let buffer = || {
+            let mut buffer = Buffer::from_data_and_path(b"a1a2".to_vec(), None::<&str>);
+            buffer.selection.select_all(4);
+            buffer
+        };
+        let mut options = ViewOptions::new();
+        let (buffers, _) = substitute(buffer(), pattern.clone(), b"b_", None, &mut options);
+        assert_eq!(buffers.current().data.slice_to_cow(..), &b"b_b_"[..]);
+
+        options.set("substitute-keep-wildcards", "on").unwrap();
+        let (buffers, _) = substitute(buffer(), pattern.clone(), b"b_", None, &mut options);
+        assert_eq!(buffers.current().data.slice_to_cow(..), &b"b1b2"[..]);
+
+        let (buffers, info) = substitute(
+            buffer(),
+            Pattern::from_bytes(b"zz"),
+            b"b",
+            None,
+            &mut options,
+        );
+        assert_eq!(info.as_deref(), Some(NO_MATCHES_INFO));
+        assert_eq!(buffers.current().data.slice_to_cow(..), &b"a1a2"[..]);
+    }
+}
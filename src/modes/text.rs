@@ -0,0 +1,122 @@
+use std::borrow::Cow;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+use crate::modes::{
+    insert::{self, Insert, InsertionMode},
+    mode::{DirtyBytes, Mode, ModeTransition},
+};
+use crate::{Buffers, ViewOptions};
+
+// Edits the ASCII column like a text field: typed characters go in as UTF-8, and enter and
+// tab insert their control bytes. Everything else works like ascii insert mode.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TextEntry {
+    pub mode: InsertionMode,
+}
+
+impl TextEntry {
+    fn as_insert(&self) -> Insert {
+        Insert {
+            mode: self.mode,
+            hex: false,
+            hex_half: None,
+        }
+    }
+
+    // Stays in text entry when the insert mode handling the key stays in ascii insert mode
+    fn keep_text_entry(mode: Box<dyn Mode>) -> Box<dyn Mode> {
+        match mode.as_any().downcast_ref::<Insert>() {
+            Some(insert) if !insert.hex => Box::new(TextEntry { mode: insert.mode }),
+            _ => mode,
+        }
+    }
+}
+
+// The character a key inserts as text, including newlines and tabs
+pub fn typed_char(evt: &Event) -> Option<char> {
+    match evt {
+        Event::Key(KeyEvent {
+            code: KeyCode::Char(ch),
+            modifiers,
+        }) if (*modifiers & !KeyModifiers::SHIFT).is_empty() => Some(*ch),
+        Event::Key(KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+        }) => Some('\n'),
+        Event::Key(KeyEvent {
+            code: KeyCode::Tab,
+            modifiers: KeyModifiers::NONE,
+        }) => Some('\t'),
+        _ => None,
+    }
+}
+
+impl Mode for TextEntry {
+    fn name(&self) -> Cow<'static, str> {
+        match self.mode {
+            InsertionMode::Insert => "TEXT".into(),
+            InsertionMode::Append => "TEXT (append)".into(),
+            InsertionMode::Overwrite => "TEXT (overwrite)".into(),
+        }
+    }
+
+    fn transition(
+        &self,
+        evt: &Event,
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+    ) -> Option<ModeTransition> {
+        if let Some(ch) = typed_char(evt) {
+            let buffer = buffers.current_mut();
+            insert::insert_bytes(buffer, self.mode, ch.to_string().into_bytes());
+            return Some(ModeTransition::DirtyBytes(DirtyBytes::ChangeLength));
+        }
+        self.as_insert()
+            .transition(evt, buffers, options)
+            .map(|transition| transition.map_mode(TextEntry::keep_text_entry))
+    }
+
+    fn paste(
+        &self,
+        text: &str,
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+    ) -> Option<ModeTransition> {
+        self.as_insert()
+            .paste(text, buffers, options)
+            .map(|transition| transition.map_mode(TextEntry::keep_text_entry))
+    }
+
+    fn caret_in_hex(&self) -> bool {
+        false
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    #[test]
+    fn test_overwrite_text() {
+        let buffer = Buffer::from_data_and_path(b"abcd".to_vec(), None::<&str>);
+        let mut buffers = Buffers::with_buffer(buffer);
+        let mut options = ViewOptions::new();
+        let mode = TextEntry {
+            mode: InsertionMode::Overwrite,
+        };
+        for code in [KeyCode::Char('é'), KeyCode::Enter] {
+            let evt = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+            mode.transition(&evt, &mut buffers, &mut options);
+        }
+        assert_eq!(
+            buffers.current().data.slice_to_cow(..),
+            &[0xc3, 0xa9, b'\n', b'd'][..]
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use lazy_static::lazy_static;
+
+use crate::keymap::KeyMap;
+use crate::modes::{
+    mode::{Mode, ModeTransition},
+    normal::Normal,
+};
+use crate::selection::Direction;
+use crate::{cmd_count, Buffers, ViewOptions};
+
+// Sticky extension: movement keys extend the selections until `v` or escape. Any other key
+// runs the normal mode command, which also leaves visual mode.
+// In line-wise mode the selections always cover whole rows.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Visual {
+    count_state: cmd_count::State,
+    linewise: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Action {
+    Extend(Direction),
+    Toggle { linewise: bool },
+    Exit,
+}
+
+fn default_maps() -> KeyMap<Action> {
+    KeyMap {
+        maps: keys!(
+            ('h' => Action::Extend(Direction::Left)),
+            ('H' => Action::Extend(Direction::Left)),
+            (key KeyCode::Left => Action::Extend(Direction::Left)),
+            ('j' => Action::Extend(Direction::Down)),
+            ('J' => Action::Extend(Direction::Down)),
+            (key KeyCode::Down => Action::Extend(Direction::Down)),
+            ('k' => Action::Extend(Direction::Up)),
+            ('K' => Action::Extend(Direction::Up)),
+            (key KeyCode::Up => Action::Extend(Direction::Up)),
+            ('l' => Action::Extend(Direction::Right)),
+            ('L' => Action::Extend(Direction::Right)),
+            (key KeyCode::Right => Action::Extend(Direction::Right)),
+            ('v' => Action::Toggle{linewise: false}),
+            ('V' => Action::Toggle{linewise: true}),
+            (key KeyCode::Esc => Action::Exit)
+        ),
+    }
+}
+
+lazy_static! {
+    static ref DEFAULT_MAPS: KeyMap<Action> = default_maps();
+}
+
+impl Mode for Visual {
+    fn name(&self) -> Cow<'static, str> {
+        if self.linewise {
+            format!("VISUAL LINE{}", self.count_state).into()
+        } else {
+            format!("VISUAL{}", self.count_state).into()
+        }
+    }
+
+    fn transition(
+        &self,
+        evt: &Event,
+        buffers: &mut Buffers,
+        options: &mut ViewOptions,
+    ) -> Option<ModeTransition> {
+        let bytes_per_line = options.bytes_per_line;
+        let buffer = buffers.current_mut();
+        if let cmd_count::Transition::Update(new_state) = self.count_state.transition(evt) {
+            Some(ModeTransition::new_mode(Visual {
+                count_state: new_state,
+                ..*self
+            }))
+        } else if let Some(action) = DEFAULT_MAPS.event_to_action(evt) {
+            let max_bytes = buffer.data.len();
+            Some(match action {
+                Action::Extend(Direction::Left | Direction::Right) if self.linewise => {
+                    ModeTransition::new_mode(Visual::new(true))
+                }
+                Action::Extend(direction) => ModeTransition::new_mode_and_dirty(
+                    Visual::new(self.linewise),
+                    buffer.map_selections(|region| {
+                        let region = region.simple_extend(
+                            direction,
+                            bytes_per_line,
+                            options.motion_limit(max_bytes),
+                            self.count_state.to_count(),
+                        );
+                        if self.linewise {
+                            vec![region.snap_to_rows(bytes_per_line, max_bytes)]
+                        } else {
+                            vec![region]
+                        }
+                    }),
+                ),
+                Action::Toggle { linewise } if linewise == self.linewise => {
+                    ModeTransition::new_mode(Normal::new())
+                }
+                Action::Toggle { linewise } => Visual::enter(buffers, options, linewise),
+                Action::Exit => ModeTransition::new_mode(Normal::new()),
+            })
+        } else {
+            let transition =
+                Normal::with_count(self.count_state).transition(evt, buffers, options)?;
+            Some(match transition {
+                // These would otherwise keep us in visual mode
+                ModeTransition::None => ModeTransition::new_mode(Normal::new()),
+                ModeTransition::DirtyBytes(dirty) => {
+                    ModeTransition::new_mode_and_dirty(Normal::new(), dirty)
+                }
+                transition => transition,
+            })
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl Visual {
+    pub fn new(linewise: bool) -> Visual {
+        Visual {
+            count_state: cmd_count::State::None,
+            linewise,
+        }
+    }
+
+    pub fn enter(buffers: &mut Buffers, options: &ViewOptions, linewise: bool) -> ModeTransition {
+        if !linewise {
+            return ModeTransition::new_mode(Visual::new(false));
+        }
+        let buffer = buffers.current_mut();
+        let max_bytes = buffer.data.len();
+        ModeTransition::new_mode_and_dirty(
+            Visual::new(true),
+            buffer.map_selections(|region| {
+                vec![region.snap_to_rows(options.bytes_per_line, max_bytes)]
+            }),
+        )
+    }
+}
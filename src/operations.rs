@@ -1,11 +1,25 @@
 use super::byte_rope::*;
 use super::selection::*;
+use std::ops::Range;
 use xi_rope::{DeltaBuilder, Interval};
 
+// The bytes a region covers. A caret past the end doesn't cover a byte, so the interval stops
+// at the end of the buffer.
+fn region_interval(base: &Rope, region: &SelRegion) -> Interval {
+    let end = std::cmp::min(base.len(), region.max() + 1);
+    Interval::new(std::cmp::min(region.min(), end), end)
+}
+
+// The byte under a caret, or an empty interval at the end for a caret past the end
+fn caret_interval(base: &Rope, caret: usize) -> Interval {
+    let end = std::cmp::min(base.len(), caret + 1);
+    Interval::new(std::cmp::min(caret, end), end)
+}
+
 pub fn deletion(base: &Rope, selection: &Selection) -> RopeDelta {
     let mut builder = DeltaBuilder::new(base.len());
     for region in selection.iter() {
-        let iv = Interval::new(region.min(), region.max() + 1);
+        let iv = region_interval(base, region);
         if !iv.is_empty() {
             builder.delete(iv);
         }
@@ -17,7 +31,9 @@ pub fn deletion(base: &Rope, selection: &Selection) -> RopeDelta {
 pub fn backspace(base: &Rope, selection: &Selection) -> RopeDelta {
     let mut builder = DeltaBuilder::new(base.len());
     for region in selection.iter() {
-        if region.caret == 0 {
+        // A bare cursor past the end has nothing to its left that belongs to it
+        let past_end = region.caret == base.len() && region.tail == base.len();
+        if region.caret == 0 || past_end {
             continue;
         }
         let iv = Interval::new(region.caret - 1, region.caret);
@@ -40,6 +56,16 @@ pub fn delete_cursor(base: &Rope, selection: &Selection) -> RopeDelta {
     builder.build()
 }
 
+// Replaces the bytes in `range` with `text`
+pub fn splice(base: &Rope, range: std::ops::Range<usize>, text: impl Into<Rope>) -> RopeDelta {
+    let mut builder = DeltaBuilder::new(base.len());
+    builder.replace(
+        Interval::new(range.start, range.end),
+        text.into().into_node(),
+    );
+    builder.build()
+}
+
 pub fn insert(base: &Rope, selection: &Selection, text: impl Into<Rope>) -> RopeDelta {
     let inserted = text.into();
     let mut builder = DeltaBuilder::new(base.len());
@@ -59,38 +85,152 @@ pub fn paste(
     count: usize,
 ) -> RopeDelta {
     let mut builder = DeltaBuilder::new(base.len());
-    let last_value = register_contents.last().unwrap();
-    let reg_iter = register_contents
+    // Build each pasted value once: nodes are reference counted, so sharing them between
+    // selections is cheap
+    let pasted_nodes = register_contents
         .iter()
-        .chain(std::iter::repeat(last_value));
-    for (region, pasted) in selection.iter().zip(reg_iter) {
+        .take(selection.len())
+        .map(|pasted| {
+            Rope::from(
+                std::iter::repeat_n(pasted, count)
+                    .flatten()
+                    .copied()
+                    .collect::<Vec<_>>(),
+            )
+            .into_node()
+        })
+        .collect::<Vec<_>>();
+    let last_value = pasted_nodes.last().unwrap();
+    let node_iter = pasted_nodes.iter().chain(std::iter::repeat(last_value));
+    for (region, pasted) in selection.iter().zip(node_iter) {
         let insert_pos = if after {
             std::cmp::min(base.len(), region.max() + 1)
         } else {
             region.min()
         };
         let iv = Interval::new(insert_pos, insert_pos);
+        builder.replace(iv, pasted.clone());
+    }
+
+    builder.build()
+}
+
+// Replaces each selection with an entry of the register, cycling through the entries
+pub fn replace_with_register(
+    base: &Rope,
+    selection: &Selection,
+    register_contents: &[Vec<u8>],
+) -> RopeDelta {
+    let mut builder = DeltaBuilder::new(base.len());
+    let nodes = register_contents
+        .iter()
+        .take(selection.len())
+        .map(|entry| Rope::from(entry.clone()).into_node())
+        .collect::<Vec<_>>();
+    for (region, node) in selection.iter().zip(nodes.iter().cycle()) {
+        builder.replace(region_interval(base, region), node.clone());
+    }
+
+    builder.build()
+}
+
+// Replaces each range with its bytes. The ranges must be in order and not overlap.
+pub fn substitute(base: &Rope, replacements: &[(Range<usize>, Vec<u8>)]) -> RopeDelta {
+    let mut builder = DeltaBuilder::new(base.len());
+    for (range, bytes) in replacements {
         builder.replace(
-            iv,
-            Rope::from(
-                std::iter::repeat(pasted)
-                    .take(count)
-                    .flatten()
-                    .copied()
-                    .collect::<Vec<_>>(),
-            )
-            .into_node(),
+            Interval::new(range.start, range.end),
+            Rope::from(bytes.clone()).into_node(),
         );
     }
 
     builder.build()
 }
 
+// Writes an entry of the register over the bytes from each caret, cycling through the entries.
+// The length never changes: an entry stops at the end of the buffer or at the next caret. The
+// boolean tells whether any entry was cut short.
+pub fn stamp(
+    base: &Rope,
+    selection: &Selection,
+    register_contents: &[Vec<u8>],
+) -> (RopeDelta, bool) {
+    let mut builder = DeltaBuilder::new(base.len());
+    let mut truncated = false;
+    let mut regions = selection.iter().peekable();
+    let mut entries = register_contents.iter().take(selection.len()).cycle();
+    while let (Some(region), Some(entry)) = (regions.next(), entries.next()) {
+        let limit = regions
+            .peek()
+            .map(|next| std::cmp::min(next.caret, base.len()))
+            .unwrap_or_else(|| base.len());
+        let start = std::cmp::min(region.caret, limit);
+        let len = std::cmp::min(entry.len(), limit - start);
+        truncated |= len < entry.len();
+        if len > 0 {
+            builder.replace(
+                Interval::new(start, start + len),
+                Rope::from(entry[..len].to_vec()).into_node(),
+            );
+        }
+    }
+
+    (builder.build(), truncated)
+}
+
+// Overwrites the bytes at each offset with the matching entry. Entries are cut short at the end
+// of the buffer and at the next offset, which the returned bool notes.
+pub fn paste_at_origin(
+    base: &Rope,
+    origins: &[usize],
+    register_contents: &[Vec<u8>],
+) -> (RopeDelta, bool) {
+    let mut builder = DeltaBuilder::new(base.len());
+    let mut truncated = false;
+    let mut offsets = origins.iter().peekable();
+    let mut entries = register_contents.iter();
+    while let (Some(&origin), Some(entry)) = (offsets.next(), entries.next()) {
+        let limit = offsets
+            .peek()
+            .map(|&&next| std::cmp::min(next, base.len()))
+            .unwrap_or_else(|| base.len());
+        let start = std::cmp::min(origin, limit);
+        let len = std::cmp::min(entry.len(), limit - start);
+        truncated |= len < entry.len();
+        if len > 0 {
+            builder.replace(
+                Interval::new(start, start + len),
+                Rope::from(entry[..len].to_vec()).into_node(),
+            );
+        }
+    }
+
+    (builder.build(), truncated)
+}
+
 pub fn change(base: &Rope, selection: &Selection, text: impl Into<Rope>) -> RopeDelta {
     let inserted = text.into();
     let mut builder = DeltaBuilder::new(base.len());
     for region in selection.iter() {
-        let iv = Interval::new(region.caret, region.caret + 1);
+        let iv = caret_interval(base, region.caret);
+        builder.replace(iv, inserted.clone().into_node());
+    }
+
+    builder.build()
+}
+
+pub fn overwrite(base: &Rope, selection: &Selection, text: impl Into<Rope>) -> RopeDelta {
+    let inserted = text.into();
+    let mut builder = DeltaBuilder::new(base.len());
+    let mut regions = selection.iter().peekable();
+    while let Some(region) = regions.next() {
+        // Stop at the next caret so that the replaced intervals never overlap
+        let limit = regions
+            .peek()
+            .map(|next| next.caret)
+            .unwrap_or_else(|| base.len());
+        let end = std::cmp::min(region.caret + inserted.len(), limit);
+        let iv = Interval::new(region.caret, std::cmp::max(region.caret, end));
         builder.replace(iv, inserted.clone().into_node());
     }
 
@@ -100,9 +240,9 @@ pub fn change(base: &Rope, selection: &Selection, text: impl Into<Rope>) -> Rope
 pub fn overwrite_half(base: &Rope, selection: &Selection, top: u8) -> RopeDelta {
     let mut builder = DeltaBuilder::new(base.len());
     for region in selection.iter() {
-        let iv = Interval::new(region.caret, region.caret + 1);
+        let iv = caret_interval(base, region.caret);
 
-        let base_char = if base.len() > region.caret {
+        let base_char = if !iv.is_empty() {
             base.slice_to_cow(iv)[0] & 0x0F
         } else {
             0
@@ -117,12 +257,81 @@ pub fn overwrite_half(base: &Rope, selection: &Selection, top: u8) -> RopeDelta
 pub fn replace(base: &Rope, selection: &Selection, ch: u8) -> RopeDelta {
     let mut builder = DeltaBuilder::new(base.len());
     for region in selection.iter() {
-        let iv = Interval::new(region.min(), region.max() + 1);
-        builder.replace(
-            iv,
-            Rope::from(vec![ch; region.max() - region.min() + 1]).into_node(),
-        );
+        let iv = region_interval(base, region);
+        if !iv.is_empty() {
+            builder.replace(iv, Rope::from(vec![ch; iv.size()]).into_node());
+        }
     }
 
     builder.build()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Four bytes, selected from the third byte to the cursor past the end
+    fn select_to_end() -> (Rope, Selection) {
+        let mut selection = Selection::new();
+        selection.map_selections(|_| vec![SelRegion::new(4, 2)]);
+        (Rope::from(b"abcd".to_vec()), selection)
+    }
+
+    fn applied(base: &Rope, delta: RopeDelta) -> Vec<u8> {
+        base.apply_delta(&delta).slice_to_cow(..).into_owned()
+    }
+
+    #[test]
+    fn test_selection_past_end() {
+        let (base, selection) = select_to_end();
+        assert_eq!(applied(&base, deletion(&base, &selection)), b"ab");
+        assert_eq!(applied(&base, replace(&base, &selection, b'x')), b"abxx");
+        assert_eq!(
+            applied(&base, paste(&base, &selection, &[b"yz".to_vec()], true, 1)),
+            b"abcdyz"
+        );
+        assert_eq!(
+            applied(&base, paste(&base, &selection, &[b"yz".to_vec()], false, 1)),
+            b"abyzcd"
+        );
+        // Changing the byte under a caret past the end appends
+        assert_eq!(
+            applied(&base, change(&base, &selection, b"e".to_vec())),
+            b"abcde"
+        );
+        assert_eq!(
+            applied(&base, overwrite_half(&base, &selection, 0xf0)),
+            b"abcd\xf0"
+        );
+    }
+
+    #[test]
+    fn test_stamp_keeps_length() {
+        let base = Rope::from(b"abcdefgh".to_vec());
+        let mut selection = Selection::new();
+        selection.map_selections(|_| vec![SelRegion::new(1, 1), SelRegion::new(3, 3)]);
+        let (delta, truncated) = stamp(&base, &selection, &[b"XYZ".to_vec()]);
+        // The first entry stops at the second caret
+        assert!(truncated);
+        assert_eq!(base.apply_delta(&delta).slice_to_cow(..), &b"aXYXYZgh"[..]);
+
+        selection.map_selections(|_| vec![SelRegion::new(6, 6)]);
+        let (delta, truncated) = stamp(&base, &selection, &[b"XYZ".to_vec()]);
+        assert!(truncated);
+        assert_eq!(base.apply_delta(&delta).slice_to_cow(..), &b"abcdefXY"[..]);
+    }
+
+    #[test]
+    fn test_paste_at_origin() {
+        let base = Rope::from(b"abcdefgh".to_vec());
+        let contents = [b"XY".to_vec(), b"Z".to_vec()];
+        let (delta, truncated) = paste_at_origin(&base, &[1, 5], &contents);
+        assert!(!truncated);
+        assert_eq!(base.apply_delta(&delta).slice_to_cow(..), &b"aXYdeZgh"[..]);
+
+        // Offsets past the end of a shorter buffer are clamped
+        let (delta, truncated) = paste_at_origin(&base, &[7, 12], &contents);
+        assert!(truncated);
+        assert_eq!(base.apply_delta(&delta).slice_to_cow(..), &b"abcdefgX"[..]);
+    }
+}
@@ -0,0 +1,302 @@
+use std::cmp;
+use std::time::Duration;
+
+use crossterm::style::Color;
+
+use crate::encoding::{AsciiClass, Encoding};
+use crate::modes::search::Pattern;
+
+pub const MAX_INSPECTOR_WIDTH: usize = 16;
+// The automatic count limit never goes below this, so small buffers still take counts
+pub const MIN_AUTO_COUNT_LIMIT: usize = 0x100;
+
+// Every match of the pattern is drawn in the color, whatever is selected
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Highlight {
+    pub pattern: Pattern,
+    pub color: Color,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ViewOptions {
+    pub bytes_per_line: usize,
+    pub bytes_per_line_auto: bool,
+    pub inspector_compact: bool,
+    pub inspector_width: usize,
+    // The inspector shows the bytes here instead of at the main caret
+    pub inspector_pin: Option<usize>,
+    pub show_changes: bool,
+    pub encoding: Encoding,
+    pub ascii_class: AsciiClass,
+    // Arrows for tabs and line breaks and middle dots for other control bytes in the ASCII column
+    pub whitespace_glyphs: bool,
+    pub search_overlapping: bool,
+    // Matches must start at a multiple of this
+    pub search_align: usize,
+    // Substitutions keep the matched bytes under the wildcards of the pattern
+    pub substitute_keep_wildcards: bool,
+    pub debug: bool,
+    // Show the names of nested modes in the statusline
+    pub debug_modes: bool,
+    pub scrolloff: usize,
+    // Whether motions can put the caret after the last byte
+    pub append_cursor: bool,
+    pub minimap: bool,
+    pub ruler: bool,
+    // Inserted by <c-n> in insert and replace modes
+    pub fill_byte: u8,
+    // Yanks into the unnamed register also go to `+`
+    pub clipboard_unnamed: bool,
+    // Background of the carets of selections other than the main one
+    pub inactive_caret_color: Color,
+    // Background of the tail of the main selection
+    pub tail_color: Color,
+    // Drawn in order, so later highlights cover earlier ones
+    pub highlights: Vec<Highlight>,
+    // How long prefix modes wait for the next key, or None to wait forever
+    pub timeout: Option<Duration>,
+    // None follows the buffer length
+    pub count_limit: Option<usize>,
+}
+
+impl Default for ViewOptions {
+    fn default() -> Self {
+        ViewOptions {
+            bytes_per_line: 0x10,
+            bytes_per_line_auto: false,
+            inspector_compact: false,
+            inspector_width: 4,
+            inspector_pin: None,
+            show_changes: false,
+            encoding: Encoding::Ascii,
+            ascii_class: AsciiClass::Default,
+            whitespace_glyphs: false,
+            substitute_keep_wildcards: false,
+            search_overlapping: false,
+            search_align: 1,
+            debug: false,
+            debug_modes: false,
+            scrolloff: 0,
+            append_cursor: true,
+            minimap: false,
+            ruler: true,
+            fill_byte: 0,
+            clipboard_unnamed: false,
+            inactive_caret_color: Color::Rgb {
+                r: 150,
+                g: 150,
+                b: 176,
+            },
+            tail_color: Color::Rgb {
+                r: 176,
+                g: 150,
+                b: 40,
+            },
+            highlights: vec![],
+            timeout: None,
+            count_limit: None,
+        }
+    }
+}
+
+pub fn parse_usize(value: &str) -> Result<usize, String> {
+    let parsed = if let Some(hex) = value.strip_prefix("0x") {
+        usize::from_str_radix(hex, 16)
+    } else {
+        value.parse()
+    };
+    parsed.map_err(|_| format!("expected a number, got {}", value))
+}
+
+// A color name like dark_grey, or #rrggbb
+pub fn parse_color(value: &str) -> Result<Color, String> {
+    let invalid = || format!("expected a color name or #rrggbb, got {}", value);
+    match value.strip_prefix('#') {
+        Some(hex) if hex.len() == 6 => {
+            let rgb = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+            Ok(Color::Rgb {
+                r: (rgb >> 16) as u8,
+                g: (rgb >> 8) as u8,
+                b: rgb as u8,
+            })
+        }
+        Some(_) => Err(invalid()),
+        None => Color::try_from(value).map_err(|_| invalid()),
+    }
+}
+
+// A number, or a single character standing for its ascii code
+pub fn parse_byte(value: &str) -> Result<u8, String> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) if ch.is_ascii() && !ch.is_ascii_digit() => Ok(ch as u8),
+        _ => {
+            u8::try_from(parse_usize(value)?).map_err(|_| format!("expected a byte, got {}", value))
+        }
+    }
+}
+
+// Hex bytes, written as `de ad`, `dead`, `0xde 0xad` or `\\xde\\xad`. Every whitespace
+// separated word holds whole bytes.
+pub fn parse_hex_bytes(value: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    for word in value.split_whitespace() {
+        let groups = match (word.strip_prefix("0x"), word.strip_prefix("\\x")) {
+            (Some(digits), _) => vec![digits],
+            (_, Some(escaped)) => escaped.split("\\x").collect(),
+            _ => vec![word],
+        };
+        for digits in groups {
+            if let Some(invalid) = digits.chars().find(|ch| !ch.is_ascii_hexdigit()) {
+                return Err(format!("invalid hex digit {} in {}", invalid, word));
+            }
+            if digits.is_empty() {
+                return Err(format!("missing hex digits in {}", word));
+            }
+            if digits.len() % 2 != 0 {
+                return Err(format!("odd number of hex digits in {}", word));
+            }
+            // Only ascii digits are left, so every pair is a valid byte
+            for pair in digits.as_bytes().chunks(2) {
+                let pair = std::str::from_utf8(pair).unwrap();
+                bytes.push(u8::from_str_radix(pair, 16).unwrap());
+            }
+        }
+    }
+    if bytes.is_empty() {
+        return Err("expected hex bytes".to_owned());
+    }
+    Ok(bytes)
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "" | "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        _ => Err(format!("expected on or off, got {}", value)),
+    }
+}
+
+impl ViewOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // The largest count accepted by operations on a buffer of `buffer_len` bytes
+    pub fn count_limit(&self, buffer_len: usize) -> usize {
+        self.count_limit
+            .unwrap_or_else(|| cmp::max(buffer_len, MIN_AUTO_COUNT_LIMIT))
+    }
+
+    // The furthest offset motions can move the caret to in a buffer of `buffer_len` bytes
+    pub fn motion_limit(&self, buffer_len: usize) -> usize {
+        if self.append_cursor {
+            buffer_len
+        } else {
+            buffer_len.saturating_sub(1)
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "append-cursor" => self.append_cursor = parse_bool(value)?,
+            "ascii-class" => self.ascii_class = AsciiClass::from_name(value)?,
+            "bpl" if value == "auto" => self.bytes_per_line_auto = true,
+            "bpl" => match parse_usize(value)? {
+                0 => return Err("bytes per line must be at least 1".to_owned()),
+                bpl => {
+                    self.bytes_per_line = bpl;
+                    self.bytes_per_line_auto = false;
+                }
+            },
+            "fillbyte" => self.fill_byte = parse_byte(value)?,
+            "inactive-caret-color" => self.inactive_caret_color = parse_color(value)?,
+            "inspector-compact" => self.inspector_compact = parse_bool(value)?,
+            "inspector-width" => match parse_usize(value)? {
+                width @ 1..=MAX_INSPECTOR_WIDTH => self.inspector_width = width,
+                _ => {
+                    return Err(format!(
+                        "inspector width must be between 1 and {}",
+                        MAX_INSPECTOR_WIDTH
+                    ))
+                }
+            },
+            "minimap" => self.minimap = parse_bool(value)?,
+            "ruler" => self.ruler = parse_bool(value)?,
+            "scrolloff" => self.scrolloff = parse_usize(value)?,
+            "search-align" => match parse_usize(value)? {
+                0 => return Err("search alignment must be at least 1".to_owned()),
+                align => self.search_align = align,
+            },
+            "search-overlap" => self.search_overlapping = parse_bool(value)?,
+            "showchanges" => self.show_changes = parse_bool(value)?,
+            "substitute-keep-wildcards" => self.substitute_keep_wildcards = parse_bool(value)?,
+            "tail-color" => self.tail_color = parse_color(value)?,
+            "timeoutlen" if value == "off" => self.timeout = None,
+            "timeoutlen" => self.timeout = Some(Duration::from_millis(parse_usize(value)? as u64)),
+            "whitespace-glyphs" => self.whitespace_glyphs = parse_bool(value)?,
+            "clipboard" => match value {
+                "unnamed" => self.clipboard_unnamed = true,
+                "off" => self.clipboard_unnamed = false,
+                _ => return Err(format!("expected unnamed or off, got {}", value)),
+            },
+            "count-limit" if value == "auto" => self.count_limit = None,
+            "count-limit" => match parse_usize(value)? {
+                0 => return Err("count limit must be at least 1".to_owned()),
+                limit => self.count_limit = Some(limit),
+            },
+            "debug" => self.debug = parse_bool(value)?,
+            "debug-modes" => self.debug_modes = parse_bool(value)?,
+            "encoding" => self.encoding = Encoding::from_name(value)?,
+            _ => return Err(format!("unknown option {}", name)),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex_bytes() {
+        for written in [
+            "de ad be ef",
+            "deadbeef",
+            "0xde 0xad 0xbeef",
+            "\\xde\\xad\\xbe\\xef",
+            " DEAD\tbeef ",
+        ] {
+            assert_eq!(parse_hex_bytes(written), Ok(vec![0xde, 0xad, 0xbe, 0xef]));
+        }
+    }
+
+    #[test]
+    fn test_parse_hex_bytes_errors() {
+        assert_eq!(
+            parse_hex_bytes("de a"),
+            Err("odd number of hex digits in a".to_owned())
+        );
+        assert_eq!(
+            parse_hex_bytes("0x"),
+            Err("missing hex digits in 0x".to_owned())
+        );
+        assert_eq!(
+            parse_hex_bytes("dg"),
+            Err("invalid hex digit g in dg".to_owned())
+        );
+        assert_eq!(
+            parse_hex_bytes("+f"),
+            Err("invalid hex digit + in +f".to_owned())
+        );
+        assert_eq!(
+            parse_hex_bytes("\\xde\\x"),
+            Err("missing hex digits in \\xde\\x".to_owned())
+        );
+        assert_eq!(
+            parse_hex_bytes("\\xdef"),
+            Err("odd number of hex digits in \\xdef".to_owned())
+        );
+        assert_eq!(parse_hex_bytes("  "), Err("expected hex bytes".to_owned()));
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+
+use xi_rope::DeltaBuilder;
+
+use super::byte_rope::{Rope, RopeDelta, RopeDeltaElement};
+use super::options::parse_hex_bytes;
+
+const PATCH_HEADER: &str = "teehee-patch 1";
+
+// Replaces base[start..end] with `inserted`
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Edit {
+    start: usize,
+    end: usize,
+    inserted: Vec<u8>,
+}
+
+// A patch is a list of steps, each corresponding to a single delta.
+// Offsets in each step are absolute offsets in the document produced by the previous steps.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Patch {
+    steps: Vec<Vec<Edit>>,
+}
+
+fn parse_hex(s: &str) -> Result<usize, String> {
+    usize::from_str_radix(s, 16).map_err(|_| format!("invalid offset {}", s))
+}
+
+impl Patch {
+    pub fn new() -> Patch {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn record(&mut self, delta: &RopeDelta) {
+        let mut edits: Vec<Edit> = vec![];
+        let mut push_edit = |start: usize, end: usize, inserted: Vec<u8>| {
+            // Deletions directly following an insertion at the same offset form a replacement
+            match edits.last_mut() {
+                Some(last) if last.end == start => {
+                    last.end = end;
+                    last.inserted.extend(inserted);
+                }
+                _ => edits.push(Edit {
+                    start,
+                    end,
+                    inserted,
+                }),
+            }
+        };
+
+        let mut pos = 0;
+        for el in &delta.els {
+            match el {
+                RopeDeltaElement::Copy(start, end) => {
+                    if *start > pos {
+                        push_edit(pos, *start, vec![]);
+                    }
+                    pos = *end;
+                }
+                RopeDeltaElement::Insert(node) => {
+                    push_edit(pos, pos, Vec::from(Rope::from_node(node.clone())));
+                }
+            }
+        }
+        if pos < delta.base_len {
+            push_edit(pos, delta.base_len, vec![]);
+        }
+
+        if !edits.is_empty() {
+            self.steps.push(edits);
+        }
+    }
+
+    pub fn to_deltas(&self, mut base_len: usize) -> Result<Vec<RopeDelta>, String> {
+        let mut deltas = Vec::with_capacity(self.steps.len());
+        for step in &self.steps {
+            let mut builder = DeltaBuilder::new(base_len);
+            let mut last_end = 0;
+            for edit in step {
+                if edit.end > base_len {
+                    return Err(format!(
+                        "patch offset {:x} is past the end of the buffer ({:x})",
+                        edit.end, base_len
+                    ));
+                }
+                if edit.start < last_end || edit.end < edit.start {
+                    return Err(format!("patch edit at {:x} is out of order", edit.start));
+                }
+                last_end = edit.end;
+                builder.replace(
+                    edit.start..edit.end,
+                    Rope::from(edit.inserted.clone()).into_node(),
+                );
+            }
+            let delta = builder.build();
+            base_len = delta.new_document_len();
+            deltas.push(delta);
+        }
+        Ok(deltas)
+    }
+
+    pub fn parse(s: &str) -> Result<Patch, String> {
+        let mut lines = s.lines();
+        if lines.next() != Some(PATCH_HEADER) {
+            return Err("not a teehee patch".to_owned());
+        }
+
+        let mut steps = vec![];
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let mut edits = vec![];
+            for token in line.split_whitespace() {
+                let (range, bytes) = token
+                    .split_once('=')
+                    .ok_or_else(|| format!("invalid edit {}", token))?;
+                let (start, end) = range
+                    .split_once('-')
+                    .ok_or_else(|| format!("invalid range {}", range))?;
+                // A deletion inserts nothing
+                let inserted = if bytes.is_empty() {
+                    vec![]
+                } else {
+                    parse_hex_bytes(bytes)?
+                };
+                edits.push(Edit {
+                    start: parse_hex(start)?,
+                    end: parse_hex(end)?,
+                    inserted,
+                });
+            }
+            steps.push(edits);
+        }
+        Ok(Patch { steps })
+    }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", PATCH_HEADER)?;
+        for step in &self.steps {
+            for (i, edit) in step.iter().enumerate() {
+                if i != 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{:x}-{:x}=", edit.start, edit.end)?;
+                for byte in &edit.inserted {
+                    write!(f, "{:02x}", byte)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_replay() {
+        let base_rope: Rope = vec![0, 1, 2, 3].into();
+        let mut builder = DeltaBuilder::new(base_rope.len());
+        builder.replace(1..2, Rope::from(vec![5, 6]).into_node());
+        builder.delete(3..4);
+        let delta = builder.build();
+
+        let mut patch = Patch::new();
+        patch.record(&delta);
+        let parsed = Patch::parse(&patch.to_string()).unwrap();
+        assert_eq!(parsed, patch);
+
+        let deltas = parsed.to_deltas(base_rope.len()).unwrap();
+        let replayed = base_rope.apply_delta(&deltas[0]);
+        assert_eq!(&replayed.slice_to_cow(..), &vec![0, 5, 6, 2]);
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let patch = Patch::parse("teehee-patch 1\n10-11=00\n").unwrap();
+        assert!(patch.to_deltas(4).is_err());
+    }
+}
@@ -67,25 +67,50 @@ impl Selection {
         self.regions[self.main_selection]
     }
 
+    // Index of the first region ending at or after `offset`, or the number of regions if there
+    // is none
     fn search(&self, offset: usize) -> usize {
-        if offset > self.regions.last().unwrap().max() {
-            return self.regions.len();
+        match self.regions.last() {
+            Some(last) if offset <= last.max() => self
+                .regions
+                .binary_search_by(|r| r.max().cmp(&offset))
+                .unwrap_or_else(std::convert::identity),
+            _ => self.regions.len(),
         }
-        self.regions
-            .binary_search_by(|r| r.max().cmp(&offset))
-            .unwrap_or_else(std::convert::identity)
     }
 
+    // The regions overlapping `start..=end`, in order. Empty if `start` is after `end`.
     pub fn regions_in_range(&self, start: usize, end: usize) -> &[SelRegion] {
+        if start > end {
+            return &[];
+        }
         let first = self.search(start);
         let mut last = self.search(end);
-        if last < self.regions.len() && self.regions[last].min() <= end {
+        if self
+            .regions
+            .get(last)
+            .is_some_and(|region| region.min() <= end)
+        {
             last += 1;
         }
-        &self.regions[first..last]
+        self.regions.get(first..last).unwrap_or(&[])
     }
 
+    // Moves the regions through the delta. Offsets after a deletion move back by its length, and
+    // offsets inside it land at its start, on the first byte after the deleted ones. So a region
+    // that is deleted entirely becomes a cursor there, while the carets of the other regions
+    // keep pointing at the same bytes. Overlapping regions are merged afterwards.
     pub fn apply_delta(&mut self, delta: &RopeDelta, max_len: usize) {
+        self.transform_regions(delta, max_len, false);
+    }
+
+    // Like apply_delta, but a bare cursor past the end grows over the bytes typed at it, so that
+    // they can be backspaced in insert mode
+    pub fn apply_typed_delta(&mut self, delta: &RopeDelta, max_len: usize) {
+        self.transform_regions(delta, max_len, true);
+    }
+
+    fn transform_regions(&mut self, delta: &RopeDelta, max_len: usize, grow_end_cursor: bool) {
         let new_max_len = delta.new_document_len();
         if new_max_len == 0 {
             self.clear();
@@ -100,13 +125,37 @@ impl Selection {
                 } else {
                     std::cmp::min(new_max_len, transformer.transform(region.caret, true))
                 },
-                if max_len == region.tail {
+                if grow_end_cursor && max_len == region.tail && max_len == region.caret {
+                    std::cmp::min(new_max_len, transformer.transform(region.tail, false))
+                } else if max_len == region.tail {
                     new_max_len
                 } else {
                     std::cmp::min(new_max_len, transformer.transform(region.tail, true))
                 },
             );
-            vec![new_region]
+            vec![new_region.keep_direction_of(&region)]
+        })
+    }
+
+    // Like apply_delta, but a region keeps covering whatever replaced the bytes at its ends, so
+    // that it grows and shrinks with the replacements inside it
+    pub fn apply_delta_covering(&mut self, delta: &RopeDelta, max_len: usize) {
+        let new_max_len = delta.new_document_len();
+        if new_max_len == 0 {
+            self.clear();
+            return;
+        }
+
+        let mut transformer = Transformer::new(delta);
+        self.map_selections(|region| {
+            let min = cmp::min(new_max_len, transformer.transform(region.min(), false));
+            let max = if region.max() >= max_len {
+                new_max_len
+            } else {
+                let end = transformer.transform(region.max() + 1, true);
+                cmp::min(new_max_len, cmp::max(min, end.saturating_sub(1)))
+            };
+            vec![SelRegion::new(max, min).keep_direction_of(&region)]
         })
     }
 
@@ -144,13 +193,17 @@ impl Selection {
                     )
                 },
             );
-            vec![new_region]
+            vec![new_region.keep_direction_of(&region)]
         })
     }
 
+    // Replaces every region with the regions `f` maps it to, merging the ones that overlap.
+    // The main selection becomes the first region mapped from the old main one. If the old main
+    // one was mapped to nothing, it becomes the first region mapped from a later one, or the last
+    // region if there are none. When no regions are left at all, the selection stays as it was.
     pub fn map_selections(&mut self, mut f: impl FnMut(SelRegion) -> Vec<SelRegion>) {
         let mut regions_out: Vec<SelRegion> = vec![];
-        let mut new_main_sel = 0;
+        let mut new_main_sel = None;
         for (i, region) in self.regions.iter().copied().enumerate() {
             for new_region in f(region) {
                 if regions_out.is_empty() || !regions_out.last().unwrap().overlaps(&new_region) {
@@ -158,20 +211,117 @@ impl Selection {
                 } else if let Some(last) = regions_out.pop() {
                     regions_out.push(last.merge(&new_region));
                 }
+                if i >= self.main_selection && new_main_sel.is_none() {
+                    new_main_sel = Some(regions_out.len() - 1);
+                }
             }
-            if i == self.main_selection {
-                new_main_sel = regions_out.len() - 1;
-                regions_out.last_mut().unwrap().main = true;
-            }
+        }
+        if regions_out.is_empty() {
+            return;
+        }
+        let new_main_sel = new_main_sel.unwrap_or(regions_out.len() - 1);
+        for (i, region) in regions_out.iter_mut().enumerate() {
+            region.main = i == new_main_sel;
         }
         self.regions = regions_out;
         self.main_selection = new_main_sel;
     }
 
+    // Each region on a line of inclusive `start end` hex offsets, for other tools to read
+    pub fn to_ranges_text(&self) -> String {
+        self.regions
+            .iter()
+            .map(|region| format!("{:x} {:x}\n", region.min(), region.max()))
+            .collect()
+    }
+
+    // Reads lines like those of `to_ranges_text` into regions clamped to a buffer of `len`
+    // bytes. Also counts the lines that were dropped because they couldn't be read or start
+    // past the end of the buffer. The regions are sorted but may overlap.
+    pub fn parse_ranges_text(text: &str, len: usize) -> (Vec<SelRegion>, usize) {
+        let parse_offset = |offset: &str| {
+            let digits = offset.strip_prefix("0x").unwrap_or(offset);
+            if digits.chars().all(|ch| ch.is_ascii_hexdigit()) {
+                usize::from_str_radix(digits, 16).ok()
+            } else {
+                None
+            }
+        };
+        let mut regions = vec![];
+        let mut dropped = 0;
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let mut offsets = line.split_whitespace().map(parse_offset);
+            match (offsets.next(), offsets.next(), offsets.next()) {
+                (Some(Some(start)), Some(Some(end)), None) if cmp::min(start, end) < len => {
+                    let (start, end) = (cmp::min(start, end), cmp::max(start, end));
+                    regions.push(SelRegion::new(cmp::min(end, len - 1), start));
+                }
+                _ => dropped += 1,
+            }
+        }
+        regions.sort_by_key(|region| (region.min(), region.max()));
+        (regions, dropped)
+    }
+
+    pub fn add_region(&mut self, region: SelRegion) {
+        let intersects = |r: &SelRegion| r.max() >= region.min() && r.min() <= region.max();
+        let intersecting: Vec<SelRegion> =
+            self.regions.iter().copied().filter(intersects).collect();
+        if let (Some(first), Some(last)) = (intersecting.first(), intersecting.last()) {
+            // Widen every intersecting region to the union so that map_selections merges them.
+            // They all take the direction of the first one, as merging requires matching directions.
+            let span = SelRegion::new(
+                cmp::max(region.max(), last.max()),
+                cmp::min(region.min(), first.min()),
+            )
+            .inherit_direction(first);
+            self.map_selections(|existing| {
+                if intersects(&existing) {
+                    vec![span]
+                } else {
+                    vec![existing]
+                }
+            });
+        } else {
+            // Otherwise insert the new region in order
+            let mut pending = Some(region);
+            let mut remaining = self.regions.len();
+            self.map_selections(|existing| {
+                remaining -= 1;
+                match pending {
+                    Some(new) if new.min() < existing.min() => {
+                        pending = None;
+                        vec![new, existing]
+                    }
+                    Some(new) if remaining == 0 => vec![existing, new],
+                    _ => vec![existing],
+                }
+            });
+        }
+
+        // The added region becomes the main selection
+        for r in self.regions.iter_mut() {
+            r.main = false;
+        }
+        self.main_selection = self.search(region.min());
+        self.regions[self.main_selection].main = true;
+    }
+
     pub fn len(&self) -> usize {
         self.regions.len()
     }
 
+    // Combined length of `count` regions, starting from the main one and wrapping around
+    pub fn len_bytes_from_main(&self, count: usize) -> usize {
+        self.regions
+            .iter()
+            .cycle()
+            .skip(self.main_selection)
+            .take(cmp::min(count, self.regions.len()))
+            .map(SelRegion::len)
+            .sum()
+    }
+
     pub fn main_cursor_offset(&self) -> usize {
         self.regions[self.main_selection].caret
     }
@@ -180,12 +330,38 @@ impl Selection {
         self.regions.iter()
     }
 
+    // The lowest offset covered by a region that isn't in both selections, including regions
+    // that only stopped or started being the main one
+    pub fn first_difference(&self, other: &Selection) -> Option<usize> {
+        let common = cmp::min(self.regions.len(), other.regions.len());
+        match (0..common).find(|&i| self.regions[i] != other.regions[i]) {
+            Some(i) => Some(cmp::min(self.regions[i].min(), other.regions[i].min())),
+            None => self.regions[common..]
+                .first()
+                .or_else(|| other.regions[common..].first())
+                .map(SelRegion::min),
+        }
+    }
+
     pub fn select_next(&mut self, count: usize) {
         self.regions[self.main_selection].main = false;
         self.main_selection = (self.main_selection + count) % self.regions.len();
         self.regions[self.main_selection].main = true;
     }
 
+    pub fn select_index(&mut self, index: usize) {
+        self.regions[self.main_selection].main = false;
+        self.main_selection = index;
+        self.regions[self.main_selection].main = true;
+    }
+
+    // Makes the first selection ending at or after `offset` the main one
+    pub fn select_at(&mut self, offset: usize) {
+        self.regions[self.main_selection].main = false;
+        self.main_selection = cmp::min(self.search(offset), self.regions.len() - 1);
+        self.regions[self.main_selection].main = true;
+    }
+
     pub fn select_prev(&mut self, count: usize) {
         self.regions[self.main_selection].main = false;
         self.main_selection = (self.main_selection + self.regions.len()
@@ -321,25 +497,48 @@ impl SelRegion {
         SelRegion::new(offset, self.tail)
     }
 
+    // Without a count, Up and Down go to the file start and end. With a count, they go to the
+    // first byte of the row `count` rows up or the last byte of the row `count` rows down.
+    // Left and Right go to the start or end of the line, or with a count, of the line
+    // `count - 1` rows away (like `$` in vim).
+    fn boundary(
+        &self,
+        direction: Direction,
+        bytes_per_line: usize,
+        max_size: usize,
+        count: Option<usize>,
+    ) -> usize {
+        let row_start = self.caret - (self.caret % bytes_per_line);
+        let rows = |n: usize| n.saturating_mul(bytes_per_line);
+        let location = match (direction, count) {
+            (Direction::Up, None) => 0,
+            (Direction::Down, None) => max_size - 1,
+            (Direction::Up, Some(count)) => row_start.saturating_sub(rows(count)),
+            (Direction::Down, Some(count)) => {
+                row_start.saturating_add(rows(count.saturating_add(1))) - 1
+            }
+            (Direction::Left, count) => {
+                row_start.saturating_sub(rows(count.unwrap_or(1).saturating_sub(1)))
+            }
+            (Direction::Right, count) => {
+                row_start.saturating_add(rows(cmp::max(1, count.unwrap_or(1)))) - 1
+            }
+        };
+        cmp::min(location, max_size - 1) // Don't do overflow selection in jumps
+    }
+
     pub fn jump_to_boundary(
         &self,
         direction: Direction,
         bytes_per_line: usize,
         max_size: usize,
+        count: Option<usize>,
     ) -> SelRegion {
         if max_size == 0 {
             return *self;
         }
 
-        let caret_location = match direction {
-            Direction::Up => 0,
-            Direction::Down => max_size - 1, // Don't do overflow selection in jumps
-            Direction::Left => self.caret - (self.caret % bytes_per_line),
-            Direction::Right => std::cmp::min(
-                self.caret + bytes_per_line - (self.caret % bytes_per_line) - 1,
-                max_size - 1,
-            ),
-        };
+        let caret_location = self.boundary(direction, bytes_per_line, max_size, count);
         SelRegion::new(caret_location, caret_location)
     }
 
@@ -348,23 +547,55 @@ impl SelRegion {
         direction: Direction,
         bytes_per_line: usize,
         max_size: usize,
+        count: Option<usize>,
     ) -> SelRegion {
         if max_size == 0 {
             return *self;
         }
 
-        let caret_location = match direction {
-            Direction::Up => 0,
-            Direction::Down => max_size - 1, // Don't do overflow selection in jumps
-            Direction::Left => self.caret - (self.caret % bytes_per_line),
-            Direction::Right => std::cmp::min(
-                self.caret + bytes_per_line - (self.caret % bytes_per_line) - 1,
-                max_size - 1,
-            ),
-        };
+        let caret_location = self.boundary(direction, bytes_per_line, max_size, count);
         SelRegion::new(caret_location, self.tail)
     }
 
+    pub fn select_rows(&self, bytes_per_line: usize, max_size: usize, count: usize) -> SelRegion {
+        if max_size == 0 {
+            return *self;
+        }
+
+        // The overflow cursor belongs to the last row that has any data
+        let caret = cmp::min(self.caret, max_size - 1);
+        let row_start = SelRegion::new(caret, caret)
+            .jump_to_boundary(Direction::Left, bytes_per_line, max_size, None)
+            .caret;
+        let row_end = cmp::min(
+            row_start.saturating_add(bytes_per_line.saturating_mul(cmp::max(1, count))) - 1,
+            max_size - 1,
+        );
+        SelRegion::new(row_end, row_start)
+    }
+
+    // Grows the region to cover whole rows, keeping its direction
+    pub fn snap_to_rows(&self, bytes_per_line: usize, max_size: usize) -> SelRegion {
+        if max_size == 0 {
+            return *self;
+        }
+
+        let caret = cmp::min(self.caret, max_size - 1);
+        let tail = cmp::min(self.tail, max_size - 1);
+        let min = cmp::min(caret, tail);
+        let max = cmp::max(caret, tail);
+        let start = min - min % bytes_per_line;
+        let end = cmp::min(
+            max - max % bytes_per_line + bytes_per_line - 1,
+            max_size - 1,
+        );
+        if caret >= tail {
+            SelRegion::new(end, start)
+        } else {
+            SelRegion::new(start, end)
+        }
+    }
+
     pub fn swap_caret(&self) -> SelRegion {
         SelRegion::new(self.tail, self.caret)
     }
@@ -416,6 +647,15 @@ impl SelRegion {
         merged
     }
 
+    // Like inherit_direction, but cursors (which have no direction) leave self untouched
+    pub fn keep_direction_of(self, original: &SelRegion) -> SelRegion {
+        if original.caret == original.tail {
+            self
+        } else {
+            self.with_direction(original.caret < original.tail)
+        }
+    }
+
     pub fn inherit_direction(&self, parent: &SelRegion) -> SelRegion {
         if parent.forward() {
             self.to_forward()
@@ -457,3 +697,214 @@ impl From<SelRegion> for Interval {
         (sel_region.min()..=sel_region.max()).into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::byte_rope::Rope;
+    use std::ops::Range;
+    use xi_rope::DeltaBuilder;
+
+    fn selection_with(region: SelRegion) -> Selection {
+        let mut selection = Selection::new();
+        selection.map_selections(|_| vec![region]);
+        selection
+    }
+
+    fn delete(len: usize, start: usize, end: usize) -> RopeDelta {
+        let mut builder = DeltaBuilder::new(len);
+        builder.delete(start..end);
+        builder.build()
+    }
+
+    fn caret_and_tail(selection: &Selection) -> (usize, usize) {
+        let main = selection.main();
+        (main.caret, main.tail)
+    }
+
+    #[test]
+    fn test_delete_inside_backward_selection() {
+        let mut selection = selection_with(SelRegion::new(2, 6));
+        selection.apply_delta(&delete(10, 3, 5), 10);
+        assert_eq!(caret_and_tail(&selection), (2, 4));
+        assert!(selection.main().backward());
+    }
+
+    #[test]
+    fn test_delete_before_backward_selection() {
+        let mut selection = selection_with(SelRegion::new(2, 6));
+        selection.apply_delta(&delete(10, 0, 2), 10);
+        assert_eq!(caret_and_tail(&selection), (0, 4));
+
+        let mut selection = selection_with(SelRegion::new(2, 6));
+        selection.apply_delta(&delete(10, 1, 3), 10);
+        assert_eq!(caret_and_tail(&selection), (1, 4));
+    }
+
+    #[test]
+    fn test_delete_after_backward_selection() {
+        let mut selection = selection_with(SelRegion::new(2, 6));
+        selection.apply_delta(&delete(10, 7, 9), 10);
+        assert_eq!(caret_and_tail(&selection), (2, 6));
+
+        let mut selection = selection_with(SelRegion::new(2, 6));
+        selection.apply_delta(&delete(10, 6, 8), 10);
+        assert_eq!(caret_and_tail(&selection), (2, 6));
+    }
+
+    #[test]
+    fn test_delete_at_forward_caret() {
+        let mut selection = selection_with(SelRegion::new(6, 2));
+        selection.apply_delta(&delete(10, 6, 8), 10);
+        assert_eq!(caret_and_tail(&selection), (6, 2));
+        assert!(selection.main().forward());
+    }
+
+    #[test]
+    fn test_crossing_offsets_keep_direction() {
+        let mut builder = DeltaBuilder::new(10);
+        builder.replace(3..3, Rope::from(vec![0, 0]).into_node());
+        let delta = builder.build();
+
+        let mut selection = selection_with(SelRegion::new(2, 3));
+        selection.apply_delta_offset_carets(&delta, 4, 0, 10);
+        assert_eq!(caret_and_tail(&selection), (5, 6));
+        assert!(selection.main().backward());
+    }
+
+    #[test]
+    fn test_ranges_text() {
+        let mut selection = Selection::new();
+        selection.map_selections(|_| vec![SelRegion::new(2, 0), SelRegion::new(0x10, 0x1f)]);
+        let text = selection.to_ranges_text();
+        assert_eq!(text, "0 2\n10 1f\n");
+        let (regions, dropped) = Selection::parse_ranges_text(&text, 0x20);
+        assert_eq!(
+            regions
+                .iter()
+                .map(|r| (r.min(), r.max()))
+                .collect::<Vec<_>>(),
+            vec![(0, 2), (0x10, 0x1f)]
+        );
+        assert_eq!(dropped, 0);
+
+        // Clamped, reordered, and invalid or out of range lines dropped
+        let (regions, dropped) =
+            Selection::parse_ranges_text("0x8 0x40\n3 1\nzz 4\n5\n30 31\n\n", 0x10);
+        assert_eq!(
+            regions
+                .iter()
+                .map(|r| (r.min(), r.max()))
+                .collect::<Vec<_>>(),
+            vec![(1, 3), (8, 0xf)]
+        );
+        assert_eq!(dropped, 3);
+    }
+
+    fn regions_after_deleting(
+        regions: Vec<SelRegion>,
+        deleted: Range<usize>,
+    ) -> Vec<(usize, usize)> {
+        let mut selection = Selection::new();
+        let mut regions = Some(regions);
+        selection.map_selections(|_| regions.take().unwrap_or_default());
+        selection.apply_delta(&delete(0x10, deleted.start, deleted.end), 0x10);
+        selection
+            .iter()
+            .map(|region| (region.caret, region.tail))
+            .collect()
+    }
+
+    #[test]
+    fn test_carets_after_deleting_first_selection() {
+        let regions = || {
+            vec![
+                SelRegion::new(3, 0),
+                SelRegion::new(6, 7),
+                SelRegion::new(12, 10),
+            ]
+        };
+        // The deleted selection becomes a cursor at the start of the deletion, and the others
+        // move back by its length
+        assert_eq!(
+            regions_after_deleting(regions(), 0..4),
+            vec![(0, 0), (2, 3), (8, 6)]
+        );
+        // Deleting the first two selections merges their cursors
+        assert_eq!(
+            regions_after_deleting(regions(), 0..7),
+            vec![(0, 0), (5, 3)]
+        );
+    }
+
+    #[test]
+    fn test_deleting_end_of_selection() {
+        // The deleted end of a selection lands on the byte after the deletion, like a caret in
+        // the deleted bytes would
+        assert_eq!(
+            regions_after_deleting(vec![SelRegion::new(2, 5), SelRegion::new(12, 10)], 3..7),
+            vec![(2, 3), (8, 6)]
+        );
+        assert_eq!(
+            regions_after_deleting(vec![SelRegion::new(5, 2), SelRegion::new(12, 10)], 3..7),
+            vec![(3, 2), (8, 6)]
+        );
+    }
+
+    // A xorshift generator, so that the random cases are the same on every run
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+    }
+
+    #[test]
+    fn test_regions_in_range_matches_overlapping_regions() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            let mut offsets = (0..rng.below(8) * 2)
+                .map(|_| rng.below(0x20) * 2)
+                .collect::<Vec<_>>();
+            // Regions with gaps between them, so that none of them get merged
+            offsets.sort_unstable();
+            offsets.dedup();
+            let mut regions = offsets
+                .chunks_exact(2)
+                .map(|pair| {
+                    if rng.below(2) == 0 {
+                        SelRegion::new(pair[0], pair[1])
+                    } else {
+                        SelRegion::new(pair[1], pair[0])
+                    }
+                })
+                .collect::<Vec<_>>();
+            if regions.is_empty() {
+                regions.push(SelRegion::new(rng.below(0x40), rng.below(0x40)));
+            }
+            let mut selection = Selection::new();
+            selection.map_selections(|_| std::mem::take(&mut regions));
+
+            for _ in 0..20 {
+                let (start, end) = (rng.below(0x50), rng.below(0x50));
+                let expected = selection
+                    .iter()
+                    .filter(|region| start <= end && region.min() <= end && region.max() >= start)
+                    .copied()
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    selection.regions_in_range(start, end),
+                    &expected[..],
+                    "{:?} in {}..={}",
+                    selection.iter().collect::<Vec<_>>(),
+                    start,
+                    end
+                );
+            }
+        }
+    }
+}